@@ -0,0 +1,5 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/propagation.proto")
+        .expect("Compiling proto/propagation.proto failed, is protoc installed?");
+}
@@ -0,0 +1,575 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use acme_validation_propagation::{dns_value, LookupIpStrategy, ResolverType};
+
+use crate::config::Defaults;
+
+const LISTEN: &str = "127.0.0.1:8053";
+
+pub const USAGE: &str = "\
+Usage: acme-propagation wait --domain <domain> --challenge <value> [options]
+       acme-propagation present <fqdn> <value> [options]
+       acme-propagation present -- <domain> <token> <key authorization> [options]
+       acme-propagation cleanup <fqdn> <value> [options]
+       acme-propagation explain <domain> [--resolver <resolver>] [--ip-strategy <ip>] [--json]
+       acme-propagation serve [--listen <address>] [--resolver <resolver>] [--verbose | --json-log]
+       acme-propagation --stdio [--resolver <resolver>] [--verbose | --json-log]
+       acme-propagation grpc [--listen <address>] [--resolver <resolver>] [--verbose | --json-log]
+
+wait      Waits until all authoritive nameservers of the domain serve the acme challenge.
+present   Same as wait, with the arguments of the exec provider of lego: the full name
+          of the challenge record and its value, or in raw mode the domain, the token
+          and the key authorization. add is an alias, for acme.sh dns hooks.
+cleanup   Waits until none of the authoritive nameservers serves the challenge anymore.
+          rm is an alias.
+explain   Prints the delegation chain of the zone, the authoritive nameservers with
+          their addresses and the TXT values each of them serves for the challenge record,
+          to find out why waiting times out.
+serve     Runs the waits submitted over http concurrently. POST /jobs with a json object
+          with the domain, the challenge and optionally timeout, record, absent and
+          resolver starts a wait and returns its id. GET /jobs/<id> returns the status
+          of the wait: waiting, done or failed, with the result as printed by --json.
+          With the metrics feature GET /metrics returns the metrics for prometheus.
+--stdio   Speaks line delimited JSON-RPC 2.0 on stdin and stdout. The wait method takes
+          the same parameters as a job of serve, sends progress notifications while
+          waiting and answers with the result. Waits run concurrently.
+grpc      Serves the Wait, WaitAbsent and Diagnose calls of proto/propagation.proto.
+          Only available with the grpc feature.
+
+Options:
+  --domain <domain>      Domain name the challenge is for
+  --challenge <value>    Value of the challenge TXT record
+  --timeout <seconds>    Give up after this many seconds, defaults to 600
+  --interval <seconds>   Time between two attempts, defaults to 5
+  --resolver <resolver>  google, cloudflare, quad9, opendns, adguard, local, system
+                         or a comma separated list of addresses, defaults to google
+  --ip-strategy <ip>     ipv4, ipv6, ipv4-and-ipv6, ipv6-then-ipv4 or ipv4-then-ipv6,
+                         the addresses of the nameservers to check
+  --config <file>        Config file, defaults to /etc/acme-propagation.toml
+  --json                 Print the result as json
+  --listen <address>     Address serve and grpc listen on, defaults to 127.0.0.1:8053
+  --verbose              Log the progress to stderr
+  --json-log             Log the progress to stderr as json, one object per line
+  --help                 Print this help
+
+The config file can set resolver, timeout, interval and ip_strategy.
+The environment variables ACME_PROPAGATION_RESOLVER, ACME_PROPAGATION_TIMEOUT,
+ACME_PROPAGATION_INTERVAL and ACME_PROPAGATION_IP_STRATEGY override the config file,
+ACME_PROPAGATION_CONFIG names the config file. The options override both.
+
+Exits with 0 when the challenge is propagated, 1 when waiting failed
+and 2 when the arguments are invalid.";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Wait(WaitArgs),
+    Serve(ServeArgs),
+    Stdio(StdioArgs),
+    Explain(ExplainArgs),
+    #[cfg(feature = "grpc")]
+    Grpc(ServeArgs),
+    Help,
+}
+
+/// Where the progress is logged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Logging {
+    Off,
+    /// Readable lines on stderr
+    Text,
+    /// Json objects on stderr, one per line
+    Json,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct WaitArgs {
+    pub domain: String,
+    pub challenge: String,
+    /// The domain is the full name of the challenge record
+    pub is_record: bool,
+    /// Wait until the challenge is no longer served
+    pub absent: bool,
+    pub timeout: Duration,
+    pub resolver: ResolverType,
+    /// Time between two attempts, the default of the library when not set
+    pub interval: Option<Duration>,
+    pub ip_strategy: Option<LookupIpStrategy>,
+    pub json: bool,
+    pub logging: Logging,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ServeArgs {
+    pub listen: SocketAddr,
+    /// Settings of the jobs that do not choose them
+    pub defaults: Defaults,
+    pub logging: Logging,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct StdioArgs {
+    /// Settings of the waits that do not choose them
+    pub defaults: Defaults,
+    pub logging: Logging,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExplainArgs {
+    pub domain: String,
+    pub resolver: ResolverType,
+    pub ip_strategy: Option<LookupIpStrategy>,
+    pub json: bool,
+}
+
+/// Parses the arguments without the name of the program,
+/// the defaults are used for the options that are not given.
+pub fn parse<I>(args: I, defaults: &Defaults) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("wait") => wait(args, defaults, false, false).map(Command::Wait),
+        Some("present" | "add") => wait(args, defaults, true, false).map(Command::Wait),
+        Some("cleanup" | "rm") => wait(args, defaults, true, true).map(Command::Wait),
+        Some("serve") => serve(args, defaults).map(Command::Serve),
+        Some("--stdio") => stdio(args, defaults).map(Command::Stdio),
+        Some("explain") => explain(args, defaults).map(Command::Explain),
+        #[cfg(feature = "grpc")]
+        Some("grpc") => serve(args, defaults).map(Command::Grpc),
+        Some("--help" | "-h" | "help") | None => Ok(Command::Help),
+        Some(command) => Err(format!("Unknown command {command}")),
+    }
+}
+
+fn wait<I>(
+    mut args: I,
+    defaults: &Defaults,
+    positional: bool,
+    absent: bool,
+) -> Result<WaitArgs, String>
+where
+    I: Iterator<Item = String>,
+{
+    let mut domain = None;
+    let mut challenge = None;
+    let mut positionals = vec![];
+    let mut timeout = defaults.timeout;
+    let mut resolver = defaults.resolver.clone();
+    let mut interval = defaults.interval;
+    let mut ip_strategy = defaults.ip_strategy;
+    let mut json = false;
+    let mut logging = Logging::Off;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--domain" => domain = Some(value()?),
+            "--challenge" => challenge = Some(value()?),
+            "--timeout" => timeout = parse_seconds("timeout", &value()?)?,
+            "--interval" => interval = Some(parse_seconds("interval", &value()?)?),
+            "--resolver" => resolver = parse_resolver(&value()?)?,
+            "--ip-strategy" => ip_strategy = Some(parse_ip_strategy(&value()?)?),
+            "--config" => {
+                value()?;
+            }
+            "--json" => json = true,
+            "--verbose" => logging = Logging::Text,
+            "--json-log" => logging = Logging::Json,
+            "--" if positional => {}
+            _ if positional && !arg.starts_with("--") => positionals.push(arg),
+            _ => return Err(format!("Unknown option {arg}")),
+        }
+    }
+    let is_record = positionals.len() == 2;
+    match positionals.as_slice() {
+        [] => {}
+        [fqdn, value] => {
+            domain = Some(fqdn.clone());
+            challenge = Some(value.clone());
+        }
+        [domain_name, _token, key_authorization] => {
+            domain = Some(domain_name.clone());
+            challenge = Some(dns_value(key_authorization));
+        }
+        _ => return Err(format!("Unexpected arguments {}", positionals.join(" "))),
+    }
+    Ok(WaitArgs {
+        domain: domain.ok_or("Missing --domain")?,
+        challenge: challenge.ok_or("Missing --challenge")?,
+        is_record,
+        absent,
+        timeout,
+        resolver,
+        interval,
+        ip_strategy,
+        json,
+        logging,
+    })
+}
+
+fn explain<I>(mut args: I, defaults: &Defaults) -> Result<ExplainArgs, String>
+where
+    I: Iterator<Item = String>,
+{
+    let mut domain = None;
+    let mut resolver = defaults.resolver.clone();
+    let mut ip_strategy = defaults.ip_strategy;
+    let mut json = false;
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--domain" => domain = Some(value()?),
+            "--resolver" => resolver = parse_resolver(&value()?)?,
+            "--ip-strategy" => ip_strategy = Some(parse_ip_strategy(&value()?)?),
+            "--config" => {
+                value()?;
+            }
+            "--json" => json = true,
+            _ if domain.is_none() && !arg.starts_with("--") => domain = Some(arg),
+            _ => return Err(format!("Unknown option {arg}")),
+        }
+    }
+    Ok(ExplainArgs {
+        domain: domain.ok_or("Missing domain")?,
+        resolver,
+        ip_strategy,
+        json,
+    })
+}
+
+fn serve<I>(mut args: I, defaults: &Defaults) -> Result<ServeArgs, String>
+where
+    I: Iterator<Item = String>,
+{
+    let mut serve = ServeArgs {
+        listen: LISTEN
+            .parse()
+            .map_err(|_| format!("Invalid address {LISTEN}"))?,
+        defaults: defaults.clone(),
+        logging: Logging::Off,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--listen" => {
+                let value = value()?;
+                serve.listen = value
+                    .parse()
+                    .map_err(|_| format!("Invalid address {value}"))?;
+            }
+            "--resolver" => serve.defaults.resolver = parse_resolver(&value()?)?,
+            "--config" => {
+                value()?;
+            }
+            "--verbose" => serve.logging = Logging::Text,
+            "--json-log" => serve.logging = Logging::Json,
+            _ => return Err(format!("Unknown option {arg}")),
+        }
+    }
+    Ok(serve)
+}
+
+fn stdio<I>(mut args: I, defaults: &Defaults) -> Result<StdioArgs, String>
+where
+    I: Iterator<Item = String>,
+{
+    let mut stdio = StdioArgs {
+        defaults: defaults.clone(),
+        logging: Logging::Off,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--resolver" => stdio.defaults.resolver = parse_resolver(&value()?)?,
+            "--config" => {
+                value()?;
+            }
+            "--verbose" => stdio.logging = Logging::Text,
+            "--json-log" => stdio.logging = Logging::Json,
+            _ => return Err(format!("Unknown option {arg}")),
+        }
+    }
+    Ok(stdio)
+}
+
+/// A whole number of seconds.
+pub fn parse_seconds(name: &str, value: &str) -> Result<Duration, String> {
+    value
+        .trim()
+        .parse()
+        .map(Duration::from_secs)
+        .map_err(|_| format!("Invalid {name} {value}"))
+}
+
+pub fn parse_ip_strategy(value: &str) -> Result<LookupIpStrategy, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "ipv4" => Ok(LookupIpStrategy::Ipv4Only),
+        "ipv6" => Ok(LookupIpStrategy::Ipv6Only),
+        "ipv4-and-ipv6" => Ok(LookupIpStrategy::Ipv4AndIpv6),
+        "ipv6-then-ipv4" => Ok(LookupIpStrategy::Ipv6thenIpv4),
+        "ipv4-then-ipv6" => Ok(LookupIpStrategy::Ipv4thenIpv6),
+        _ => Err(format!("Invalid ip strategy {value}")),
+    }
+}
+
+/// A resolver by name, or a custom resolver from a comma separated list of addresses.
+pub fn parse_resolver(value: &str) -> Result<ResolverType, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "google" => Ok(ResolverType::Google),
+        "cloudflare" => Ok(ResolverType::Cloudflare),
+        "quad9" => Ok(ResolverType::Quad9),
+        "opendns" => Ok(ResolverType::OpenDNS),
+        "adguard" => Ok(ResolverType::AdGuard),
+        "local" => Ok(ResolverType::Local),
+        "system" => Ok(ResolverType::System),
+        addresses => addresses
+            .split(',')
+            .map(|address| address.trim().parse::<IpAddr>())
+            .collect::<Result<Vec<_>, _>>()
+            .map(ResolverType::Custom)
+            .map_err(|_| format!("Invalid resolver {value}")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use acme_validation_propagation::{dns_value, LookupIpStrategy, ResolverType};
+
+    use super::{Command, ExplainArgs, Logging, ServeArgs, StdioArgs, WaitArgs};
+    use crate::config::Defaults;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn parse(args: Vec<String>) -> Result<Command, String> {
+        super::parse(args, &Defaults::default())
+    }
+
+    fn wait_args(arguments: &[&str]) -> WaitArgs {
+        match parse(args(arguments)) {
+            Ok(Command::Wait(wait)) => wait,
+            other => panic!("not a wait command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wait_with_options() {
+        assert_eq!(
+            parse(args(&[
+                "wait",
+                "--domain",
+                "paulmin.nl",
+                "--challenge",
+                "JaJaNeeNee",
+                "--timeout",
+                "60",
+                "--resolver",
+                "Cloudflare",
+                "--json",
+            ])),
+            Ok(Command::Wait(WaitArgs {
+                domain: "paulmin.nl".to_owned(),
+                challenge: "JaJaNeeNee".to_owned(),
+                is_record: false,
+                absent: false,
+                timeout: Duration::from_secs(60),
+                resolver: ResolverType::Cloudflare,
+                interval: None,
+                ip_strategy: None,
+                json: true,
+                logging: Logging::Off,
+            }))
+        );
+    }
+
+    #[test]
+    fn custom_resolver() {
+        let wait = wait_args(&[
+            "wait",
+            "--domain",
+            "paulmin.nl",
+            "--challenge",
+            "JaJaNeeNee",
+            "--resolver",
+            "9.9.9.9, 2620:fe::fe",
+        ]);
+        assert_eq!(
+            wait.resolver,
+            ResolverType::Custom(vec![
+                "9.9.9.9".parse().unwrap(),
+                "2620:fe::fe".parse().unwrap()
+            ])
+        );
+        assert_eq!(wait.timeout, Duration::from_secs(600));
+    }
+
+    #[test]
+    fn options_override_defaults() {
+        let defaults = Defaults {
+            timeout: Duration::from_secs(300),
+            resolver: ResolverType::Quad9,
+            interval: Some(Duration::from_secs(10)),
+            ip_strategy: Some(LookupIpStrategy::Ipv6Only),
+        };
+        let arguments = args(&[
+            "wait",
+            "--config",
+            "/etc/acme-propagation.toml",
+            "--domain",
+            "paulmin.nl",
+            "--challenge",
+            "JaJaNeeNee",
+            "--interval",
+            "2",
+            "--ip-strategy",
+            "ipv4",
+        ]);
+        match super::parse(arguments, &defaults) {
+            Ok(Command::Wait(wait)) => {
+                assert_eq!(wait.timeout, Duration::from_secs(300));
+                assert_eq!(wait.resolver, ResolverType::Quad9);
+                assert_eq!(wait.interval, Some(Duration::from_secs(2)));
+                assert_eq!(wait.ip_strategy, Some(LookupIpStrategy::Ipv4Only));
+            }
+            other => panic!("not a wait command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lego_exec_provider() {
+        let present = wait_args(&["present", "_acme-challenge.paulmin.nl.", "JaJaNeeNee"]);
+        assert_eq!(present.domain, "_acme-challenge.paulmin.nl.");
+        assert_eq!(present.challenge, "JaJaNeeNee");
+        assert!(present.is_record);
+        assert!(!present.absent);
+
+        let cleanup = wait_args(&["cleanup", "_acme-challenge.paulmin.nl.", "JaJaNeeNee"]);
+        assert!(cleanup.is_record);
+        assert!(cleanup.absent);
+
+        let raw = wait_args(&["present", "--", "paulmin.nl", "token", "token.thumbprint"]);
+        assert_eq!(raw.domain, "paulmin.nl");
+        assert_eq!(raw.challenge, dns_value("token.thumbprint"));
+        assert!(!raw.is_record);
+    }
+
+    #[test]
+    fn acme_sh_hook() {
+        let add = wait_args(&[
+            "add",
+            "_acme-challenge.paulmin.nl",
+            "JaJaNeeNee",
+            "--timeout",
+            "120",
+        ]);
+        assert!(add.is_record);
+        assert_eq!(add.timeout, Duration::from_secs(120));
+        assert!(wait_args(&["rm", "_acme-challenge.paulmin.nl", "JaJaNeeNee"]).absent);
+    }
+
+    #[test]
+    fn serve() {
+        assert_eq!(
+            parse(args(&[
+                "serve",
+                "--listen",
+                "[::1]:8080",
+                "--resolver",
+                "quad9"
+            ])),
+            Ok(Command::Serve(ServeArgs {
+                listen: "[::1]:8080".parse().unwrap(),
+                defaults: Defaults {
+                    resolver: ResolverType::Quad9,
+                    ..Defaults::default()
+                },
+                logging: Logging::Off,
+            }))
+        );
+        assert!(matches!(
+            parse(args(&["serve"])),
+            Ok(Command::Serve(ServeArgs { listen, .. })) if listen.port() == 8053
+        ));
+        assert!(parse(args(&["serve", "--listen", "localhost"])).is_err());
+    }
+
+    #[test]
+    fn stdio() {
+        assert_eq!(
+            parse(args(&["--stdio", "--verbose"])),
+            Ok(Command::Stdio(StdioArgs {
+                defaults: Defaults::default(),
+                logging: Logging::Text,
+            }))
+        );
+        assert!(parse(args(&["--stdio", "--listen", "127.0.0.1:8053"])).is_err());
+        assert!(matches!(
+            parse(args(&["--stdio", "--json-log"])),
+            Ok(Command::Stdio(StdioArgs {
+                logging: Logging::Json,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn explain() {
+        assert_eq!(
+            parse(args(&[
+                "explain",
+                "paulmin.nl",
+                "--resolver",
+                "quad9",
+                "--json"
+            ])),
+            Ok(Command::Explain(ExplainArgs {
+                domain: "paulmin.nl".to_owned(),
+                resolver: ResolverType::Quad9,
+                ip_strategy: None,
+                json: true,
+            }))
+        );
+        assert!(parse(args(&["explain"])).is_err());
+        assert!(parse(args(&["explain", "paulmin.nl", "JaJaNeeNee"])).is_err());
+    }
+
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn grpc() {
+        assert!(matches!(
+            parse(args(&["grpc", "--listen", "[::1]:50051"])),
+            Ok(Command::Grpc(ServeArgs { listen, .. })) if listen.port() == 50051
+        ));
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        assert_eq!(parse(args(&[])), Ok(Command::Help));
+        assert!(parse(args(&["wait", "--domain", "paulmin.nl"])).is_err());
+        assert!(parse(args(&["wait", "--domain"])).is_err());
+        assert!(parse(args(&["wait", "--timeout", "soon"])).is_err());
+        assert!(parse(args(&["wait", "--resolver", "nonexistent"])).is_err());
+        assert!(parse(args(&["wait", "paulmin.nl", "JaJaNeeNee"])).is_err());
+        assert!(parse(args(&["present", "paulmin.nl"])).is_err());
+        assert!(parse(args(&["wait", "--ip-strategy", "ipv5"])).is_err());
+        assert!(parse(args(&["revoke"])).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::{env, fs, path::PathBuf, time::Duration};
+
+use acme_validation_propagation::{LookupIpStrategy, ResolverType};
+
+use crate::args::{parse_ip_strategy, parse_resolver, parse_seconds};
+
+const TIMEOUT_SECONDS: u64 = 600;
+/// Used when there is no --config or ACME_PROPAGATION_CONFIG.
+const CONFIG_FILE: &str = "/etc/acme-propagation.toml";
+const PREFIX: &str = "ACME_PROPAGATION_";
+
+/// Settings used when the flags do not set them. They are read from the config file,
+/// then overridden by the ACME_PROPAGATION_* environment variables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Defaults {
+    pub timeout: Duration,
+    pub resolver: ResolverType,
+    /// Time between two attempts, the default of the library when not set
+    pub interval: Option<Duration>,
+    pub ip_strategy: Option<LookupIpStrategy>,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(TIMEOUT_SECONDS),
+            resolver: ResolverType::default(),
+            interval: None,
+            ip_strategy: None,
+        }
+    }
+}
+
+impl Defaults {
+    /// Reads the config file named by --config in the arguments, ACME_PROPAGATION_CONFIG
+    /// or the default config file when it exists, and the environment variables.
+    pub fn load(args: &[String]) -> Result<Self, String> {
+        let named = args
+            .iter()
+            .position(|arg| arg == "--config")
+            .and_then(|index| args.get(index + 1))
+            .map(PathBuf::from)
+            .or_else(|| env::var_os(format!("{PREFIX}CONFIG")).map(PathBuf::from));
+        let mut defaults = Self::default();
+        let path = named.or_else(|| {
+            let path = PathBuf::from(CONFIG_FILE);
+            path.exists().then_some(path)
+        });
+        if let Some(path) = path {
+            let content = fs::read_to_string(&path)
+                .map_err(|error| format!("Reading {} failed: {error}", path.display()))?;
+            defaults = defaults
+                .with_file(&content)
+                .map_err(|error| format!("Invalid config {}: {error}", path.display()))?;
+        }
+        defaults.with_env(|key| env::var(format!("{PREFIX}{key}")).ok())
+    }
+
+    fn with_file(self, content: &str) -> Result<Self, String> {
+        let table = content
+            .parse::<toml::Table>()
+            .map_err(|error| error.to_string())?;
+        if let Some(key) = table
+            .keys()
+            .find(|key| !["resolver", "timeout", "interval", "ip_strategy"].contains(&key.as_str()))
+        {
+            return Err(format!("Unknown setting {key}"));
+        }
+        self.with(|key| {
+            table
+                .get(&key.to_ascii_lowercase())
+                .map(|value| match value {
+                    toml::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                })
+        })
+    }
+
+    fn with_env<F>(self, lookup: F) -> Result<Self, String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        self.with(|key| lookup(key).filter(|value| !value.is_empty()))
+    }
+
+    /// Overrides the settings found by lookup, the keys are the names of the environment
+    /// variables without prefix.
+    fn with<F>(mut self, lookup: F) -> Result<Self, String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if let Some(resolver) = lookup("RESOLVER") {
+            self.resolver = parse_resolver(&resolver)?;
+        }
+        if let Some(timeout) = lookup("TIMEOUT") {
+            self.timeout = parse_seconds("timeout", &timeout)?;
+        }
+        if let Some(interval) = lookup("INTERVAL") {
+            self.interval = Some(parse_seconds("interval", &interval)?);
+        }
+        if let Some(ip_strategy) = lookup("IP_STRATEGY") {
+            self.ip_strategy = Some(parse_ip_strategy(&ip_strategy)?);
+        }
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use acme_validation_propagation::{LookupIpStrategy, ResolverType};
+
+    use super::Defaults;
+
+    #[test]
+    fn file_then_env() {
+        let defaults = Defaults::default()
+            .with_file("resolver = \"quad9\"\ntimeout = 300\ninterval = 5\n")
+            .unwrap()
+            .with_env(|key| match key {
+                "TIMEOUT" => Some("120".to_owned()),
+                "IP_STRATEGY" => Some("ipv4".to_owned()),
+                "RESOLVER" => Some(String::new()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            defaults,
+            Defaults {
+                timeout: Duration::from_secs(120),
+                resolver: ResolverType::Quad9,
+                interval: Some(Duration::from_secs(5)),
+                ip_strategy: Some(LookupIpStrategy::Ipv4Only),
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_file() {
+        assert!(Defaults::default().with_file("resolver = ").is_err());
+        assert!(Defaults::default().with_file("retries = 3").is_err());
+        assert!(Defaults::default().with_file("timeout = \"soon\"").is_err());
+    }
+}
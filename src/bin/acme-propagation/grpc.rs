@@ -0,0 +1,282 @@
+use std::time::Duration;
+
+use acme_validation_propagation::{Error, NameserverReport, PropagationEvent, PropagationReport};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::{
+    args::{parse_resolver, Logging, ServeArgs, WaitArgs},
+    config::Defaults,
+    serve::MAX_TIMEOUT,
+    OnEvent, Run,
+};
+
+pub mod proto {
+    tonic::include_proto!("acme_propagation");
+}
+
+use proto::{
+    propagation_server::{Propagation, PropagationServer},
+    wait_update::Update,
+    Event, Nameserver, WaitRequest, WaitResult, WaitUpdate,
+};
+
+/// Number of updates buffered for a client that reads slowly.
+const UPDATES: usize = 16;
+
+type Updates = ReceiverStream<Result<WaitUpdate, Status>>;
+
+struct Service {
+    defaults: Defaults,
+    run: Run,
+    diagnose: Run,
+}
+
+#[tonic::async_trait]
+impl Propagation for Service {
+    type WaitStream = Updates;
+    type WaitAbsentStream = Updates;
+
+    async fn wait(&self, request: Request<WaitRequest>) -> Result<Response<Updates>, Status> {
+        self.stream(self.wait_args(request.into_inner(), false)?)
+    }
+
+    async fn wait_absent(
+        &self,
+        request: Request<WaitRequest>,
+    ) -> Result<Response<Updates>, Status> {
+        self.stream(self.wait_args(request.into_inner(), true)?)
+    }
+
+    async fn diagnose(
+        &self,
+        request: Request<WaitRequest>,
+    ) -> Result<Response<WaitResult>, Status> {
+        let args = self.wait_args(request.into_inner(), false)?;
+        let diagnose = self.diagnose;
+        tokio::task::spawn_blocking(move || wait_result(&diagnose(&args, None)))
+            .await
+            .map(Response::new)
+            .map_err(|error| Status::internal(error.to_string()))
+    }
+}
+
+// The handlers have to return tonic's Status, boxing it here would only move the unboxing.
+#[allow(clippy::result_large_err)]
+impl Service {
+    fn wait_args(&self, request: WaitRequest, absent: bool) -> Result<WaitArgs, Status> {
+        let resolver = if request.resolver.is_empty() {
+            self.defaults.resolver.clone()
+        } else {
+            parse_resolver(&request.resolver).map_err(Status::invalid_argument)?
+        };
+        if request.domain.is_empty() || request.challenge.is_empty() {
+            return Err(Status::invalid_argument("Missing domain or challenge"));
+        }
+        Ok(WaitArgs {
+            domain: request.domain,
+            challenge: request.challenge,
+            is_record: request.record,
+            absent,
+            timeout: match request.timeout {
+                0 => self.defaults.timeout,
+                timeout if timeout > MAX_TIMEOUT => {
+                    return Err(Status::invalid_argument(format!(
+                        "Invalid timeout {timeout}, at most {MAX_TIMEOUT} seconds"
+                    )))
+                }
+                timeout => Duration::from_secs(timeout),
+            },
+            resolver,
+            interval: self.defaults.interval,
+            ip_strategy: self.defaults.ip_strategy,
+            json: false,
+            logging: Logging::Off,
+        })
+    }
+
+    /// Runs the wait on a blocking thread, the events and the result are sent as updates.
+    fn stream(&self, args: WaitArgs) -> Result<Response<Updates>, Status> {
+        let (sender, receiver) = mpsc::channel(UPDATES);
+        let events = sender.clone();
+        let on_event: OnEvent = Box::new(move |event| {
+            let update = Update::Event(event_message(event));
+            events
+                .blocking_send(Ok(WaitUpdate {
+                    update: Some(update),
+                }))
+                .ok();
+        });
+        let run = self.run;
+        tokio::task::spawn_blocking(move || {
+            let update = Update::Result(wait_result(&run(&args, Some(on_event))));
+            sender
+                .blocking_send(Ok(WaitUpdate {
+                    update: Some(update),
+                }))
+                .ok();
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+/// Serves the calls until the server fails.
+pub fn serve(args: &ServeArgs, run: Run, diagnose: Run) -> Result<(), String> {
+    let service = Service {
+        defaults: args.defaults.clone(),
+        run,
+        diagnose,
+    };
+    tracing::info!("Serving grpc on {}", args.listen);
+    crate::notify::ready(&format!("serving grpc on {}", args.listen));
+    tokio::runtime::Runtime::new()
+        .map_err(|error| error.to_string())?
+        .block_on(
+            Server::builder()
+                .add_service(PropagationServer::new(service))
+                .serve(args.listen),
+        )
+        .map_err(|error| error.to_string())
+}
+
+fn event_message(event: &PropagationEvent) -> Event {
+    let message = |kind: &str, attempt: usize, nameserver: &str, error: &str| Event {
+        kind: kind.to_owned(),
+        attempt: attempt as u64,
+        nameserver: nameserver.to_owned(),
+        error: error.to_owned(),
+    };
+    match event {
+        PropagationEvent::AttemptStarted { attempt } => {
+            message("attempt_started", *attempt, "", "")
+        }
+        PropagationEvent::NameserverVerified {
+            attempt,
+            nameserver,
+        } => message("nameserver_verified", *attempt, nameserver, ""),
+        PropagationEvent::NameserverLagging {
+            attempt,
+            nameserver,
+        } => message("nameserver_lagging", *attempt, nameserver, ""),
+        PropagationEvent::Done { attempts } => message("done", *attempts, "", ""),
+        PropagationEvent::TimedOut { attempts } => message("timed_out", *attempts, "", ""),
+        PropagationEvent::Failed { error } => message("failed", 0, "", error),
+    }
+}
+
+fn wait_result(result: &Result<Option<PropagationReport>, Error>) -> WaitResult {
+    match result {
+        Ok(report) => WaitResult {
+            propagated: true,
+            attempts: report.as_ref().map_or(0, |report| report.attempts as u64),
+            duration_ms: report.as_ref().map_or(0, |report| millis(report.duration)),
+            nameservers: report
+                .iter()
+                .flat_map(|report| report.nameservers.iter().map(nameserver))
+                .collect(),
+            ..WaitResult::default()
+        },
+        Err(error) => WaitResult {
+            timeout: error.is_timeout(),
+            error: error.to_string(),
+            nameservers: match error {
+                Error::AcmeChallege(lagging) | Error::AcmeChallengePresent(lagging) => {
+                    lagging.iter().map(nameserver).collect()
+                }
+                _ => vec![],
+            },
+            ..WaitResult::default()
+        },
+    }
+}
+
+fn nameserver(nameserver: &NameserverReport) -> Nameserver {
+    Nameserver {
+        name: nameserver.name.clone(),
+        addresses: nameserver
+            .addresses
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        propagated_after_ms: nameserver.propagated_after.map(millis),
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use acme_validation_propagation::{Error, NameserverReport, PropagationReport};
+    use tonic::Code;
+
+    use super::{
+        proto::{Nameserver, WaitRequest},
+        wait_result, Service,
+    };
+    use crate::{args::WaitArgs, config::Defaults, OnEvent};
+
+    fn unused(_: &WaitArgs, _: Option<OnEvent>) -> Result<Option<PropagationReport>, Error> {
+        unreachable!()
+    }
+
+    fn report(propagated_after: Option<Duration>) -> NameserverReport {
+        NameserverReport {
+            name: "ns1.transip.nl.".to_owned(),
+            addresses: vec!["195.135.195.195".parse().unwrap()],
+            propagated_after,
+        }
+    }
+
+    #[test]
+    fn propagated() {
+        let result = wait_result(&Ok(Some(PropagationReport {
+            domain_name: "paulmin.nl.".to_owned(),
+            attempts: 2,
+            duration: Duration::from_millis(6_500),
+            nameservers: vec![report(Some(Duration::from_millis(6_400)))],
+        })));
+        assert!(result.propagated);
+        assert_eq!(result.attempts, 2);
+        assert_eq!(result.duration_ms, 6_500);
+        assert_eq!(
+            result.nameservers,
+            vec![Nameserver {
+                name: "ns1.transip.nl.".to_owned(),
+                addresses: vec!["195.135.195.195".to_owned()],
+                propagated_after_ms: Some(6_400),
+            }]
+        );
+    }
+
+    #[test]
+    fn lagging() {
+        let result = wait_result(&Err(Error::AcmeChallege(vec![report(None)])));
+        assert!(!result.propagated);
+        assert!(result.timeout);
+        assert_eq!(result.nameservers[0].propagated_after_ms, None);
+    }
+
+    #[test]
+    fn timeout_bounded() {
+        let service = Service {
+            defaults: Defaults::default(),
+            run: unused,
+            diagnose: unused,
+        };
+        let request = |timeout| WaitRequest {
+            domain: "paulmin.nl".to_owned(),
+            challenge: "JaJaNeeNee".to_owned(),
+            timeout,
+            ..Default::default()
+        };
+        let args = service.wait_args(request(604_800), false).unwrap();
+        assert_eq!(args.timeout, Duration::from_secs(604_800));
+        let status = service.wait_args(request(u64::MAX), false).unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+}
@@ -0,0 +1,190 @@
+//! Command line interface waiting until an acme challenge record is propagated
+//! to all authoritive nameservers, for use in shell based acme hooks.
+//! The present and cleanup commands follow the exec provider of lego and
+//! the add and rm commands the dns hooks of acme.sh.
+
+mod args;
+mod config;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod notify;
+#[cfg(feature = "otel")]
+mod otel;
+mod output;
+mod rpc;
+mod serve;
+
+use std::{process::ExitCode, time::Instant};
+
+use acme_validation_propagation::{Error, Propagation, PropagationEvent, PropagationReport};
+use args::{Command, ExplainArgs, Logging, ServeArgs, StdioArgs, WaitArgs, USAGE};
+
+/// Receives the progress of a wait.
+pub type OnEvent = Box<dyn Fn(&PropagationEvent) + Send + Sync>;
+
+/// Runs a wait, see [`run`].
+pub type Run = fn(&WaitArgs, Option<OnEvent>) -> Result<Option<PropagationReport>, Error>;
+
+fn main() -> ExitCode {
+    let arguments = std::env::args().skip(1).collect::<Vec<_>>();
+    let parsed =
+        config::Defaults::load(&arguments).and_then(|defaults| args::parse(arguments, &defaults));
+    let code = match parsed {
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        }
+        Ok(Command::Wait(args)) => wait(&args),
+        Ok(Command::Serve(args)) => serve(&args),
+        Ok(Command::Stdio(args)) => stdio(&args),
+        Ok(Command::Explain(args)) => explain(&args),
+        #[cfg(feature = "grpc")]
+        Ok(Command::Grpc(args)) => {
+            logging(args.logging);
+            match grpc::serve(&args, run, diagnose) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(error) => {
+                    eprintln!("Serving grpc on {} failed: {}", args.listen, error);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            ExitCode::from(2)
+        }
+    };
+    #[cfg(feature = "otel")]
+    otel::shutdown();
+    code
+}
+
+fn logging(logging: Logging) {
+    #[cfg(feature = "otel")]
+    {
+        if otel::init(logging) {
+            return;
+        }
+    }
+    match logging {
+        Logging::Off => {}
+        Logging::Text => tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .init(),
+        Logging::Json => {
+            acme_validation_propagation::init_json_log();
+        }
+    }
+}
+
+fn wait(args: &WaitArgs) -> ExitCode {
+    logging(args.logging);
+    notify::ready(&format!("waiting for {}", args.domain));
+    let result = run(args, notify::on_event());
+    if args.json {
+        println!("{}", output::json(&args.domain, &result));
+    } else if result.is_ok() {
+        println!("{}", output::text(&args.domain, &result));
+    } else {
+        eprintln!("{}", output::text(&args.domain, &result));
+    }
+    if result.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn serve(args: &ServeArgs) -> ExitCode {
+    logging(args.logging);
+    match serve::serve(args, run) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Serving on {} failed: {}", args.listen, error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn stdio(args: &StdioArgs) -> ExitCode {
+    logging(args.logging);
+    notify::ready("reading requests from stdin");
+    match rpc::serve(args, run, std::io::stdin().lock(), std::io::stdout()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Reading from stdin failed: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn explain(args: &ExplainArgs) -> ExitCode {
+    let mut propagation = Propagation::new().resolver(args.resolver.clone());
+    if let Some(ip_strategy) = args.ip_strategy {
+        propagation = propagation.ip_strategy(ip_strategy);
+    }
+    match propagation.diagnose(&args.domain) {
+        Ok(diagnosis) if args.json => {
+            println!("{}", output::diagnosis(&diagnosis));
+            ExitCode::SUCCESS
+        }
+        Ok(diagnosis) => {
+            println!("{}", diagnosis);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("Diagnosing {} failed: {}", args.domain, error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Waits for the challenge, without a report when waiting for its removal.
+fn run(args: &WaitArgs, on_event: Option<OnEvent>) -> Result<Option<PropagationReport>, Error> {
+    let propagation = propagation(args, on_event);
+    let domain = args.domain.as_str();
+    let challenge = args.challenge.as_str();
+    // A timeout too large to be an instant waits without a deadline
+    match (args.absent, Instant::now().checked_add(args.timeout)) {
+        (true, Some(deadline)) => propagation
+            .wait_absent_until(domain, challenge, deadline)
+            .map(|_| None),
+        (true, None) => propagation.wait_absent(domain, challenge).map(|_| None),
+        (false, Some(deadline)) => propagation
+            .wait_report_until(domain, challenge, deadline)
+            .map(Some),
+        (false, None) => propagation.wait_report(domain, challenge).map(Some),
+    }
+}
+
+/// Checks all nameservers once.
+#[cfg(feature = "grpc")]
+fn diagnose(
+    args: &WaitArgs,
+    on_event: Option<OnEvent>,
+) -> Result<Option<PropagationReport>, Error> {
+    propagation(args, on_event)
+        .retries(1)
+        .initial_delay(std::time::Duration::ZERO)
+        .wait_report(args.domain.as_str(), args.challenge.as_str())
+        .map(Some)
+}
+
+fn propagation(args: &WaitArgs, on_event: Option<OnEvent>) -> Propagation {
+    let mut propagation = Propagation::new()
+        .resolver(args.resolver.clone())
+        .retries(usize::MAX);
+    if args.is_record {
+        propagation = propagation.label("");
+    }
+    if let Some(interval) = args.interval {
+        propagation = propagation.interval(interval);
+    }
+    if let Some(ip_strategy) = args.ip_strategy {
+        propagation = propagation.ip_strategy(ip_strategy);
+    }
+    if let Some(on_event) = on_event {
+        propagation = propagation.on_event(on_event);
+    }
+    propagation
+}
@@ -0,0 +1,191 @@
+//! Notifications to systemd, see sd_notify(3). Without NOTIFY_SOCKET nothing is sent.
+
+use std::{env, sync::Mutex, thread, time::Duration};
+
+use acme_validation_propagation::PropagationEvent;
+
+use crate::OnEvent;
+
+/// Reports the service as started with the status, and keeps the watchdog
+/// of the service manager happy from a thread when the unit has WatchdogSec set.
+pub fn ready(status: &str) {
+    notify(&format!("READY=1\nSTATUS={status}"));
+    let interval = watchdog_interval(
+        env::var("WATCHDOG_USEC").ok().as_deref(),
+        env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    );
+    if let Some(interval) = interval {
+        thread::spawn(move || loop {
+            notify("WATCHDOG=1");
+            thread::sleep(interval);
+        });
+    }
+}
+
+/// Sends the progress of a wait as status, when started by systemd.
+pub fn on_event() -> Option<OnEvent> {
+    env::var_os("NOTIFY_SOCKET")?;
+    let counts = Mutex::new(Counts::default());
+    Some(Box::new(move |event| {
+        let status = counts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .status(event);
+        notify(&format!("STATUS={status}"));
+    }))
+}
+
+/// Nameservers checked in the current attempt.
+#[derive(Default)]
+struct Counts {
+    converged: usize,
+    checked: usize,
+}
+
+impl Counts {
+    fn status(&mut self, event: &PropagationEvent) -> String {
+        match event {
+            PropagationEvent::AttemptStarted { attempt } => {
+                *self = Counts::default();
+                return format!("waiting, attempt {attempt}");
+            }
+            PropagationEvent::NameserverVerified { .. } => {
+                self.converged += 1;
+                self.checked += 1;
+            }
+            PropagationEvent::NameserverLagging { .. } => self.checked += 1,
+            PropagationEvent::Done { attempts } => {
+                return format!("propagated after {attempts} attempts")
+            }
+            PropagationEvent::TimedOut { attempts } => {
+                return format!("timed out after {attempts} attempts")
+            }
+            PropagationEvent::Failed { error } => return format!("failed: {error}"),
+        }
+        format!("waiting, {}/{} NS converged", self.converged, self.checked)
+    }
+}
+
+/// Half of the watchdog timeout, when the watchdog is meant for this process.
+fn watchdog_interval(usec: Option<&str>, pid: Option<&str>, own_pid: u32) -> Option<Duration> {
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok() != Some(own_pid) {
+            return None;
+        }
+    }
+    usec?
+        .parse::<u64>()
+        .ok()
+        .filter(|usec| *usec > 0)
+        .map(|usec| Duration::from_micros(usec / 2))
+}
+
+fn notify(state: &str) {
+    if let Some(socket) = env::var_os("NOTIFY_SOCKET") {
+        if let Err(error) = send(&socket, state) {
+            tracing::warn!("Notifying systemd failed: {}", error);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn send(socket: &std::ffi::OsStr, state: &str) -> std::io::Result<()> {
+    use std::{
+        ffi::OsString,
+        os::unix::ffi::{OsStrExt, OsStringExt},
+    };
+
+    use socket2::{Domain, SockAddr, Socket, Type};
+
+    // An address starting with @ is in the abstract namespace
+    let mut path = socket.as_bytes().to_vec();
+    if path.first() == Some(&b'@') {
+        path[0] = 0;
+    }
+    let address = SockAddr::unix(OsString::from_vec(path))?;
+    Socket::new(Domain::UNIX, Type::DGRAM, None)?
+        .send_to(state.as_bytes(), &address)
+        .map(drop)
+}
+
+#[cfg(not(unix))]
+fn send(_socket: &std::ffi::OsStr, _state: &str) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use acme_validation_propagation::PropagationEvent;
+
+    use super::{watchdog_interval, Counts};
+
+    #[test]
+    fn status() {
+        let mut counts = Counts::default();
+        let nameserver = || "ns1.transip.nl.".to_owned();
+        let statuses = [
+            PropagationEvent::AttemptStarted { attempt: 2 },
+            PropagationEvent::NameserverVerified {
+                attempt: 2,
+                nameserver: nameserver(),
+            },
+            PropagationEvent::NameserverLagging {
+                attempt: 2,
+                nameserver: nameserver(),
+            },
+            PropagationEvent::AttemptStarted { attempt: 3 },
+            PropagationEvent::NameserverVerified {
+                attempt: 3,
+                nameserver: nameserver(),
+            },
+            PropagationEvent::Done { attempts: 3 },
+        ]
+        .iter()
+        .map(|event| counts.status(event))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            vec![
+                "waiting, attempt 2",
+                "waiting, 1/1 NS converged",
+                "waiting, 1/2 NS converged",
+                "waiting, attempt 3",
+                "waiting, 1/1 NS converged",
+                "propagated after 3 attempts",
+            ]
+        );
+    }
+
+    #[test]
+    fn watchdog() {
+        assert_eq!(
+            watchdog_interval(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            watchdog_interval(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(watchdog_interval(Some("30000000"), Some("7"), 42), None);
+        assert_eq!(watchdog_interval(Some("0"), None, 42), None);
+        assert_eq!(watchdog_interval(None, None, 42), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn send_datagram() {
+        use std::os::unix::net::UnixDatagram;
+
+        let path = std::env::temp_dir().join(format!("acme-propagation-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        super::send(path.as_os_str(), "READY=1").unwrap();
+        let mut buffer = [0; 64];
+        let length = receiver.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..length], b"READY=1");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,47 @@
+//! Export of the spans of the waits to an OpenTelemetry collector.
+
+use opentelemetry::trace::TracerProvider as _;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::args::Logging;
+
+/// Name of the tracer, the service name is taken from OTEL_SERVICE_NAME.
+const TRACER: &str = "acme-propagation";
+
+/// Exports the spans with OTLP over http when OTEL_EXPORTER_OTLP_ENDPOINT is set,
+/// and logs to stderr as asked. Returns false when nothing was set up.
+pub fn init(logging: Logging) -> bool {
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() {
+        return false;
+    }
+    let provider = match opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().http())
+        .install_simple()
+    {
+        Ok(provider) => provider,
+        Err(error) => {
+            eprintln!("Exporting spans failed: {}", error);
+            return false;
+        }
+    };
+    let tracer = provider.tracer(TRACER);
+    opentelemetry::global::set_tracer_provider(provider);
+    tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .with((logging == Logging::Text).then(|| fmt::layer().with_writer(std::io::stderr)))
+        .with((logging == Logging::Json).then(|| {
+            fmt::layer()
+                .json()
+                .flatten_event(true)
+                .with_current_span(false)
+                .with_writer(std::io::stderr)
+        }))
+        .init();
+    true
+}
+
+/// Exports the spans that are not exported yet.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
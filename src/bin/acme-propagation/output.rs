@@ -0,0 +1,241 @@
+use acme_validation_propagation::{
+    Diagnosis, Error, NameserverReport, PropagationEvent, PropagationReport,
+};
+use serde_json::{json, Value};
+
+/// The result of a wait as json, with the durations in milliseconds.
+/// Without a report the removal of the challenge has propagated.
+pub fn json(domain: &str, result: &Result<Option<PropagationReport>, Error>) -> Value {
+    match result {
+        Ok(None) => json!({
+            "domain": domain,
+            "propagated": true,
+            "removed": true,
+        }),
+        Ok(Some(report)) => json!({
+            "domain": domain,
+            "propagated": true,
+            "attempts": report.attempts,
+            "duration_ms": millis(report.duration),
+            "nameservers": report.nameservers.iter().map(nameserver).collect::<Vec<_>>(),
+        }),
+        Err(error) => json!({
+            "domain": domain,
+            "propagated": false,
+            "timeout": error.is_timeout(),
+            "error": error.to_string(),
+        }),
+    }
+}
+
+fn nameserver(nameserver: &NameserverReport) -> Value {
+    json!({
+        "name": nameserver.name,
+        "addresses": nameserver
+            .addresses
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        "propagated_after_ms": nameserver.propagated_after.map(millis),
+    })
+}
+
+fn millis(duration: std::time::Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// A progress event as json, the kind of event is in the event field.
+pub fn event(event: &PropagationEvent) -> Value {
+    match event {
+        PropagationEvent::AttemptStarted { attempt } => {
+            json!({ "event": "attempt_started", "attempt": attempt })
+        }
+        PropagationEvent::NameserverVerified {
+            attempt,
+            nameserver,
+        } => json!({
+            "event": "nameserver_verified",
+            "attempt": attempt,
+            "nameserver": nameserver,
+        }),
+        PropagationEvent::NameserverLagging {
+            attempt,
+            nameserver,
+        } => json!({
+            "event": "nameserver_lagging",
+            "attempt": attempt,
+            "nameserver": nameserver,
+        }),
+        PropagationEvent::Done { attempts } => json!({ "event": "done", "attempts": attempts }),
+        PropagationEvent::TimedOut { attempts } => {
+            json!({ "event": "timed_out", "attempts": attempts })
+        }
+        PropagationEvent::Failed { error } => json!({ "event": "failed", "error": error }),
+    }
+}
+
+/// The diagnosis of a domain as json.
+pub fn diagnosis(diagnosis: &Diagnosis) -> Value {
+    json!({
+        "domain": diagnosis.domain_name,
+        "record": diagnosis.record_name,
+        "zone": diagnosis.zone,
+        "delegations": diagnosis
+            .delegations
+            .iter()
+            .map(|delegation| json!({
+                "zone": delegation.zone,
+                "nameservers": delegation.nameservers,
+            }))
+            .collect::<Vec<_>>(),
+        "nameservers": diagnosis
+            .nameservers
+            .iter()
+            .map(|nameserver| json!({
+                "name": nameserver.name,
+                "addresses": nameserver
+                    .addresses
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                "values": nameserver.values,
+                "error": nameserver.error,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// The result of a wait as a line of text.
+pub fn text(domain: &str, result: &Result<Option<PropagationReport>, Error>) -> String {
+    match result {
+        Ok(None) => format!("Challenge for {} removed from all nameservers", domain),
+        Ok(Some(report)) => format!(
+            "Challenge for {} propagated to {} nameservers after {:?}",
+            domain,
+            report.nameservers.len(),
+            report.duration
+        ),
+        Err(error) => format!("Challenge for {} not propagated: {}", domain, error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use acme_validation_propagation::{
+        Diagnosis, Error, NameserverDiagnosis, NameserverReport, PropagationEvent,
+        PropagationReport, ZoneDelegation,
+    };
+
+    use super::{diagnosis, event, json};
+
+    #[test]
+    fn propagated() {
+        let report = PropagationReport {
+            domain_name: "paulmin.nl.".to_owned(),
+            attempts: 2,
+            duration: Duration::from_millis(6_500),
+            nameservers: vec![NameserverReport {
+                name: "ns1.transip.nl.".to_owned(),
+                addresses: vec!["195.135.195.195".parse().unwrap()],
+                propagated_after: Some(Duration::from_millis(6_400)),
+            }],
+        };
+        assert_eq!(
+            json("paulmin.nl", &Ok(Some(report))),
+            json!({
+                "domain": "paulmin.nl",
+                "propagated": true,
+                "attempts": 2,
+                "duration_ms": 6500,
+                "nameservers": [{
+                    "name": "ns1.transip.nl.",
+                    "addresses": ["195.135.195.195"],
+                    "propagated_after_ms": 6400,
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn failed() {
+        assert_eq!(
+            json("paulmin.nl", &Err(Error::Cancelled)),
+            json!({
+                "domain": "paulmin.nl",
+                "propagated": false,
+                "timeout": false,
+                "error": "Cancelled",
+            })
+        );
+    }
+
+    #[test]
+    fn removed() {
+        assert_eq!(
+            json("_acme-challenge.paulmin.nl", &Ok(None)),
+            json!({
+                "domain": "_acme-challenge.paulmin.nl",
+                "propagated": true,
+                "removed": true,
+            })
+        );
+    }
+
+    #[test]
+    fn diagnosed() {
+        let diagnosed = Diagnosis {
+            domain_name: "paulmin.nl".to_owned(),
+            record_name: "_acme-challenge.paulmin.nl".to_owned(),
+            zone: "paulmin.nl".to_owned(),
+            delegations: vec![ZoneDelegation {
+                zone: "paulmin.nl".to_owned(),
+                nameservers: vec!["ns1.transip.nl.".to_owned()],
+            }],
+            nameservers: vec![NameserverDiagnosis {
+                name: "ns1.transip.nl.".to_owned(),
+                addresses: vec!["195.135.195.195".parse().unwrap()],
+                values: vec![],
+                error: Some("Cancelled".to_owned()),
+            }],
+        };
+        assert_eq!(
+            diagnosis(&diagnosed),
+            json!({
+                "domain": "paulmin.nl",
+                "record": "_acme-challenge.paulmin.nl",
+                "zone": "paulmin.nl",
+                "delegations": [{
+                    "zone": "paulmin.nl",
+                    "nameservers": ["ns1.transip.nl."],
+                }],
+                "nameservers": [{
+                    "name": "ns1.transip.nl.",
+                    "addresses": ["195.135.195.195"],
+                    "values": [],
+                    "error": "Cancelled",
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn events() {
+        assert_eq!(
+            event(&PropagationEvent::NameserverLagging {
+                attempt: 3,
+                nameserver: "ns1.transip.nl.".to_owned(),
+            }),
+            json!({
+                "event": "nameserver_lagging",
+                "attempt": 3,
+                "nameserver": "ns1.transip.nl.",
+            })
+        );
+        assert_eq!(
+            event(&PropagationEvent::Done { attempts: 3 }),
+            json!({ "event": "done", "attempts": 3 })
+        );
+    }
+}
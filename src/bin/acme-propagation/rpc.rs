@@ -0,0 +1,265 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    args::{StdioArgs, WaitArgs},
+    config::Defaults,
+    output,
+    serve::wait_args,
+    OnEvent, Run,
+};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The wait ended without the challenge being propagated.
+const NOT_PROPAGATED: i64 = 1;
+
+/// Handles the requests read from input, one json object per line, until input is closed
+/// and all waits have finished.
+pub fn serve<R, W>(args: &StdioArgs, run: Run, input: R, output: W) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write + Send + 'static,
+{
+    let session = Session {
+        defaults: args.defaults.clone(),
+        run,
+        output: Arc::new(Mutex::new(output)),
+    };
+    let mut waits = vec![];
+    for line in input.lines() {
+        let line = line?;
+        if !line.trim().is_empty() {
+            waits.extend(session.handle(&line));
+        }
+    }
+    for wait in waits {
+        if wait.join().is_err() {
+            tracing::warn!("Wait panicked");
+        }
+    }
+    Ok(())
+}
+
+struct Session<W> {
+    defaults: Defaults,
+    run: Run,
+    output: Arc<Mutex<W>>,
+}
+
+impl<W: Write + Send + 'static> Session<W> {
+    /// Starts the wait of the request, requests without an id get no response.
+    fn handle(&self, line: &str) -> Option<JoinHandle<()>> {
+        let request = match serde_json::from_str::<Value>(line) {
+            Ok(request) => request,
+            Err(error) => {
+                send(
+                    &self.output,
+                    &failure(Value::Null, PARSE_ERROR, error, None),
+                );
+                return None;
+            }
+        };
+        let id = request.get("id").cloned();
+        let method = match (request.get("jsonrpc"), request.get("method")) {
+            (Some(Value::String(version)), Some(Value::String(method))) if version == "2.0" => {
+                method
+            }
+            _ => {
+                let id = id.unwrap_or(Value::Null);
+                let message = "Invalid request";
+                send(&self.output, &failure(id, INVALID_REQUEST, message, None));
+                return None;
+            }
+        };
+        let result = match method.as_str() {
+            "wait" => wait_args(request.get("params").unwrap_or(&json!({})), &self.defaults)
+                .map_err(|error| (INVALID_PARAMS, error)),
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method {method}"))),
+        };
+        match result {
+            Ok(args) => Some(self.spawn(id, args)),
+            Err((code, message)) => {
+                if let Some(id) = id {
+                    send(&self.output, &failure(id, code, message, None));
+                }
+                None
+            }
+        }
+    }
+
+    /// Runs the wait on its own thread, sending progress notifications until it finishes.
+    fn spawn(&self, id: Option<Value>, args: WaitArgs) -> JoinHandle<()> {
+        let events = self.output.clone();
+        let progress_id = id.clone().unwrap_or(Value::Null);
+        let on_event: OnEvent = Box::new(move |event| {
+            send(
+                &events,
+                &json!({
+                    "jsonrpc": "2.0",
+                    "method": "progress",
+                    "params": { "id": progress_id, "event": output::event(event) },
+                }),
+            )
+        });
+        let run = self.run;
+        let responses = self.output.clone();
+        thread::spawn(move || {
+            let result = run(&args, Some(on_event));
+            if let Some(id) = id {
+                let value = output::json(&args.domain, &result);
+                let response = match &result {
+                    Ok(_) => json!({ "jsonrpc": "2.0", "id": id, "result": value }),
+                    Err(error) => failure(id, NOT_PROPAGATED, error, Some(value)),
+                };
+                send(&responses, &response);
+            }
+        })
+    }
+}
+
+fn failure<M: ToString>(id: Value, code: i64, message: M, data: Option<Value>) -> Value {
+    let mut error = json!({ "code": code, "message": message.to_string() });
+    if let Some(data) = data {
+        error["data"] = data;
+    }
+    json!({ "jsonrpc": "2.0", "id": id, "error": error })
+}
+
+/// Writes the message as one line. A closed output is ignored, the waits still finish.
+fn send<W: Write>(output: &Mutex<W>, message: &Value) {
+    let mut output = output.lock().unwrap_or_else(|e| e.into_inner());
+    if let Err(error) = writeln!(output, "{}", message).and_then(|_| output.flush()) {
+        tracing::warn!("Writing a response failed: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    use acme_validation_propagation::{Error, PropagationEvent, PropagationReport};
+    use serde_json::{json, Value};
+
+    use super::serve;
+    use crate::{
+        args::{Logging, StdioArgs, WaitArgs},
+        config::Defaults,
+        OnEvent, Run,
+    };
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn propagated(
+        args: &WaitArgs,
+        on_event: Option<OnEvent>,
+    ) -> Result<Option<PropagationReport>, Error> {
+        if let Some(on_event) = on_event {
+            on_event(&PropagationEvent::Done { attempts: 1 });
+        }
+        if args.domain == "paulmin.nl" {
+            Ok(None)
+        } else {
+            Err(Error::Cancelled)
+        }
+    }
+
+    fn messages(input: &str, run: Run) -> Vec<Value> {
+        let output = Output::default();
+        let args = StdioArgs {
+            defaults: Defaults::default(),
+            logging: Logging::Off,
+        };
+        serve(&args, run, input.as_bytes(), output.clone()).unwrap();
+        let bytes = output.0.lock().unwrap().clone();
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn progress_then_result() {
+        let messages = messages(
+            r#"{"jsonrpc": "2.0", "id": 7, "method": "wait", "params": {"domain": "paulmin.nl", "challenge": "JaJaNeeNee", "absent": true}}"#,
+            propagated,
+        );
+        assert_eq!(
+            messages,
+            vec![
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "progress",
+                    "params": { "id": 7, "event": { "event": "done", "attempts": 1 } },
+                }),
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 7,
+                    "result": { "domain": "paulmin.nl", "propagated": true, "removed": true },
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_wait() {
+        let messages = messages(
+            r#"{"jsonrpc": "2.0", "id": "a", "method": "wait", "params": {"domain": "example.com", "challenge": "JaJaNeeNee"}}"#,
+            propagated,
+        );
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1]["id"], "a");
+        assert_eq!(messages[1]["error"]["code"], 1);
+        assert_eq!(messages[1]["error"]["message"], "Cancelled");
+        assert_eq!(messages[1]["error"]["data"]["propagated"], false);
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let messages = messages(
+            concat!(
+                "not json\n",
+                "{\"id\": 1, \"method\": \"wait\"}\n",
+                "{\"jsonrpc\": \"2.0\", \"id\": 2, \"method\": \"revoke\"}\n",
+                "{\"jsonrpc\": \"2.0\", \"id\": 3, \"method\": \"wait\", \"params\": {}}\n",
+                "{\"jsonrpc\": \"2.0\", \"method\": \"revoke\"}\n",
+            ),
+            propagated,
+        );
+        let errors = messages
+            .iter()
+            .map(|message| (message["id"].clone(), message["error"]["code"].clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (Value::Null, json!(-32700)),
+                (json!(1), json!(-32600)),
+                (json!(2), json!(-32601)),
+                (json!(3), json!(-32602)),
+            ]
+        );
+    }
+}
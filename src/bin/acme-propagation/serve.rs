@@ -0,0 +1,363 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    args::{parse_resolver, Logging, ServeArgs, WaitArgs},
+    config::Defaults,
+    Run,
+};
+
+/// Largest request body accepted.
+const MAX_BODY: usize = 64 * 1024;
+/// Largest timeout in seconds a client can ask for, one week.
+pub const MAX_TIMEOUT: u64 = 7 * 24 * 60 * 60;
+const JSON: &str = "application/json";
+const PROMETHEUS: &str = "text/plain; version=0.0.4";
+
+/// The waits submitted over http. Each wait runs on its own thread.
+#[derive(Clone)]
+pub struct Jobs {
+    inner: Arc<Mutex<Inner>>,
+    defaults: Defaults,
+    run: Run,
+    /// Renders the metrics for GET /metrics
+    #[cfg(feature = "metrics")]
+    metrics: Option<metrics_exporter_prometheus::PrometheusHandle>,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    /// Status of every job, without the id
+    status: HashMap<u64, Value>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub body: Vec<u8>,
+}
+
+impl Jobs {
+    pub fn new(defaults: Defaults, run: Run) -> Self {
+        Self {
+            inner: Arc::default(),
+            defaults,
+            run,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Records the metrics of the waits, to serve them on GET /metrics.
+    #[cfg(feature = "metrics")]
+    fn with_metrics(mut self) -> std::io::Result<Self> {
+        let handle = metrics_exporter_prometheus::PrometheusBuilder::new()
+            .install_recorder()
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::Other, error.to_string()))?;
+        self.metrics = Some(handle);
+        Ok(self)
+    }
+
+    /// The metrics in the prometheus text format, when the request asks for them.
+    #[cfg(feature = "metrics")]
+    fn metrics(&self, request: &Request) -> Option<String> {
+        let metrics = self.metrics.as_ref()?;
+        (request.method == "GET" && request.path == "/metrics").then(|| metrics.render())
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    fn metrics(&self, _request: &Request) -> Option<String> {
+        None
+    }
+
+    /// Answers a request with the status code and the json body.
+    pub fn handle(&self, request: &Request) -> (u16, Value) {
+        let segments = request
+            .path
+            .trim_matches('/')
+            .split('/')
+            .collect::<Vec<_>>();
+        match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["jobs"]) => match self.submit(&request.body) {
+                Ok(id) => (202, json!({ "id": id, "status": "waiting" })),
+                Err(error) => (400, json!({ "error": error })),
+            },
+            ("GET", ["jobs", id]) => match id.parse().ok().and_then(|id| self.status(id)) {
+                Some(status) => (200, status),
+                None => (404, json!({ "error": format!("Unknown job {id}") })),
+            },
+            (_, ["jobs"] | ["jobs", _]) => (405, json!({ "error": "Method not allowed" })),
+            _ => (
+                404,
+                json!({ "error": format!("Unknown path {}", request.path) }),
+            ),
+        }
+    }
+
+    /// Starts a wait and returns its id.
+    fn submit(&self, body: &[u8]) -> Result<u64, String> {
+        let args = self.wait_args(body)?;
+        let id = {
+            let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+            inner.next_id += 1;
+            let id = inner.next_id;
+            inner
+                .status
+                .insert(id, json!({ "domain": args.domain, "status": "waiting" }));
+            id
+        };
+        tracing::info!("Job {} waits for {}", id, args.domain);
+        let jobs = self.clone();
+        thread::spawn(move || {
+            let result = (jobs.run)(&args, None);
+            let mut status = crate::output::json(&args.domain, &result);
+            status["status"] = Value::from(if result.is_ok() { "done" } else { "failed" });
+            jobs.inner
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .status
+                .insert(id, status);
+        });
+        Ok(id)
+    }
+
+    fn status(&self, id: u64) -> Option<Value> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.status.get(&id).map(|status| {
+            let mut status = status.clone();
+            status["id"] = Value::from(id);
+            status
+        })
+    }
+
+    fn wait_args(&self, body: &[u8]) -> Result<WaitArgs, String> {
+        let body: Value =
+            serde_json::from_slice(body).map_err(|error| format!("Invalid json: {error}"))?;
+        wait_args(&body, &self.defaults)
+    }
+}
+
+/// The wait described by a json object with the domain, the challenge and optionally
+/// timeout, record, absent and resolver. The defaults are used for the settings it does not have.
+pub fn wait_args(job: &Value, defaults: &Defaults) -> Result<WaitArgs, String> {
+    let text = |key: &str| job.get(key).and_then(Value::as_str);
+    let flag = |key: &str| job.get(key).and_then(Value::as_bool).unwrap_or_default();
+    Ok(WaitArgs {
+        domain: text("domain").ok_or("Missing domain")?.to_owned(),
+        challenge: text("challenge").ok_or("Missing challenge")?.to_owned(),
+        is_record: flag("record"),
+        absent: flag("absent"),
+        timeout: match job.get("timeout") {
+            Some(timeout) => timeout
+                .as_u64()
+                .filter(|seconds| *seconds <= MAX_TIMEOUT)
+                .map(Duration::from_secs)
+                .ok_or_else(|| {
+                    format!("Invalid timeout {timeout}, at most {MAX_TIMEOUT} seconds")
+                })?,
+            None => defaults.timeout,
+        },
+        resolver: match text("resolver") {
+            Some(resolver) => parse_resolver(resolver)?,
+            None => defaults.resolver.clone(),
+        },
+        interval: defaults.interval,
+        ip_strategy: defaults.ip_strategy,
+        json: true,
+        logging: Logging::Off,
+    })
+}
+
+/// Accepts connections until the listener fails, one thread per connection.
+pub fn serve(args: &ServeArgs, run: Run) -> std::io::Result<()> {
+    let listener = TcpListener::bind(args.listen)?;
+    let address = listener.local_addr()?;
+    tracing::info!("Listening on {}", address);
+    crate::notify::ready(&format!("serving on {address}"));
+    let jobs = Jobs::new(args.defaults.clone(), run);
+    #[cfg(feature = "metrics")]
+    let jobs = jobs.with_metrics()?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let jobs = jobs.clone();
+        thread::spawn(move || {
+            if let Err(error) = connection(stream, &jobs) {
+                tracing::warn!("Connection failed: {}", error);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn connection(mut stream: TcpStream, jobs: &Jobs) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(30)))?;
+    let (status, body) = match read_request(&mut BufReader::new(&stream)) {
+        Ok(request) => {
+            if let Some(metrics) = jobs.metrics(&request) {
+                return write_response(&mut stream, 200, PROMETHEUS, &metrics);
+            }
+            jobs.handle(&request)
+        }
+        Err(error) => (400, json!({ "error": error })),
+    };
+    write_response(&mut stream, status, JSON, &body.to_string())
+}
+
+/// Reads a HTTP/1.1 request, only the Content-Length header is used.
+pub fn read_request<R: BufRead>(reader: &mut R) -> Result<Request, String> {
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|error| error.to_string())?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_owned(), path.to_owned()),
+        _ => return Err(format!("Invalid request line {}", line.trim_end())),
+    };
+    let mut length = 0;
+    loop {
+        line.clear();
+        reader
+            .read_line(&mut line)
+            .map_err(|error| error.to_string())?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid content length {}", value.trim()))?;
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return Err(format!("Body larger than {MAX_BODY} bytes"));
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|error| error.to_string())?;
+    Ok(Request { method, path, body })
+}
+
+fn write_response<W: Write>(
+    writer: &mut W,
+    status: u16,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        404 => "Not Found",
+        _ => "Method Not Allowed",
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{thread::sleep, time::Duration};
+
+    use acme_validation_propagation::{Error, PropagationReport};
+    use serde_json::json;
+
+    use super::{read_request, Jobs, Request};
+    use crate::{args::WaitArgs, config::Defaults, OnEvent};
+
+    fn removed(_: &WaitArgs, _: Option<OnEvent>) -> Result<Option<PropagationReport>, Error> {
+        Ok(None)
+    }
+
+    fn request(method: &str, path: &str, body: &str) -> Request {
+        Request {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            body: body.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn submit_and_poll() {
+        let jobs = Jobs::new(Defaults::default(), removed);
+        let (status, body) = jobs.handle(&request(
+            "POST",
+            "/jobs",
+            r#"{"domain": "_acme-challenge.paulmin.nl", "challenge": "JaJaNeeNee", "absent": true}"#,
+        ));
+        assert_eq!(status, 202);
+        assert_eq!(body, json!({ "id": 1, "status": "waiting" }));
+
+        let mut polls = 0;
+        let body = loop {
+            let (status, body) = jobs.handle(&request("GET", "/jobs/1", ""));
+            assert_eq!(status, 200);
+            if body["status"] != "waiting" || polls == 100 {
+                break body;
+            }
+            polls += 1;
+            sleep(Duration::from_millis(10));
+        };
+        assert_eq!(
+            body,
+            json!({
+                "id": 1,
+                "domain": "_acme-challenge.paulmin.nl",
+                "status": "done",
+                "propagated": true,
+                "removed": true,
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let jobs = Jobs::new(Defaults::default(), removed);
+        assert_eq!(jobs.handle(&request("POST", "/jobs", "{")).0, 400);
+        assert_eq!(
+            jobs.handle(&request("POST", "/jobs", r#"{"domain": "paulmin.nl"}"#)),
+            (400, json!({ "error": "Missing challenge" }))
+        );
+        let body =
+            r#"{"domain": "paulmin.nl", "challenge": "JaJa", "timeout": 18446744073709551615}"#;
+        assert_eq!(
+            jobs.handle(&request("POST", "/jobs", body)),
+            (
+                400,
+                json!({ "error": "Invalid timeout 18446744073709551615, at most 604800 seconds" })
+            )
+        );
+        assert_eq!(jobs.handle(&request("GET", "/jobs/1", "")).0, 404);
+        assert_eq!(jobs.handle(&request("DELETE", "/jobs/1", "")).0, 405);
+        assert_eq!(jobs.handle(&request("GET", "/", "")).0, 404);
+    }
+
+    #[test]
+    fn http_request() {
+        let raw = "POST /jobs HTTP/1.1\r\nHost: localhost\r\ncontent-length: 2\r\n\r\n{}";
+        assert_eq!(
+            read_request(&mut raw.as_bytes()),
+            Ok(request("POST", "/jobs", "{}"))
+        );
+        assert!(read_request(&mut "\r\n".as_bytes()).is_err());
+    }
+}
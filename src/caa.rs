@@ -0,0 +1,126 @@
+use hickory_resolver::proto::rr::rdata::caa::{Property, Value, CAA};
+
+use crate::{
+    domain::normalize,
+    resolver::{ancestors, RecursiveResolver},
+    Result,
+};
+
+/// Result of [`crate::Propagation::check_caa`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaaReport {
+    /// Name the relevant CAA records were found at, None if no name up to
+    /// the top level domain has CAA records
+    pub name: Option<String>,
+    /// The relevant CAA records, in presentation format
+    pub records: Vec<String>,
+    /// The certification authority may issue a certificate for the domain
+    pub permitted: bool,
+}
+
+/// Walks up from the domain to the top level domain like a certification authority does
+/// and checks the first CAA records found.
+pub(crate) fn check_caa(
+    recursive: &RecursiveResolver,
+    domain_name: &str,
+    ca: &str,
+) -> Result<CaaReport> {
+    let domain_name = normalize(domain_name)?;
+    let wildcard = domain_name.starts_with("*.");
+    let name = domain_name.strip_prefix("*.").unwrap_or(&domain_name);
+    for candidate in ancestors(name) {
+        let records = recursive.caa(candidate)?;
+        if !records.is_empty() {
+            let permitted = permits(&records, wildcard, ca);
+            tracing::info!(
+                "CAA records of {} {} {}",
+                candidate,
+                if permitted { "permit" } else { "do not permit" },
+                ca
+            );
+            return Ok(CaaReport {
+                name: Some(candidate.to_owned()),
+                records: records.iter().map(ToString::to_string).collect(),
+                permitted,
+            });
+        }
+    }
+    Ok(CaaReport {
+        name: None,
+        records: vec![],
+        permitted: true,
+    })
+}
+
+/// The CAA records permit the certification authority with the issuer domain name ca to issue,
+/// following the processing rules of RFC 8659.
+fn permits(records: &[CAA], wildcard: bool, ca: &str) -> bool {
+    if records
+        .iter()
+        .any(|record| record.issuer_critical() && matches!(record.tag(), Property::Unknown(_)))
+    {
+        return false;
+    }
+    let issuewild = records
+        .iter()
+        .filter(|record| record.tag().is_issuewild())
+        .collect::<Vec<_>>();
+    let relevant = if wildcard && !issuewild.is_empty() {
+        issuewild
+    } else {
+        records
+            .iter()
+            .filter(|record| record.tag().is_issue())
+            .collect()
+    };
+    let ca = ca.trim_end_matches('.');
+    relevant.is_empty()
+        || relevant.iter().any(|record| match record.value() {
+            Value::Issuer(Some(issuer), _) => issuer
+                .to_ascii()
+                .trim_end_matches('.')
+                .eq_ignore_ascii_case(ca),
+            _ => false,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use hickory_resolver::proto::rr::{rdata::caa::CAA, Name};
+
+    use super::permits;
+
+    fn issue(ca: &str) -> CAA {
+        CAA::new_issue(false, Some(Name::from_ascii(ca).unwrap()), vec![])
+    }
+
+    fn issuewild(ca: &str) -> CAA {
+        CAA::new_issuewild(false, Some(Name::from_ascii(ca).unwrap()), vec![])
+    }
+
+    #[test]
+    fn issue_permits() {
+        let records = [issue("letsencrypt.org"), issuewild("sectigo.com")];
+        assert!(permits(&records, false, "letsencrypt.org"));
+        assert!(permits(&records, false, "LetsEncrypt.org."));
+        assert!(!permits(&records, false, "sectigo.com"));
+        assert!(!permits(&records, true, "letsencrypt.org"));
+        assert!(permits(&records, true, "sectigo.com"));
+    }
+
+    #[test]
+    fn forbidden_and_unrestricted() {
+        let forbidden = CAA::new_issue(false, None, vec![]);
+        assert!(!permits(&[forbidden], false, "letsencrypt.org"));
+        assert!(permits(
+            &[issuewild("sectigo.com")],
+            false,
+            "letsencrypt.org"
+        ));
+        assert!(permits(
+            &[issue("letsencrypt.org")],
+            true,
+            "letsencrypt.org"
+        ));
+    }
+}
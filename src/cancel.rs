@@ -0,0 +1,153 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// CancellationToken can be used to abandon a running wait from another thread.
+///
+/// Clones share the same state, so cancelling one clone cancels all of them.
+///
+/// ```no_run
+/// use acme_validation_propagation::{CancellationToken, Propagation};
+///
+/// let token = CancellationToken::new();
+/// let propagation = Propagation::new().cancellation(token.clone());
+/// let handle = std::thread::spawn(move || propagation.wait("example.com", "89823875"));
+/// token.cancel();
+/// assert!(handle.join().unwrap().is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: Mutex<bool>,
+    condvar: Condvar,
+    /// Wakes the async waits
+    #[cfg(feature = "tokio")]
+    notify: tokio::sync::Notify,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the wait. Sleeping waits are woken up immediately.
+    pub fn cancel(&self) {
+        *self
+            .inner
+            .cancelled
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = true;
+        self.inner.condvar.notify_all();
+        #[cfg(feature = "tokio")]
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self
+            .inner
+            .cancelled
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Sleeps for duration unless cancelled earlier, a duration too large to be an instant
+    /// sleeps until cancelled. Returns false if the token was cancelled.
+    pub(crate) fn sleep(&self, duration: Duration) -> bool {
+        let Inner {
+            cancelled, condvar, ..
+        } = &*self.inner;
+        let end = Instant::now().checked_add(duration);
+        let mut guard = cancelled.lock().unwrap_or_else(|e| e.into_inner());
+        while !*guard {
+            guard = match end {
+                Some(end) => {
+                    let remaining = end.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        break;
+                    }
+                    condvar
+                        .wait_timeout(guard, remaining)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => condvar.wait(guard).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+        !*guard
+    }
+
+    /// Completes when the token is cancelled.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn cancelled(&self) {
+        // A notified future created before the check is woken by a later cancel
+        let notified = self.inner.notify.notified();
+        if !self.is_cancelled() {
+            notified.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::CancellationToken;
+
+    #[test]
+    fn sleep_without_cancel() {
+        let token = CancellationToken::new();
+        assert!(token.sleep(Duration::from_millis(10)));
+        assert!(!token.is_cancelled());
+    }
+
+    #[test]
+    fn cancel_wakes_sleep() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        let start = Instant::now();
+        let handle = thread::spawn(move || clone.sleep(Duration::from_secs(10)));
+        thread::sleep(Duration::from_millis(50));
+        token.cancel();
+        assert!(!handle.join().unwrap());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn cancel_wakes_endless_sleep() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        let handle = thread::spawn(move || clone.sleep(Duration::MAX));
+        thread::sleep(Duration::from_millis(50));
+        token.cancel();
+        assert!(!handle.join().unwrap());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn cancel_wakes_async_wait() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            clone.cancel();
+        });
+        runtime
+            .block_on(async {
+                tokio::time::timeout(Duration::from_secs(5), token.cancelled()).await
+            })
+            .unwrap();
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,152 @@
+use data_encoding::{BASE32_NOPAD, BASE64URL_NOPAD};
+use sha2::{Digest, Sha256};
+
+use crate::{resolver::ACME_CHALLENGE_LABEL, Error};
+
+const MAX_TXT_LENGTH: usize = 255;
+const DIGEST_LENGTH: usize = 43;
+
+/// How strictly the challenge must look like the value computed by [`dns_value`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChallengeFormat {
+    /// Accept any value that can be the value of a TXT record.
+    Any,
+    /// Log a warning when the challenge is not a base64url encoded sha256 digest.
+    #[default]
+    Warn,
+    /// Fail with [`Error::NotDigest`] when the challenge is not a base64url encoded sha256 digest.
+    Digest,
+}
+
+/// Checks that the challenge can be the value of a TXT record:
+/// not empty, at most 255 characters and only printable ascii without spaces or quotes.
+pub(crate) fn validate<S>(challenge: S) -> Result<(), Error>
+where
+    S: AsRef<str>,
+{
+    let challenge = challenge.as_ref();
+    if !challenge.is_empty()
+        && challenge.len() <= MAX_TXT_LENGTH
+        && challenge.chars().all(|c| c.is_ascii_graphic() && c != '"')
+    {
+        Ok(())
+    } else {
+        Err(Error::InvalidChallenge(challenge.to_owned()))
+    }
+}
+
+/// Checks that the challenge looks like base64url(sha256(..)), 43 characters without padding.
+pub(crate) fn is_digest(challenge: &str) -> bool {
+    challenge.len() == DIGEST_LENGTH
+        && challenge
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Catches the common mistake of passing the token or the key authorization
+/// instead of the digest of the key authorization.
+pub(crate) fn check_format(challenge: &str, format: ChallengeFormat) -> Result<(), Error> {
+    if format == ChallengeFormat::Any || is_digest(challenge) {
+        return Ok(());
+    }
+    if format == ChallengeFormat::Digest {
+        return Err(Error::NotDigest(challenge.to_owned()));
+    }
+    if challenge.contains('.') {
+        tracing::warn!(
+            "Challenge {} looks like a key authorization, use dns_value to compute the digest",
+            challenge
+        );
+    } else {
+        tracing::warn!("Challenge {} is not a sha256 digest", challenge);
+    }
+    Ok(())
+}
+
+/// Computes the value of the acme challenge record from the key authorization,
+/// base64url(sha256(key authorization)) as specified in RFC 8555 section 8.4.
+pub fn dns_value<S>(key_authorization: S) -> String
+where
+    S: AsRef<str>,
+{
+    BASE64URL_NOPAD.encode(&Sha256::digest(key_authorization.as_ref().as_bytes()))
+}
+
+/// Computes the record label for the dns-account-01 challenge from the acme account uri,
+/// `_<base32(sha256(account uri)[0..10])>._acme-challenge`.
+pub fn dns_account_label<S>(account_uri: S) -> String
+where
+    S: AsRef<str>,
+{
+    let digest = Sha256::digest(account_uri.as_ref().as_bytes());
+    format!(
+        "_{}.{}",
+        BASE32_NOPAD.encode(&digest[..10]).to_ascii_lowercase(),
+        ACME_CHALLENGE_LABEL
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_format, dns_account_label, dns_value, is_digest, validate, ChallengeFormat};
+    use crate::Error;
+
+    #[test]
+    fn key_authorization_digest() {
+        assert_eq!(
+            dns_value("evaGxfADs6pSRb2LAv9IZf17Dt3juxGJ-PCt92wr-oA.9jg46WB3rR_AHD-EBXdN7cBkH1WOu0tA3M9fm21mqTI"),
+            "lCM7cZyQXcVHK2nnW3jjAhNT3Fvm18UN-kWZZknKoYM",
+        );
+    }
+
+    #[test]
+    fn account_label() {
+        assert_eq!(
+            dns_account_label("https://example.com/acme/acct/ExampleAccount"),
+            "_ujmmovf2vn55tgye._acme-challenge",
+        );
+    }
+
+    #[test]
+    fn valid_challenge() {
+        assert!(validate("lCM7cZyQXcVHK2nnW3jjAhNT3Fvm18UN-kWZZknKoYM").is_ok());
+    }
+
+    #[test]
+    fn invalid_challenges() {
+        let long = "a".repeat(256);
+        for challenge in ["", "with space", "\"quoted\"", long.as_str()] {
+            assert!(matches!(
+                validate(challenge),
+                Err(Error::InvalidChallenge(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn digest_format() {
+        assert!(is_digest("lCM7cZyQXcVHK2nnW3jjAhNT3Fvm18UN-kWZZknKoYM"));
+        assert!(!is_digest("89823875"));
+        assert!(!is_digest(
+            "evaGxfADs6pSRb2LAv9IZf17Dt3juxGJ-PCt92wr-oA.9jg46WB3rR_AHD-EBXdN7cBkH1WOu0tA3M9fm21mqTI"
+        ));
+        assert!(!is_digest("lCM7cZyQXcVHK2nnW3jjAhNT3Fvm18UN+kWZZknKoYM"));
+    }
+
+    #[test]
+    fn challenge_format() {
+        let key_authorization =
+            "evaGxfADs6pSRb2LAv9IZf17Dt3juxGJ-PCt92wr-oA.9jg46WB3rR_AHD-EBXdN7cBkH1WOu0tA3M9fm21mqTI";
+        assert!(check_format(key_authorization, ChallengeFormat::Any).is_ok());
+        assert!(check_format(key_authorization, ChallengeFormat::Warn).is_ok());
+        assert!(matches!(
+            check_format(key_authorization, ChallengeFormat::Digest),
+            Err(Error::NotDigest(_))
+        ));
+        assert!(check_format(
+            "lCM7cZyQXcVHK2nnW3jjAhNT3Fvm18UN-kWZZknKoYM",
+            ChallengeFormat::Digest
+        )
+        .is_ok());
+    }
+}
@@ -0,0 +1,125 @@
+#[cfg(feature = "tokio")]
+use std::{future::Future, pin::Pin};
+
+use crate::{Propagation, PropagationReport, Result};
+
+/// Future of the waits of a [`PropagationChecker`] with the `tokio` feature.
+#[cfg(feature = "tokio")]
+pub type CheckFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Object safe interface to the waiting, implemented by [`Propagation`],
+/// so a service can keep a `Box<dyn PropagationChecker>` and replace it in its tests.
+/// With the `tokio` feature the waits are async and return a boxed future.
+pub trait PropagationChecker: Send + Sync {
+    /// Waits until all authoritive nameservers serve the challenge,
+    /// see [`Propagation::wait_report`].
+    #[cfg(not(feature = "tokio"))]
+    fn wait(&self, domain_name: &str, challenge: &str) -> Result<PropagationReport>;
+
+    /// Waits until none of the authoritive nameservers serves the challenge,
+    /// see [`Propagation::wait_absent`].
+    #[cfg(not(feature = "tokio"))]
+    fn wait_absent(&self, domain_name: &str, challenge: &str) -> Result<()>;
+
+    /// Waits until all authoritive nameservers serve the challenge,
+    /// see [`Propagation::wait_report_async`].
+    #[cfg(feature = "tokio")]
+    fn wait<'a>(
+        &'a self,
+        domain_name: &'a str,
+        challenge: &'a str,
+    ) -> CheckFuture<'a, PropagationReport>;
+
+    /// Waits until none of the authoritive nameservers serves the challenge,
+    /// see [`Propagation::wait_absent_async`].
+    #[cfg(feature = "tokio")]
+    fn wait_absent<'a>(&'a self, domain_name: &'a str, challenge: &'a str) -> CheckFuture<'a, ()>;
+}
+
+#[cfg(not(feature = "tokio"))]
+impl PropagationChecker for Propagation {
+    fn wait(&self, domain_name: &str, challenge: &str) -> Result<PropagationReport> {
+        self.wait_report(domain_name, challenge)
+    }
+
+    fn wait_absent(&self, domain_name: &str, challenge: &str) -> Result<()> {
+        Propagation::wait_absent(self, domain_name, challenge)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl PropagationChecker for Propagation {
+    fn wait<'a>(
+        &'a self,
+        domain_name: &'a str,
+        challenge: &'a str,
+    ) -> CheckFuture<'a, PropagationReport> {
+        Box::pin(self.wait_report_async(domain_name, challenge))
+    }
+
+    fn wait_absent<'a>(&'a self, domain_name: &'a str, challenge: &'a str) -> CheckFuture<'a, ()> {
+        Box::pin(self.wait_absent_async(domain_name, challenge))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::PropagationChecker;
+    use crate::{MockResolver, Propagation, ResolverType};
+
+    fn checker() -> Box<dyn PropagationChecker> {
+        let mock = MockResolver::new();
+        mock.nameserver(
+            "paulmin.nl",
+            "ns0.transip.net.",
+            "195.135.195.195".parse().unwrap(),
+        )
+        .txt(
+            "ns0.transip.net",
+            "_acme-challenge.paulmin.nl",
+            &["JaJaNeeNee"],
+        );
+        Box::new(
+            Propagation::new()
+                .resolver(ResolverType::Mock(mock))
+                .initial_delay(Duration::ZERO),
+        )
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    #[test]
+    fn boxed_checker() {
+        let checker = checker();
+        assert_eq!(
+            checker.wait("paulmin.nl", "JaJaNeeNee").unwrap().attempts,
+            1
+        );
+        assert!(checker.wait_absent("paulmin.nl", "NeeNeeJaJa").is_ok());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn boxed_checker() {
+        let checker = checker();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            assert_eq!(
+                checker
+                    .wait("paulmin.nl", "JaJaNeeNee")
+                    .await
+                    .unwrap()
+                    .attempts,
+                1
+            );
+            assert!(checker
+                .wait_absent("paulmin.nl", "NeeNeeJaJa")
+                .await
+                .is_ok());
+        });
+    }
+}
@@ -0,0 +1,15 @@
+//! Helpers for the challenges of acme client crates.
+
+#[cfg(feature = "acme2")]
+pub use self::acme2::wait_acme2;
+#[cfg(feature = "acme-lib")]
+pub use self::acme_lib::wait_acme_lib;
+#[cfg(feature = "instant-acme")]
+pub use self::instant::wait_instant_acme;
+
+#[cfg(feature = "acme2")]
+mod acme2;
+#[cfg(feature = "acme-lib")]
+mod acme_lib;
+#[cfg(feature = "instant-acme")]
+mod instant;
@@ -0,0 +1,28 @@
+use acme2::Authorization;
+
+use crate::{Error, Propagation, Result};
+
+impl Propagation {
+    /// wait_acme2 behaves like [`Propagation::wait`] for the dns-01 challenge
+    /// of an acme2 authorization, after its record has been created.
+    pub fn wait_acme2(&self, authorization: &Authorization) -> Result<()> {
+        let domain_name = authorization.identifier.value.as_str();
+        let value = authorization
+            .get_challenge("dns-01")
+            .and_then(|challenge| match challenge.key_authorization_encoded() {
+                Ok(value) => value,
+                Err(error) => {
+                    tracing::warn!("No key authorization for {}: {}", domain_name, error);
+                    None
+                }
+            })
+            .ok_or_else(|| Error::NoDnsChallenge(domain_name.to_owned()))?;
+        self.wait(domain_name, value.as_str())
+    }
+}
+
+/// wait_acme2 behaves like [`crate::wait`] for the dns-01 challenge
+/// of an acme2 authorization, after its record has been created.
+pub fn wait_acme2(authorization: &Authorization) -> Result<()> {
+    Propagation::default().wait_acme2(authorization)
+}
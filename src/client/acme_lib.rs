@@ -0,0 +1,18 @@
+use acme_lib::{order::Auth, persist::Persist};
+
+use crate::{Propagation, Result};
+
+impl Propagation {
+    /// wait_acme_lib behaves like [`Propagation::wait`] for the dns proof
+    /// of an acme-lib authorization, after its record has been created.
+    pub fn wait_acme_lib<P: Persist>(&self, authorization: &Auth<P>) -> Result<()> {
+        let proof = authorization.dns_challenge().dns_proof();
+        self.wait(authorization.domain_name(), proof.as_str())
+    }
+}
+
+/// wait_acme_lib behaves like [`crate::wait`] for the dns proof
+/// of an acme-lib authorization, after its record has been created.
+pub fn wait_acme_lib<P: Persist>(authorization: &Auth<P>) -> Result<()> {
+    Propagation::default().wait_acme_lib(authorization)
+}
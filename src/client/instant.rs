@@ -0,0 +1,65 @@
+use instant_acme::{Authorization, Challenge, ChallengeType, Identifier, Order};
+
+use crate::{Error, Propagation, Result};
+
+impl Propagation {
+    /// wait_instant_acme behaves like [`Propagation::wait`] for the dns-01 challenge
+    /// of an authorization of the order, after its record has been created.
+    pub fn wait_instant_acme(&self, order: &Order, authorization: &Authorization) -> Result<()> {
+        let (domain_name, challenge) = dns_challenge(authorization)?;
+        let value = order.key_authorization(challenge).dns_value();
+        self.wait(domain_name, value.as_str())
+    }
+}
+
+/// wait_instant_acme behaves like [`crate::wait`] for the dns-01 challenge
+/// of an authorization of the order, after its record has been created.
+pub fn wait_instant_acme(order: &Order, authorization: &Authorization) -> Result<()> {
+    Propagation::default().wait_instant_acme(order, authorization)
+}
+
+/// The domain of the authorization with its dns-01 challenge.
+fn dns_challenge(authorization: &Authorization) -> Result<(&str, &Challenge)> {
+    let Identifier::Dns(domain_name) = &authorization.identifier;
+    authorization
+        .challenges
+        .iter()
+        .find(|challenge| challenge.r#type == ChallengeType::Dns01)
+        .map(|challenge| (domain_name.as_str(), challenge))
+        .ok_or_else(|| Error::NoDnsChallenge(domain_name.clone()))
+}
+
+#[cfg(test)]
+mod test {
+    use instant_acme::Authorization;
+    use serde_json::json;
+
+    use super::dns_challenge;
+    use crate::Error;
+
+    fn authorization(challenge_type: &str) -> Authorization {
+        serde_json::from_value(json!({
+            "identifier": {"type": "dns", "value": "paulmin.nl"},
+            "status": "pending",
+            "challenges": [{
+                "type": challenge_type,
+                "url": "https://acme.example/challenge/1",
+                "token": "JaJaNeeNee",
+                "status": "pending"
+            }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn dns_01() {
+        let authorization = authorization("dns-01");
+        let (domain_name, challenge) = dns_challenge(&authorization).unwrap();
+        assert_eq!(domain_name, "paulmin.nl");
+        assert_eq!(challenge.token, "JaJaNeeNee");
+        assert!(matches!(
+            dns_challenge(&self::authorization("http-01")),
+            Err(Error::NoDnsChallenge(domain_name)) if domain_name == "paulmin.nl"
+        ));
+    }
+}
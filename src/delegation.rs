@@ -0,0 +1,191 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::IpAddr,
+};
+
+use hickory_resolver::proto::{
+    op::Message,
+    rr::{Name, RData, RecordType},
+};
+
+use crate::{
+    resolver::{ancestors, AuthoritiveResolver, RecursiveResolver},
+    Error, Result,
+};
+
+/// Names of the nameservers of a zone, as delegated by the parent zone
+/// and as served by the nameservers of the zone itself.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Delegation {
+    parent: BTreeSet<String>,
+    child: BTreeSet<String>,
+}
+
+impl Delegation {
+    /// Nameservers only known to the parent zone, the child zone does not list them.
+    pub(crate) fn parent_only(&self) -> Vec<&str> {
+        self.parent
+            .difference(&self.child)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Nameservers only listed in the child zone, resolvers starting at the parent never ask them.
+    pub(crate) fn child_only(&self) -> Vec<&str> {
+        self.child
+            .difference(&self.parent)
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub(crate) fn is_consistent(&self) -> bool {
+        self.parent == self.child
+    }
+}
+
+/// Compares the NS records for the zone in the parent zone with those in the zone itself.
+/// Returns None for a top level domain, the root zone is not queried.
+pub(crate) fn delegation(
+    recursive: &RecursiveResolver,
+    zone: &str,
+    nameservers: &[AuthoritiveResolver],
+) -> Result<Option<Delegation>> {
+    let name = Name::from_ascii(zone).map_err(Error::proto)?;
+    let Some(referral) = referral(recursive, zone)? else {
+        return Ok(None);
+    };
+    Ok(Some(Delegation {
+        parent: ns_names(&referral, &name),
+        child: ns_names(&first_answer(nameservers, &name)?, &name),
+    }))
+}
+
+/// The addresses of the nameservers of the zone from the glue records in the parent zone,
+/// by lowercase host name. Only nameservers within the delegated zone need glue,
+/// so other nameservers are missing. Empty for a top level domain.
+pub(crate) fn glue(
+    recursive: &RecursiveResolver,
+    zone: &str,
+) -> Result<HashMap<String, Vec<IpAddr>>> {
+    let mut glue = HashMap::<String, Vec<IpAddr>>::new();
+    if let Some(referral) = referral(recursive, zone)? {
+        for record in referral.additionals() {
+            let address = match record.data() {
+                Some(RData::A(a)) => IpAddr::V4(**a),
+                Some(RData::AAAA(aaaa)) => IpAddr::V6(**aaaa),
+                _ => continue,
+            };
+            glue.entry(record.name().to_ascii().to_ascii_lowercase())
+                .or_default()
+                .push(address);
+        }
+    }
+    Ok(glue)
+}
+
+/// The addresses of the host name as served by the authoritive nameservers of its zone,
+/// so a renumbered nameserver is reached at its new addresses before caches expire.
+pub(crate) fn host_addresses(
+    recursive: &RecursiveResolver,
+    host_name: &str,
+) -> Result<Vec<IpAddr>> {
+    let nameservers = recursive.authoritive_resolvers(recursive.zone(host_name)?)?;
+    let name = Name::from_ascii(host_name).map_err(Error::proto)?;
+    let mut addresses = vec![];
+    for record_type in [RecordType::AAAA, RecordType::A] {
+        let response = first_authoritative_answer(&nameservers, &name, record_type)?;
+        addresses.extend(
+            response
+                .answers()
+                .iter()
+                .filter_map(|record| match record.data() {
+                    Some(RData::A(a)) => Some(IpAddr::V4(**a)),
+                    Some(RData::AAAA(aaaa)) => Some(IpAddr::V6(**aaaa)),
+                    _ => None,
+                }),
+        );
+    }
+    Ok(addresses)
+}
+
+fn first_authoritative_answer(
+    nameservers: &[AuthoritiveResolver],
+    name: &Name,
+    record_type: RecordType,
+) -> Result<Message> {
+    let mut last_error = Error::NoNameservers;
+    for nameserver in nameservers {
+        match nameserver.authoritative_answer(name, record_type) {
+            Ok(response) => return Ok(response),
+            Err(error) => last_error = error,
+        }
+    }
+    Err(last_error)
+}
+
+/// The answer of a nameserver of the parent zone to the NS query for the zone,
+/// None for a top level domain, the root zone is not queried.
+fn referral(recursive: &RecursiveResolver, zone: &str) -> Result<Option<Message>> {
+    let Some(parent) = ancestors(zone).nth(1) else {
+        return Ok(None);
+    };
+    let parent_zone = recursive.zone(parent)?;
+    let parent_nameservers = recursive.authoritive_resolvers(parent_zone)?;
+    let name = Name::from_ascii(zone).map_err(Error::proto)?;
+    first_answer(&parent_nameservers, &name).map(Some)
+}
+
+/// The answer to the NS query of the first nameserver that answers.
+fn first_answer(nameservers: &[AuthoritiveResolver], name: &Name) -> Result<Message> {
+    let mut last_error = Error::NoNameservers;
+    for nameserver in nameservers {
+        match nameserver.query(name, RecordType::NS) {
+            Ok(response) => return Ok(response),
+            Err(error) => last_error = error,
+        }
+    }
+    Err(last_error)
+}
+
+/// The NS records for name in the answer or, for a referral, in the authority section.
+fn ns_names(response: &Message, name: &Name) -> BTreeSet<String> {
+    response
+        .answers()
+        .iter()
+        .chain(response.name_servers())
+        .filter(|record| record.name() == name)
+        .filter_map(|record| record.data().and_then(RData::as_ns))
+        .map(|ns| ns.to_ascii().to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::Delegation;
+
+    fn names(names: &[&str]) -> std::collections::BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn consistent_delegation() {
+        let delegation = Delegation {
+            parent: names(&["ns0.transip.net.", "ns1.transip.nl."]),
+            child: names(&["ns0.transip.net.", "ns1.transip.nl."]),
+        };
+        assert!(delegation.is_consistent());
+        assert!(delegation.parent_only().is_empty());
+        assert!(delegation.child_only().is_empty());
+    }
+
+    #[test]
+    fn mismatched_delegation() {
+        let delegation = Delegation {
+            parent: names(&["ns0.transip.net.", "ns1.transip.nl."]),
+            child: names(&["ns0.transip.net.", "ns2.transip.eu."]),
+        };
+        assert!(!delegation.is_consistent());
+        assert_eq!(delegation.parent_only(), vec!["ns1.transip.nl."]);
+        assert_eq!(delegation.child_only(), vec!["ns2.transip.eu."]);
+    }
+}
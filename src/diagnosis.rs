@@ -0,0 +1,121 @@
+use std::{fmt, net::IpAddr};
+
+/// What [`crate::Propagation::diagnose`] found out about a domain,
+/// the information needed to find out why a wait times out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnosis {
+    /// Domain name the challenge is for
+    pub domain_name: String,
+    /// Name of the challenge record checked, the target when the record is an alias
+    pub record_name: String,
+    /// Zone of the challenge record
+    pub zone: String,
+    /// The zones from the top level domain down to the zone of the challenge record,
+    /// with their nameservers
+    pub delegations: Vec<ZoneDelegation>,
+    /// The authoritive nameservers of the zone of the challenge record
+    pub nameservers: Vec<NameserverDiagnosis>,
+}
+
+/// The nameservers of a zone in the delegation chain
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZoneDelegation {
+    pub zone: String,
+    pub nameservers: Vec<String>,
+}
+
+/// What a single authoritive nameserver serves
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NameserverDiagnosis {
+    /// Host name of the nameserver
+    pub name: String,
+    /// Addresses the nameserver was queried on
+    pub addresses: Vec<IpAddr>,
+    /// The TXT values of the challenge record currently served
+    pub values: Vec<String>,
+    /// Why querying the nameserver failed
+    pub error: Option<String>,
+}
+
+/// Several lines of text, like the output of dig gathered by hand.
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Domain: {}", self.domain_name)?;
+        writeln!(f, "Record: {}", self.record_name)?;
+        writeln!(f, "Zone:   {}", self.zone)?;
+        writeln!(f, "Delegation:")?;
+        for delegation in &self.delegations {
+            writeln!(
+                f,
+                "  {} -> {}",
+                delegation.zone,
+                delegation.nameservers.join(", ")
+            )?;
+        }
+        write!(f, "Nameservers:")?;
+        for nameserver in &self.nameservers {
+            let addresses = nameserver
+                .addresses
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            write!(f, "\n  {} ({})", nameserver.name, addresses.join(", "))?;
+            match &nameserver.error {
+                Some(error) => write!(f, "\n    error: {}", error)?,
+                None if nameserver.values.is_empty() => write!(f, "\n    no values")?,
+                None => {
+                    for value in &nameserver.values {
+                        write!(f, "\n    \"{}\"", value)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Diagnosis, NameserverDiagnosis, ZoneDelegation};
+
+    #[test]
+    fn display() {
+        let diagnosis = Diagnosis {
+            domain_name: "paulmin.nl".to_owned(),
+            record_name: "_acme-challenge.paulmin.nl".to_owned(),
+            zone: "paulmin.nl".to_owned(),
+            delegations: vec![ZoneDelegation {
+                zone: "paulmin.nl".to_owned(),
+                nameservers: vec!["ns0.transip.net.".to_owned(), "ns1.transip.nl.".to_owned()],
+            }],
+            nameservers: vec![
+                NameserverDiagnosis {
+                    name: "ns0.transip.net.".to_owned(),
+                    addresses: vec!["195.135.195.195".parse().unwrap()],
+                    values: vec!["JaJaNeeNee".to_owned()],
+                    error: None,
+                },
+                NameserverDiagnosis {
+                    name: "ns1.transip.nl.".to_owned(),
+                    addresses: vec![],
+                    values: vec![],
+                    error: None,
+                },
+            ],
+        };
+        assert_eq!(
+            diagnosis.to_string(),
+            "\
+Domain: paulmin.nl
+Record: _acme-challenge.paulmin.nl
+Zone:   paulmin.nl
+Delegation:
+  paulmin.nl -> ns0.transip.net., ns1.transip.nl.
+Nameservers:
+  ns0.transip.net. (195.135.195.195)
+    \"JaJaNeeNee\"
+  ns1.transip.nl. ()
+    no values"
+        );
+    }
+}
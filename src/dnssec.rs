@@ -0,0 +1,177 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hickory_resolver::proto::rr::{
+    dnssec::{
+        rdata::{DNSSECRData, DNSKEY, RRSIG},
+        Verifier,
+    },
+    DNSClass, Name, RData, Record, RecordType,
+};
+
+use crate::{
+    resolver::{txt_value, AuthoritiveResolver},
+    Error, Result,
+};
+
+/// The TXT values for the record name, queried directly on the nameserver.
+/// When the zone is signed, the TXT records must have a signature made with one of the
+/// DNSKEY records of the zone, failing with [`Error::Unsigned`] or [`Error::InvalidSignature`].
+/// Only the signature of the TXT records is checked, not the chain of trust to the root.
+/// A warning is logged when the signature expires within the window.
+pub(crate) fn verified_txt_values(
+    resolver: &AuthoritiveResolver,
+    record_name: &str,
+    window: Duration,
+) -> Result<Vec<String>> {
+    let name = Name::from_ascii(record_name).map_err(Error::proto)?;
+    let response = resolver.authoritative_answer(&name, RecordType::TXT)?;
+    let records = response
+        .answers()
+        .iter()
+        .filter(|record| record.record_type() == RecordType::TXT)
+        .cloned()
+        .collect::<Vec<_>>();
+    let signatures = response
+        .answers()
+        .iter()
+        .filter_map(|record| record.data().and_then(as_rrsig))
+        .filter(|rrsig| rrsig.type_covered() == RecordType::TXT)
+        .collect::<Vec<_>>();
+    let values = records
+        .iter()
+        .filter_map(|record| record.data().and_then(RData::as_txt))
+        .map(txt_value)
+        .collect::<Vec<_>>();
+
+    if records.is_empty() {
+        return Ok(values);
+    }
+    let zone = match signatures.first() {
+        Some(rrsig) => rrsig.signer_name().clone(),
+        None => zone(resolver, &name)?,
+    };
+    let keys = dnskeys(resolver, &zone)?;
+    if keys.is_empty() {
+        return Ok(values);
+    }
+    if signatures.is_empty() {
+        return Err(Error::Unsigned(record_name.to_owned()));
+    }
+    match signatures
+        .iter()
+        .find(|rrsig| keys.iter().any(|key| verifies(key, &name, rrsig, &records)))
+    {
+        Some(rrsig) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs());
+            check_expiration(record_name, rrsig.sig_expiration(), now, window)?;
+            Ok(values)
+        }
+        None => Err(Error::InvalidSignature(record_name.to_owned())),
+    }
+}
+
+/// Fails if the signature has expired, warns if it expires within the window.
+/// Expiration and now are in seconds since the unix epoch.
+fn check_expiration(record_name: &str, expiration: u32, now: u64, window: Duration) -> Result<()> {
+    let remaining = u64::from(expiration).saturating_sub(now);
+    if remaining == 0 {
+        return Err(Error::SignatureExpired(record_name.to_owned()));
+    }
+    if remaining < window.as_secs() {
+        tracing::warn!(
+            record = record_name,
+            expires_in = ?Duration::from_secs(remaining),
+            "Signature expires soon"
+        );
+    }
+    Ok(())
+}
+
+fn verifies(key: &DNSKEY, name: &Name, rrsig: &RRSIG, records: &[Record]) -> bool {
+    match key.verify_rrsig(name, DNSClass::IN, rrsig, records) {
+        Ok(()) => true,
+        Err(error) => {
+            tracing::debug!("Signature of {} not verified: {}", name, error);
+            false
+        }
+    }
+}
+
+fn as_rrsig(data: &RData) -> Option<&RRSIG> {
+    match data {
+        RData::DNSSEC(DNSSECRData::RRSIG(rrsig)) => Some(rrsig),
+        _ => None,
+    }
+}
+
+fn as_dnskey(data: &RData) -> Option<&DNSKEY> {
+    match data {
+        RData::DNSSEC(DNSSECRData::DNSKEY(dnskey)) => Some(dnskey),
+        _ => None,
+    }
+}
+
+/// The zone keys of the zone that are not revoked, empty if the zone is not signed.
+fn dnskeys(resolver: &AuthoritiveResolver, zone: &Name) -> Result<Vec<DNSKEY>> {
+    resolver
+        .authoritative_answer(zone, RecordType::DNSKEY)
+        .map(|response| {
+            response
+                .answers()
+                .iter()
+                .filter_map(|record| record.data().and_then(as_dnskey))
+                .filter(|dnskey| dnskey.zone_key() && !dnskey.revoke())
+                .cloned()
+                .collect()
+        })
+}
+
+/// The apex of the zone name belongs to, taken from the SOA record in the answer
+/// or, when name is not the apex, in the authority section.
+fn zone(resolver: &AuthoritiveResolver, name: &Name) -> Result<Name> {
+    let response = resolver.authoritative_answer(name, RecordType::SOA)?;
+    response
+        .answers()
+        .iter()
+        .chain(response.name_servers())
+        .find(|record| record.record_type() == RecordType::SOA)
+        .map(|record| record.name().clone())
+        .ok_or_else(|| Error::NoZone(name.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use hickory_resolver::proto::rr::{rdata::TXT, RData};
+
+    use super::{as_dnskey, as_rrsig, check_expiration};
+    use crate::Error;
+
+    const DAY: Duration = Duration::from_secs(86_400);
+
+    #[test]
+    fn other_records_are_ignored() {
+        let txt = RData::TXT(TXT::new(vec!["JaJaNeeNee".to_owned()]));
+        assert!(as_rrsig(&txt).is_none());
+        assert!(as_dnskey(&txt).is_none());
+    }
+
+    #[test]
+    fn signature_expiration() {
+        let now = 1_700_000_000;
+        let record_name = "_acme-challenge.paulmin.nl.";
+        assert!(check_expiration(record_name, 1_700_000_000 + 7 * 86_400, now, DAY).is_ok());
+        assert!(check_expiration(record_name, 1_700_000_000 + 3_600, now, DAY).is_ok());
+        assert!(matches!(
+            check_expiration(record_name, 1_700_000_000, now, DAY),
+            Err(Error::SignatureExpired(_))
+        ));
+        assert!(matches!(
+            check_expiration(record_name, 1_600_000_000, now, DAY),
+            Err(Error::SignatureExpired(_))
+        ));
+    }
+}
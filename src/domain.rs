@@ -0,0 +1,95 @@
+use crate::Error;
+
+const MAX_NAME_LENGTH: usize = 253;
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// Converts the domain name to ascii and checks that it is a valid host name.
+/// A trailing dot is allowed, a leading wildcard label as well.
+pub(crate) fn normalize(domain_name: &str) -> Result<String, Error> {
+    to_ascii(domain_name).and_then(|ascii| {
+        if is_valid(&ascii) {
+            Ok(ascii)
+        } else {
+            Err(Error::InvalidDomain(domain_name.to_owned()))
+        }
+    })
+}
+
+fn is_valid(ascii: &str) -> bool {
+    let name = ascii.strip_prefix("*.").unwrap_or(ascii);
+    let name = name.strip_suffix('.').unwrap_or(name);
+    !name.is_empty() && name.len() <= MAX_NAME_LENGTH && name.split('.').all(is_valid_label)
+}
+
+fn is_valid_label(label: &str) -> bool {
+    !label.is_empty()
+        && label.len() <= MAX_LABEL_LENGTH
+        && !label.starts_with('-')
+        && !label.ends_with('-')
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Converts a domain name to its ascii form,
+/// internationalized labels are converted to punycode.
+pub(crate) fn to_ascii(domain_name: &str) -> Result<String, Error> {
+    match domain_name.strip_prefix("*.") {
+        Some(domain_name) => to_ascii(domain_name).map(|ascii| format!("*.{ascii}")),
+        None => idna::domain_to_ascii(domain_name)
+            .map_err(|_| Error::InvalidDomain(domain_name.to_owned())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalize, to_ascii};
+    use crate::Error;
+
+    #[test]
+    fn ascii_unchanged() {
+        assert_eq!(to_ascii("paulmin.nl.").unwrap(), "paulmin.nl.");
+    }
+
+    #[test]
+    fn unicode_to_punycode() {
+        assert_eq!(to_ascii("bücher.example").unwrap(), "xn--bcher-kva.example");
+        assert_eq!(
+            to_ascii("*.Bücher.example.").unwrap(),
+            "*.xn--bcher-kva.example."
+        );
+    }
+
+    #[test]
+    fn valid_domains() {
+        assert_eq!(normalize("paulmin.nl").unwrap(), "paulmin.nl");
+        assert_eq!(normalize("paulmin.nl.").unwrap(), "paulmin.nl.");
+        assert_eq!(normalize("*.paulmin.nl").unwrap(), "*.paulmin.nl");
+        assert_eq!(
+            normalize("Bücher.example").unwrap(),
+            "xn--bcher-kva.example"
+        );
+    }
+
+    #[test]
+    fn invalid_domains() {
+        let long_label = format!("{}.nl", "a".repeat(64));
+        let long_name = format!("{}nl", "abcdefghi.".repeat(26));
+        for domain_name in [
+            "",
+            ".",
+            "paulmin..nl",
+            "-paulmin.nl",
+            "paulmin-.nl",
+            "paul min.nl",
+            "paulmin.nl..",
+            long_label.as_str(),
+            long_name.as_str(),
+        ] {
+            assert!(
+                matches!(normalize(domain_name), Err(Error::InvalidDomain(_))),
+                "{domain_name}"
+            );
+        }
+    }
+}
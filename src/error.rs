@@ -1,25 +1,245 @@
-use std::{io, net::AddrParseError};
+use std::{fmt, io, net::AddrParseError};
 
-use hickory_resolver::error::ResolveError;
+use hickory_resolver::{
+    error::ResolveError,
+    proto::{error::ProtoError, op},
+};
 use thiserror::Error;
 
+use crate::report::NameserverReport;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Ipv4")]
     Ipv4,
 
-    #[error("ACME challenge")]
-    AcmeChallege,
+    #[error("ACME challenge not propagated to {}", lagging(.0))]
+    AcmeChallege(Vec<NameserverReport>),
 
     #[error("IO: {0}")]
     IO(#[from] io::Error),
 
     #[error("Resolve: {0}")]
-    Resolve(#[from] ResolveError),
+    Resolve(Box<dyn std::error::Error + Send + Sync>),
 
     #[error("")]
     Parse(#[from] AddrParseError),
 
     #[error("Multiple acme challenges")]
     MultipleAcme,
+
+    #[error("ACME challenge still served by {}", lagging(.0))]
+    AcmeChallengePresent(Vec<NameserverReport>),
+
+    #[error("CNAME chain too long for {0}")]
+    CnameChain(String),
+
+    #[error("No zone found for {0}")]
+    NoZone(String),
+
+    #[error("Invalid domain name {0}")]
+    InvalidDomain(String),
+
+    #[error("Invalid challenge {0}")]
+    InvalidChallenge(String),
+
+    #[error("Challenge {0} is not the digest of a key authorization")]
+    NotDigest(String),
+
+    #[error("Nameserver {nameserver} does not answer for zone {zone}")]
+    Lame { nameserver: String, zone: String },
+
+    #[error("Protocol: {0}")]
+    Proto(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Message too large")]
+    Truncated,
+
+    #[error("Answer for {0} does not match the question")]
+    QuestionMismatch(String),
+
+    #[error("TXT records of {0} are not signed in a signed zone")]
+    Unsigned(String),
+
+    #[error("Signature of the TXT records of {0} is not valid")]
+    InvalidSignature(String),
+
+    #[error("Signature of the TXT records of {0} has expired")]
+    SignatureExpired(String),
+
+    #[error("Answer from {0} is not authoritive")]
+    NotAuthoritative(String),
+
+    #[error("Nameserver {nameserver} answered {response_code}")]
+    Response {
+        nameserver: String,
+        response_code: ResponseCode,
+    },
+
+    #[error("Cancelled")]
+    Cancelled,
+
+    #[error("No nameservers to query")]
+    NoNameservers,
+
+    #[error("No addresses found for nameserver {0}")]
+    NoAddresses(String),
+
+    #[error("Invalid secret for key {0}")]
+    InvalidKey(String),
+
+    #[error("No dns-01 challenge for {0}")]
+    NoDnsChallenge(String),
+
+    #[error("Provider: {0}")]
+    Provider(Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Serial of the primary not reached by {}", lagging(.0))]
+    SerialNotConverged(Vec<NameserverReport>),
+
+    #[error("Record not propagated to {}", lagging(.0))]
+    RecordNotPropagated(Vec<NameserverReport>),
+}
+
+/// Response code of a nameserver answer that is not an answer about the records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ResponseCode {
+    FormErr,
+    ServFail,
+    NXDomain,
+    NotImp,
+    Refused,
+    Other(u16),
+}
+
+impl ResponseCode {
+    pub(crate) fn from_hickory(response_code: op::ResponseCode) -> Self {
+        match response_code {
+            op::ResponseCode::FormErr => ResponseCode::FormErr,
+            op::ResponseCode::ServFail => ResponseCode::ServFail,
+            op::ResponseCode::NXDomain => ResponseCode::NXDomain,
+            op::ResponseCode::NotImp => ResponseCode::NotImp,
+            op::ResponseCode::Refused => ResponseCode::Refused,
+            other => ResponseCode::Other(other.into()),
+        }
+    }
+}
+
+impl fmt::Display for ResponseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResponseCode::FormErr => f.write_str("FORMERR"),
+            ResponseCode::ServFail => f.write_str("SERVFAIL"),
+            ResponseCode::NXDomain => f.write_str("NXDOMAIN"),
+            ResponseCode::NotImp => f.write_str("NOTIMP"),
+            ResponseCode::Refused => f.write_str("REFUSED"),
+            ResponseCode::Other(code) => write!(f, "RCODE{code}"),
+        }
+    }
+}
+
+impl Error {
+    pub(crate) fn resolve(error: ResolveError) -> Self {
+        Error::Resolve(Box::new(error))
+    }
+
+    pub(crate) fn proto(error: ProtoError) -> Self {
+        Error::Proto(Box::new(error))
+    }
+
+    pub(crate) fn response(nameserver: String, response_code: op::ResponseCode) -> Self {
+        Error::Response {
+            nameserver,
+            response_code: ResponseCode::from_hickory(response_code),
+        }
+    }
+
+    /// Waiting ended because the nameservers were not up to date in time.
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            self,
+            Error::AcmeChallege(_)
+                | Error::AcmeChallengePresent(_)
+                | Error::SerialNotConverged(_)
+                | Error::RecordNotPropagated(_)
+        )
+    }
+
+    /// A single nameserver failed in a way that may pass, like a timeout or SERVFAIL.
+    pub(crate) fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Error::Resolve(_)
+                | Error::IO(_)
+                | Error::Response {
+                    response_code: ResponseCode::ServFail | ResponseCode::Refused,
+                    ..
+                }
+        )
+    }
+}
+
+fn lagging(nameservers: &[NameserverReport]) -> String {
+    nameservers
+        .iter()
+        .map(|nameserver| {
+            format!(
+                "{} ({})",
+                nameserver.name,
+                nameserver
+                    .addresses
+                    .iter()
+                    .map(|address| address.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use hickory_resolver::proto::op;
+
+    use super::{Error, ResponseCode};
+    use crate::report::NameserverReport;
+
+    #[test]
+    fn acme_challenge_names_lagging_nameservers() {
+        let error = Error::AcmeChallege(vec![NameserverReport {
+            name: "ns1.transip.nl.".to_owned(),
+            addresses: vec![
+                "2a01:7c8:7000:195::195".parse().unwrap(),
+                "195.135.195.195".parse().unwrap(),
+            ],
+            propagated_after: None,
+        }]);
+        assert_eq!(
+            error.to_string(),
+            "ACME challenge not propagated to ns1.transip.nl. (2a01:7c8:7000:195::195, 195.135.195.195)"
+        );
+    }
+
+    #[test]
+    fn response_code_of_the_crate() {
+        let error = Error::response("ns1.transip.nl.".to_owned(), op::ResponseCode::Refused);
+        assert!(matches!(
+            error,
+            Error::Response {
+                response_code: ResponseCode::Refused,
+                ..
+            }
+        ));
+        assert!(error.is_transient());
+        assert_eq!(
+            error.to_string(),
+            "Nameserver ns1.transip.nl. answered REFUSED"
+        );
+        assert_eq!(
+            ResponseCode::from_hickory(op::ResponseCode::BADCOOKIE),
+            ResponseCode::Other(23)
+        );
+    }
 }
@@ -0,0 +1,121 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+/// Target of the log lines of the events.
+pub(crate) const TARGET: &str = "acme_validation_propagation::event";
+
+/// Progress reported while waiting for propagation.
+/// Serialized with the name of the event in the `event` field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(tag = "event", rename_all = "snake_case")
+)]
+pub enum PropagationEvent {
+    /// A new round of checking all nameservers starts.
+    AttemptStarted { attempt: usize },
+    /// The nameserver serves the expected challenge.
+    NameserverVerified { attempt: usize, nameserver: String },
+    /// The nameserver does not serve the expected challenge yet.
+    NameserverLagging { attempt: usize, nameserver: String },
+    /// All nameservers serve the expected challenge.
+    Done { attempts: usize },
+    /// Waiting was abandoned because the retries or the deadline ran out.
+    TimedOut { attempts: usize },
+    /// Waiting was abandoned because of an error.
+    Failed { error: String },
+}
+
+/// Callback receiving every [`PropagationEvent`].
+#[derive(Clone)]
+pub(crate) struct Observer(Arc<dyn Fn(&PropagationEvent) + Send + Sync>);
+
+impl Observer {
+    pub(crate) fn new<F>(f: F) -> Self
+    where
+        F: Fn(&PropagationEvent) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn notify(&self, event: &PropagationEvent) {
+        (self.0)(event)
+    }
+}
+
+impl fmt::Debug for Observer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Observer")
+    }
+}
+
+impl PropagationEvent {
+    /// Name of the event, the `event` field of the log lines.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Self::AttemptStarted { .. } => "attempt_started",
+            Self::NameserverVerified { .. } => "nameserver_verified",
+            Self::NameserverLagging { .. } => "nameserver_lagging",
+            Self::Done { .. } => "done",
+            Self::TimedOut { .. } => "timed_out",
+            Self::Failed { .. } => "failed",
+        }
+    }
+}
+
+/// Logs the event with the fields event, domain, elapsed_ms and, depending on the event,
+/// attempt, nameserver or error. These names are kept stable for log processing.
+pub(crate) fn log(event: &PropagationEvent, domain: &str, elapsed: Duration) {
+    let name = event.name();
+    let elapsed_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+    match event {
+        PropagationEvent::AttemptStarted { attempt } => tracing::info!(
+            target: TARGET,
+            event = name,
+            domain,
+            elapsed_ms,
+            attempt = *attempt,
+            "Attempt started"
+        ),
+        PropagationEvent::NameserverVerified {
+            attempt,
+            nameserver,
+        }
+        | PropagationEvent::NameserverLagging {
+            attempt,
+            nameserver,
+        } => tracing::info!(
+            target: TARGET,
+            event = name,
+            domain,
+            elapsed_ms,
+            attempt = *attempt,
+            nameserver = nameserver.as_str(),
+            "Nameserver checked"
+        ),
+        PropagationEvent::Done { attempts } => tracing::info!(
+            target: TARGET,
+            event = name,
+            domain,
+            elapsed_ms,
+            attempt = *attempts,
+            "Propagated"
+        ),
+        PropagationEvent::TimedOut { attempts } => tracing::warn!(
+            target: TARGET,
+            event = name,
+            domain,
+            elapsed_ms,
+            attempt = *attempts,
+            "Timed out"
+        ),
+        PropagationEvent::Failed { error } => tracing::warn!(
+            target: TARGET,
+            event = name,
+            domain,
+            elapsed_ms,
+            error = error.as_str(),
+            "Failed"
+        ),
+    }
+}
@@ -0,0 +1,82 @@
+use std::net::IpAddr;
+
+/// Selects the authoritive nameservers that are checked.
+/// Patterns are host names or ip addresses.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct NameserverFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl NameserverFilter {
+    pub(crate) fn include<S: AsRef<str>>(&mut self, pattern: S) {
+        self.include.push(pattern.as_ref().to_owned());
+    }
+
+    pub(crate) fn exclude<S: AsRef<str>>(&mut self, pattern: S) {
+        self.exclude.push(pattern.as_ref().to_owned());
+    }
+
+    pub(crate) fn allows(&self, name: &str, addresses: &[IpAddr]) -> bool {
+        (self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| matches(pattern, name, addresses)))
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| matches(pattern, name, addresses))
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn matches(pattern: &str, name: &str, addresses: &[IpAddr]) -> bool {
+    match pattern.parse::<IpAddr>() {
+        Ok(address) => addresses.contains(&address),
+        Err(_) => normalize(pattern) == normalize(name),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+
+    use super::NameserverFilter;
+
+    fn addresses() -> Vec<IpAddr> {
+        vec!["2a01:7c8:7000:195::195".parse().unwrap()]
+    }
+
+    #[test]
+    fn empty_allows_all() {
+        assert!(NameserverFilter::default().allows("ns1.transip.nl.", &addresses()));
+    }
+
+    #[test]
+    fn include_by_name() {
+        let mut filter = NameserverFilter::default();
+        filter.include("NS0.transip.net");
+        assert!(filter.allows("ns0.transip.net.", &addresses()));
+        assert!(!filter.allows("ns1.transip.nl.", &addresses()));
+    }
+
+    #[test]
+    fn exclude_by_address() {
+        let mut filter = NameserverFilter::default();
+        filter.exclude("2a01:7c8:7000:195::195");
+        assert!(!filter.allows("ns1.transip.nl.", &addresses()));
+        assert!(filter.allows("ns0.transip.net.", &[]));
+    }
+
+    #[test]
+    fn exclude_wins() {
+        let mut filter = NameserverFilter::default();
+        filter.include("ns1.transip.nl");
+        filter.exclude("ns1.transip.nl.");
+        assert!(!filter.allows("ns1.transip.nl.", &addresses()));
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    panic::resume_unwind,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
+
+use crate::{cancel::CancellationToken, event::PropagationEvent, Result};
+
+/// Current state of a wait running in the background.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum PropagationStatus {
+    /// The nameservers have not been checked yet.
+    #[default]
+    Starting,
+    /// The nameservers are being checked.
+    Checking {
+        attempt: usize,
+        verified: Vec<String>,
+        lagging: Vec<String>,
+    },
+    /// All nameservers serve the expected challenge.
+    Propagated { attempts: usize },
+    /// The retries or the deadline ran out.
+    TimedOut { attempts: usize },
+    /// Waiting was abandoned because of an error.
+    Failed { error: String },
+}
+
+impl PropagationStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            Self::Propagated { .. } | Self::TimedOut { .. } | Self::Failed { .. }
+        )
+    }
+
+    pub(crate) fn apply(&mut self, event: &PropagationEvent) {
+        match event {
+            PropagationEvent::AttemptStarted { attempt } => {
+                *self = Self::Checking {
+                    attempt: *attempt,
+                    verified: vec![],
+                    lagging: vec![],
+                };
+            }
+            PropagationEvent::NameserverVerified { nameserver, .. } => {
+                if let Self::Checking { verified, .. } = self {
+                    verified.push(nameserver.clone());
+                }
+            }
+            PropagationEvent::NameserverLagging { nameserver, .. } => {
+                if let Self::Checking { lagging, .. } = self {
+                    lagging.push(nameserver.clone());
+                }
+            }
+            PropagationEvent::Done { attempts } => {
+                *self = Self::Propagated {
+                    attempts: *attempts,
+                };
+            }
+            PropagationEvent::TimedOut { attempts } => {
+                *self = Self::TimedOut {
+                    attempts: *attempts,
+                };
+            }
+            PropagationEvent::Failed { error } => {
+                *self = Self::Failed {
+                    error: error.clone(),
+                };
+            }
+        }
+    }
+}
+
+/// Handle to a wait running on a background thread,
+/// returned by [`crate::Propagation::spawn_wait`].
+#[derive(Debug)]
+pub struct WaitHandle {
+    pub(crate) status: Arc<Mutex<PropagationStatus>>,
+    pub(crate) cancellation: CancellationToken,
+    pub(crate) thread: JoinHandle<Result<()>>,
+}
+
+impl WaitHandle {
+    /// Snapshot of the current state.
+    pub fn status(&self) -> PropagationStatus {
+        self.status
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Abandons the wait. [`WaitHandle::join`] will return [`crate::Error::Cancelled`].
+    pub fn abort(&self) {
+        self.cancellation.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Blocks until the wait has finished and returns its result.
+    pub fn join(self) -> Result<()> {
+        self.thread
+            .join()
+            .unwrap_or_else(|panic| resume_unwind(panic))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::PropagationStatus;
+    use crate::event::PropagationEvent;
+
+    #[test]
+    fn status_follows_events() {
+        let mut status = PropagationStatus::default();
+        status.apply(&PropagationEvent::AttemptStarted { attempt: 1 });
+        status.apply(&PropagationEvent::NameserverVerified {
+            attempt: 1,
+            nameserver: "ns0.transip.net.".to_owned(),
+        });
+        status.apply(&PropagationEvent::NameserverLagging {
+            attempt: 1,
+            nameserver: "ns1.transip.nl.".to_owned(),
+        });
+        assert_eq!(
+            status,
+            PropagationStatus::Checking {
+                attempt: 1,
+                verified: vec!["ns0.transip.net.".to_owned()],
+                lagging: vec!["ns1.transip.nl.".to_owned()],
+            }
+        );
+        assert!(!status.is_finished());
+
+        status.apply(&PropagationEvent::Done { attempts: 2 });
+        assert_eq!(status, PropagationStatus::Propagated { attempts: 2 });
+        assert!(status.is_finished());
+    }
+}
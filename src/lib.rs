@@ -1,73 +1,340 @@
 #![doc = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/README.md"))]
 
 use hickory_resolver::{
-    config::{LookupIpStrategy, NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
     Resolver,
 };
-use std::{convert::identity, net::IpAddr, thread::sleep, time::Duration};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
 
-use crate::error::Error;
-use resolver::ResolverType;
+pub use caa::CaaReport;
+pub use cancel::CancellationToken;
+pub use challenge::{dns_account_label, dns_value, ChallengeFormat};
+#[cfg(feature = "tokio")]
+pub use checker::CheckFuture;
+pub use checker::PropagationChecker;
+#[cfg(feature = "acme2")]
+pub use client::wait_acme2;
+#[cfg(feature = "acme-lib")]
+pub use client::wait_acme_lib;
+#[cfg(feature = "instant-acme")]
+pub use client::wait_instant_acme;
+pub use diagnosis::{Diagnosis, NameserverDiagnosis, ZoneDelegation};
+pub use error::{Error, ResponseCode};
+pub use event::PropagationEvent;
+pub use handle::{PropagationStatus, WaitHandle};
+#[cfg(feature = "json-log")]
+pub use log::init_json_log;
+#[cfg(any(test, feature = "test-util"))]
+pub use lookup::MockResolver;
+#[cfg(feature = "tsig")]
+pub use primary::{TsigAlgorithm, TsigKey};
+pub use propagation::Propagation;
+#[cfg(feature = "cloudflare")]
+pub use provider::CloudflareProvider;
+pub use provider::DnsProvider;
+#[cfg(feature = "transip")]
+pub use provider::TransipProvider;
+use query::QueryOptions;
+pub use query::{LookupIpStrategy, ServerOrderingStrategy};
+pub use record::RecordType;
+pub use report::{NameserverReport, PropagationReport};
+pub use resolver::{AuthoritiveResolver, RecursiveResolver, ResolverType};
 
+mod caa;
+mod cancel;
+mod challenge;
+mod checker;
+mod client;
+mod delegation;
+mod diagnosis;
+#[cfg(feature = "dnssec")]
+mod dnssec;
+mod domain;
 mod error;
+mod event;
+mod filter;
+mod handle;
+mod limit;
+#[cfg(feature = "json-log")]
+mod log;
+mod lookup;
+#[cfg(feature = "metrics")]
+mod metric;
+mod primary;
+mod propagation;
+mod provider;
+#[cfg(feature = "python")]
+mod python;
+mod query;
+mod record;
+mod report;
 mod resolver;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-const MAX_RETRIES: usize = 720;
-const WAIT_SECONDS: u64 = 5;
-
 fn ipv6_resolver(
     group: NameServerConfigGroup,
     recursion: bool,
     ipv6_only: bool,
+    tuning: &QueryOptions,
 ) -> Result<Resolver> {
-    let config = ResolverConfig::from_parts(None, vec![], group);
+    let config = ResolverConfig::from_parts(None, vec![], tuning.bind(group));
     let mut options = ResolverOpts::default();
+    tuning.tune(&mut options);
     if ipv6_only {
-        options.ip_strategy = LookupIpStrategy::Ipv6Only;
+        options.ip_strategy = LookupIpStrategy::Ipv6Only.hickory();
     }
     options.recursion_desired = recursion;
     options.use_hosts_file = false;
     Resolver::new(config, options).map_err(Error::from)
 }
 
-fn recursive_resolver(ips: &[IpAddr], ipv6_only: bool) -> Result<Resolver> {
+fn recursive_resolver(ips: &[IpAddr], ipv6_only: bool, tuning: &QueryOptions) -> Result<Resolver> {
     let group = NameServerConfigGroup::from_ips_clear(ips, 53, false);
-    ipv6_resolver(group, true, ipv6_only)
+    ipv6_resolver(group, true, ipv6_only, tuning)
 }
 
 /// wait checks the authoritive nameservers periodically.
 /// It returns Ok(()) when all nameservers have the challenge.
 /// It returns an error after several attempts failed.
+///
+/// Use [`Propagation`] to change the number of attempts or the polling interval.
+///
+/// This function blocks the current thread while waiting.
 pub fn wait<S>(domain_name: S, challenge: S) -> Result<()>
 where
     S: AsRef<str>,
 {
-    let resolvers = ResolverType::Google
-        .recursive_resolver(false)
-        .and_then(|resolver| resolver.authoritive_resolvers(domain_name.as_ref()))?;
+    Propagation::default().wait(domain_name, challenge)
+}
+
+/// wait_with_resolver behaves like [`wait`] but finds the authoritive nameservers
+/// with the given recursive resolver instead of the public resolver of Google.
+pub fn wait_with_resolver<S>(domain_name: S, challenge: S, resolver: ResolverType) -> Result<()>
+where
+    S: AsRef<str>,
+{
+    Propagation::default()
+        .resolver(resolver)
+        .wait(domain_name, challenge)
+}
 
-    let mut i: usize = 0;
+/// wait_with_servers behaves like [`wait`] but checks the authoritive nameservers
+/// at the given addresses, without discovering the nameservers of the zone.
+pub fn wait_with_servers<S>(domain_name: S, challenge: S, servers: &[IpAddr]) -> Result<()>
+where
+    S: AsRef<str>,
+{
+    Propagation::default()
+        .servers(servers)
+        .wait(domain_name, challenge)
+}
 
-    sleep(Duration::from_secs(1));
-    while !resolvers
-        .iter()
-        .map(|resolver| resolver.has_single_acme(domain_name.as_ref(), challenge.as_ref()))
-        .collect::<Result<Vec<_>>>()?
-        .into_iter()
-        .all(identity)
-        && i < MAX_RETRIES
-    {
-        i += 1;
-        tracing::warn!("Attempt {} failed", i);
-        sleep(Duration::from_secs(WAIT_SECONDS));
-    }
-    if i >= MAX_RETRIES {
-        tracing::error!("Timeout checking acme challenge record");
-        Err(Error::AcmeChallege)
-    } else {
-        Ok(())
+/// wait_values checks the authoritive nameservers periodically.
+/// It returns Ok(()) when all nameservers have all the challenges,
+/// as needed for an order with both `example.com` and `*.example.com`.
+/// It returns an error after several attempts failed.
+pub fn wait_values<S>(domain_name: S, challenges: &[S]) -> Result<()>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().wait_values(domain_name, challenges)
+}
+
+/// wait_for_key_authorization checks the authoritive nameservers periodically.
+/// The challenge is computed from the acme key authorization with [`dns_value`].
+/// It returns Ok(()) when all nameservers have the challenge.
+/// It returns an error after several attempts failed.
+pub fn wait_for_key_authorization<S>(domain_name: S, key_authorization: S) -> Result<()>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().wait_for_key_authorization(domain_name, key_authorization)
+}
+
+/// wait_all checks the challenges of several domains at the same time.
+/// It returns the result for every domain name.
+pub fn wait_all<K, V>(challenges: &HashMap<K, V>) -> HashMap<String, Result<()>>
+where
+    K: AsRef<str> + Sync,
+    V: AsRef<str> + Sync,
+{
+    Propagation::default().wait_all(challenges)
+}
+
+/// wait_absent checks the authoritive nameservers periodically.
+/// It returns Ok(()) when none of the nameservers serves the challenge anymore.
+/// It returns an error after several attempts failed.
+pub fn wait_absent<S>(domain_name: S, challenge: S) -> Result<()>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().wait_absent(domain_name, challenge)
+}
+
+/// wait_report checks the authoritive nameservers periodically.
+/// It returns a report with timing details when all nameservers have the challenge.
+/// It returns an error after several attempts failed.
+pub fn wait_report<S>(domain_name: S, challenge: S) -> Result<PropagationReport>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().wait_report(domain_name, challenge)
+}
+
+/// wait_with_deadline checks the authoritive nameservers periodically until the deadline.
+/// It returns Ok(()) when all nameservers have the challenge.
+/// It returns an error when the deadline has passed.
+pub fn wait_with_deadline<S>(domain_name: S, challenge: S, deadline: Instant) -> Result<()>
+where
+    S: AsRef<str>,
+{
+    Propagation::default()
+        .retries(usize::MAX)
+        .wait_until(domain_name, challenge, deadline)
+}
+
+/// wait_events checks the authoritive nameservers periodically on a background thread.
+/// The returned blocking iterator yields the progress of the wait until it has finished.
+pub fn wait_events<S>(domain_name: S, challenge: S) -> impl Iterator<Item = PropagationEvent>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().wait_events(domain_name, challenge)
+}
+
+/// wait_stream checks the authoritive nameservers periodically on a task of the current
+/// tokio runtime. The returned stream yields the progress of the wait until it has finished.
+#[cfg(feature = "tokio")]
+pub fn wait_stream<S>(
+    domain_name: S,
+    challenge: S,
+) -> impl tokio_stream::Stream<Item = PropagationEvent>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().wait_stream(domain_name, challenge)
+}
+
+/// spawn_wait checks the authoritive nameservers periodically on a background thread.
+/// The returned handle can be polled for the current status and used to abort the wait.
+pub fn spawn_wait<S>(domain_name: S, challenge: S) -> WaitHandle
+where
+    S: AsRef<str>,
+{
+    Propagation::default().spawn_wait(domain_name, challenge)
+}
+
+/// negative_cache_ttl returns how long the public recursive resolver may keep answering
+/// that the acme challenge record does not exist, None if the record is visible.
+pub fn negative_cache_ttl<S>(domain_name: S) -> Result<Option<Duration>>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().negative_cache_ttl(domain_name)
+}
+
+/// provision_and_wait creates the challenge record with the provider and behaves like
+/// [`wait_report`]. When waiting fails the record is deleted again.
+///
+/// Use [`Propagation::cleanup_and_wait`] to delete the record after the validation.
+pub fn provision_and_wait<P, S>(
+    provider: &P,
+    domain_name: S,
+    challenge: S,
+) -> Result<PropagationReport>
+where
+    P: DnsProvider,
+    S: AsRef<str>,
+{
+    Propagation::default().provision_and_wait(provider, domain_name, challenge)
+}
+
+/// check_caa reports whether the CAA records of the domain permit the certification
+/// authority with the issuer domain name ca to issue a certificate for the domain,
+/// before the order is placed.
+pub fn check_caa<S>(domain_name: S, ca: S) -> Result<CaaReport>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().check_caa(domain_name, ca)
+}
+
+/// diagnose returns the delegation chain, the authoritive nameservers with their addresses
+/// and the TXT values each of them serves for the challenge record of the domain.
+pub fn diagnose<S>(domain_name: S) -> Result<Diagnosis>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().diagnose(domain_name)
+}
+
+/// wait_record checks the authoritive nameservers periodically.
+/// It returns a report when all nameservers serve the expected values
+/// in the records of the type at the name.
+/// It returns an error after several attempts failed.
+pub fn wait_record<S>(
+    name: S,
+    record_type: RecordType,
+    expected_values: &[S],
+) -> Result<PropagationReport>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().wait_record(name, record_type, expected_values)
+}
+
+/// wait_txt checks the authoritive nameservers periodically.
+/// It returns a report when all nameservers serve the expected value in the TXT records
+/// at the name, for example a domain verification token of a provider.
+/// It returns an error after several attempts failed.
+pub fn wait_txt<S>(name: S, expected_value: S) -> Result<PropagationReport>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().wait_txt(name, expected_value)
+}
+
+/// wait_tlsa checks the authoritive nameservers periodically.
+/// It returns a report when all nameservers serve the expected TLSA records
+/// for the tcp port of the domain.
+/// It returns an error after several attempts failed.
+pub fn wait_tlsa<S>(domain_name: S, port: u16, expected_values: &[S]) -> Result<PropagationReport>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().wait_tlsa(domain_name, port, expected_values)
+}
+
+/// wait_serial_converged checks the authoritive nameservers periodically.
+/// It returns a report when all nameservers serve the SOA serial of the primary nameserver.
+/// It returns an error after several attempts failed.
+pub fn wait_serial_converged<S>(domain_name: S) -> Result<PropagationReport>
+where
+    S: AsRef<str>,
+{
+    Propagation::default().wait_serial_converged(domain_name)
+}
+
+/// wait_for checks the authoritive nameservers periodically for at most timeout.
+/// It returns Ok(()) when all nameservers have the challenge.
+/// It returns an error when the timeout has elapsed.
+/// A timeout too large to be an instant waits without deadline.
+pub fn wait_for<S>(domain_name: S, challenge: S, timeout: Duration) -> Result<()>
+where
+    S: AsRef<str>,
+{
+    match Instant::now().checked_add(timeout) {
+        Some(deadline) => wait_with_deadline(domain_name, challenge, deadline),
+        None => Propagation::default()
+            .retries(usize::MAX)
+            .wait(domain_name, challenge),
     }
 }
 
@@ -92,11 +359,11 @@ mod tests {
     }
 
     fn lookup(name: &str) -> impl Fn(Resolver) -> Result<Ipv6Lookup, Error> + '_ {
-        move |resolver| resolver.ipv6_lookup(name).map_err(Error::from)
+        move |resolver| resolver.ipv6_lookup(name).map_err(Error::resolve)
     }
 
     fn ns_lookup(name: &str) -> impl Fn(Resolver) -> Result<NsLookup, Error> + '_ {
-        move |resolver| resolver.ns_lookup(name).map_err(Error::from)
+        move |resolver| resolver.ns_lookup(name).map_err(Error::resolve)
     }
 
     fn aaaa_mapper(f: fn(AAAA) -> IpAddr) -> impl Fn(Ipv6Lookup) -> Vec<IpAddr> {
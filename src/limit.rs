@@ -0,0 +1,170 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// Token bucket limiting the number of queries sent to the authoritive nameservers.
+///
+/// Clones share the same bucket, so all waits of a [`crate::Propagation`] together
+/// stay within the limit.
+#[derive(Clone, Debug)]
+pub(crate) struct RateLimiter {
+    inner: Arc<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    /// Allows queries_per_second on average with bursts of at most burst queries.
+    pub(crate) fn new(queries_per_second: u32, burst: u32) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Bucket::new(
+                queries_per_second.max(1),
+                burst.max(1),
+                Instant::now(),
+            ))),
+        }
+    }
+
+    /// Blocks until a query may be sent.
+    pub(crate) fn acquire(&self) {
+        let wait = self
+            .inner
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take(Instant::now());
+        if !wait.is_zero() {
+            tracing::trace!("Rate limited for {:?}", wait);
+            sleep(wait);
+        }
+    }
+}
+
+/// Semaphore limiting the number of queries in flight at the same time.
+///
+/// Clones share the same permits.
+#[derive(Clone, Debug)]
+pub(crate) struct QueryLimit {
+    inner: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl QueryLimit {
+    pub(crate) fn new(max_in_flight: usize) -> Self {
+        Self {
+            inner: Arc::new((Mutex::new(max_in_flight.max(1)), Condvar::new())),
+        }
+    }
+
+    /// Blocks until fewer than the maximum number of queries are in flight.
+    /// The query is in flight until the permit is dropped.
+    pub(crate) fn acquire(&self) -> Permit {
+        let (available, condvar) = &*self.inner;
+        let mut guard = available.lock().unwrap_or_else(|e| e.into_inner());
+        while *guard == 0 {
+            guard = condvar.wait(guard).unwrap_or_else(|e| e.into_inner());
+        }
+        *guard -= 1;
+        Permit {
+            limit: self.clone(),
+        }
+    }
+
+    fn release(&self) {
+        let (available, condvar) = &*self.inner;
+        *available.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        condvar.notify_one();
+    }
+}
+
+/// A query in flight, see [`QueryLimit::acquire`].
+pub(crate) struct Permit {
+    limit: QueryLimit,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limit.release();
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Tokens available, negative when queries are waiting for a token
+    tokens: f64,
+    capacity: f64,
+    per_second: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(per_second: u32, capacity: u32, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(capacity),
+            capacity: f64::from(capacity),
+            per_second: f64::from(per_second),
+            updated: now,
+        }
+    }
+
+    /// Takes a token and returns how long to wait before it may be used.
+    /// A token that is not available yet is reserved, so waiting queries keep their order.
+    fn take(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_second)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::mpsc::channel,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::{Bucket, QueryLimit};
+
+    #[test]
+    fn burst_then_rate() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(10, 2, now);
+        assert_eq!(bucket.take(now), Duration::ZERO);
+        assert_eq!(bucket.take(now), Duration::ZERO);
+        assert_eq!(bucket.take(now), Duration::from_millis(100));
+        assert_eq!(bucket.take(now), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn refill_up_to_capacity() {
+        let now = Instant::now();
+        let mut bucket = Bucket::new(10, 2, now);
+        bucket.take(now);
+        bucket.take(now);
+        let later = now + Duration::from_secs(10);
+        assert_eq!(bucket.take(later), Duration::ZERO);
+        assert_eq!(bucket.take(later), Duration::ZERO);
+        assert_eq!(bucket.take(later), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn permits_are_released() {
+        let limit = QueryLimit::new(1);
+        let permit = limit.acquire();
+        let (sender, receiver) = channel();
+        let clone = limit.clone();
+        let handle = thread::spawn(move || {
+            let _permit = clone.acquire();
+            sender.send(()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+        drop(permit);
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).is_ok());
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,15 @@
+/// Installs a global subscriber that writes the log lines to stderr as json objects,
+/// one per line with the fields at the top level. The progress of a wait is logged
+/// with the target `acme_validation_propagation::event` and the fields `event`, `domain`,
+/// `elapsed_ms`, `attempt`, `nameserver` and `error`.
+///
+/// Returns false when a global subscriber was installed already.
+pub fn init_json_log() -> bool {
+    tracing_subscriber::fmt()
+        .json()
+        .flatten_event(true)
+        .with_current_span(false)
+        .with_writer(std::io::stderr)
+        .try_init()
+        .is_ok()
+}
@@ -0,0 +1,59 @@
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use hickory_resolver::proto::rr::{
+    rdata::{caa::CAA, SOA},
+    Record, RecordType,
+};
+
+#[cfg(any(test, feature = "test-util"))]
+pub use self::mock::MockResolver;
+use crate::{query::QueryOptions, Error};
+
+#[cfg(any(test, feature = "test-util"))]
+mod mock;
+
+/// The lookups needed to find and check the authoritive nameservers,
+/// implemented by the resolvers of hickory and by the mock of the `test-util` feature.
+pub(crate) trait Lookup: Send + Sync {
+    /// The TXT values of the name, empty if it has none.
+    fn txt(&self, name: &str) -> Result<Vec<String>, Error>;
+
+    /// The host names of the nameservers of the name, empty if it is not a zone.
+    fn ns(&self, name: &str) -> Result<Vec<String>, Error>;
+
+    /// The ipv4 addresses of the host, empty if it has no A records.
+    fn a(&self, host_name: &str) -> Result<Vec<IpAddr>, Error>;
+
+    /// The ipv6 addresses of the host, empty if it has no AAAA records.
+    fn aaaa(&self, host_name: &str) -> Result<Vec<IpAddr>, Error>;
+
+    /// The CAA records of the name, empty if it has none.
+    fn caa(&self, name: &str) -> Result<Vec<CAA>, Error>;
+
+    /// The lookups on the authoritive nameserver at the addresses.
+    fn authoritive(
+        &self,
+        name: &str,
+        addresses: &[IpAddr],
+        options: &QueryOptions,
+    ) -> Result<Arc<dyn Lookup>, Error>;
+
+    /// False if the name does not exist, so no records exist at or below it.
+    fn exists(&self, name: &str) -> Result<bool, Error>;
+
+    /// The target of the CNAME record of the name, None if it is not an alias.
+    fn cname(&self, name: &str) -> Result<Option<String>, Error>;
+
+    /// The SOA record of the zone, None if there is none.
+    fn soa(&self, zone: &str) -> Result<Option<SOA>, Error>;
+
+    /// How long the answer that the TXT record of the name does not exist may be cached,
+    /// None if the record exists.
+    fn negative_ttl(&self, name: &str) -> Result<Option<Duration>, Error>;
+
+    /// The records of any type of the name, empty if it has none.
+    fn records(&self, name: &str, record_type: RecordType) -> Result<Vec<Record>, Error>;
+
+    /// Forgets the cached answers, so the next lookups are answered by the nameservers.
+    fn clear_cache(&self) {}
+}
@@ -0,0 +1,454 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use hickory_resolver::proto::rr::{
+    rdata::{caa::CAA, A, AAAA, CNAME, NS, SOA, TXT},
+    Name, RData, Record, RecordType,
+};
+
+use super::Lookup;
+use crate::{query::QueryOptions, Error, ResponseCode};
+
+/// Scripted answers of the recursive resolver and the authoritive nameservers,
+/// to test waiting without network. Used with [`ResolverType::Mock`](crate::ResolverType::Mock),
+/// clones share the answers. Every zone with a nameserver has a SOA record with serial 1
+/// and a negative TTL of 300 seconds, names without any records do not exist.
+///
+/// ```
+/// use std::time::Duration;
+/// use acme_validation_propagation::{MockResolver, Propagation, ResolverType};
+///
+/// let mock = MockResolver::new();
+/// mock.nameserver("example.com", "ns1.example.com", "192.0.2.1".parse().unwrap())
+///     .nameserver("example.com", "ns2.example.com", "192.0.2.2".parse().unwrap())
+///     .txt("ns1.example.com", "_acme-challenge.example.com", &["89823875"])
+///     .txt_after("ns2.example.com", "_acme-challenge.example.com", 2, &["89823875"]);
+/// let report = Propagation::new()
+///     .resolver(ResolverType::Mock(mock))
+///     .initial_delay(Duration::ZERO)
+///     .interval(Duration::ZERO)
+///     .wait_report("example.com", "89823875")
+///     .unwrap();
+/// assert_eq!(report.nameservers.len(), 2);
+/// ```
+#[derive(Clone, Default)]
+pub struct MockResolver {
+    answers: Arc<Mutex<Answers>>,
+    /// The authoritive nameserver answering, None for the recursive resolver
+    nameserver: Option<String>,
+}
+
+#[derive(Default)]
+struct Answers {
+    nameservers: HashMap<String, Vec<String>>,
+    addresses: HashMap<String, Vec<IpAddr>>,
+    /// TXT answers by nameserver and name, every lookup takes the first until one is left.
+    /// None fails the lookup.
+    txt: HashMap<(String, String), VecDeque<Option<Vec<String>>>>,
+    caa: HashMap<String, Vec<CAA>>,
+    cnames: HashMap<String, String>,
+    /// Serials by nameserver and zone, None for a nameserver without the zone
+    serials: HashMap<(String, String), Option<u32>>,
+}
+
+impl MockResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an authoritive nameserver of the zone with its address.
+    pub fn nameserver(&self, zone: &str, host_name: &str, address: IpAddr) -> &Self {
+        let mut answers = self.lock();
+        answers
+            .nameservers
+            .entry(normalize(zone))
+            .or_default()
+            .push(host_name.to_owned());
+        answers
+            .addresses
+            .entry(normalize(host_name))
+            .or_default()
+            .push(address);
+        drop(answers);
+        self
+    }
+
+    /// The nameserver serves the values at the name.
+    pub fn txt(&self, nameserver: &str, name: &str, values: &[&str]) -> &Self {
+        self.script(nameserver, name, vec![Some(owned(values))])
+    }
+
+    /// The nameserver answers without values for the first lookups of the name,
+    /// then serves the values, like a nameserver that lags behind.
+    pub fn txt_after(
+        &self,
+        nameserver: &str,
+        name: &str,
+        lookups: usize,
+        values: &[&str],
+    ) -> &Self {
+        let mut answers = vec![Some(vec![]); lookups];
+        answers.push(Some(owned(values)));
+        self.script(nameserver, name, answers)
+    }
+
+    /// Lookups of the name on the nameserver fail with SERVFAIL.
+    pub fn fail(&self, nameserver: &str, name: &str) -> &Self {
+        self.script(nameserver, name, vec![None])
+    }
+
+    /// Lookups of the name on the nameserver fail with SERVFAIL the first lookups,
+    /// then the nameserver serves the values, like a nameserver that recovers.
+    pub fn fail_before(
+        &self,
+        nameserver: &str,
+        name: &str,
+        lookups: usize,
+        values: &[&str],
+    ) -> &Self {
+        let mut answers = vec![None; lookups];
+        answers.push(Some(owned(values)));
+        self.script(nameserver, name, answers)
+    }
+
+    /// The recursive resolver answers a CAA record with the issue tag at the name,
+    /// an empty or invalid issuer forbids issuing.
+    pub fn caa_issue(&self, name: &str, issuer: &str) -> &Self {
+        self.add_caa(name, CAA::new_issue(false, issuer_name(issuer), vec![]))
+    }
+
+    /// The recursive resolver answers a CAA record with the issuewild tag at the name,
+    /// an empty or invalid issuer forbids issuing for wildcard names.
+    pub fn caa_issuewild(&self, name: &str, issuer: &str) -> &Self {
+        self.add_caa(name, CAA::new_issuewild(false, issuer_name(issuer), vec![]))
+    }
+
+    /// The recursive resolver answers that the name is an alias of the target.
+    pub fn cname(&self, name: &str, target: &str) -> &Self {
+        self.lock()
+            .cnames
+            .insert(normalize(name), target.to_owned());
+        self
+    }
+
+    /// The nameserver answers the SOA record of the zone with the serial.
+    pub fn serial(&self, nameserver: &str, zone: &str, serial: u32) -> &Self {
+        self.lock()
+            .serials
+            .insert((normalize(nameserver), normalize(zone)), Some(serial));
+        self
+    }
+
+    /// The nameserver answers without a SOA record for the zone, like a lame delegation.
+    pub fn lame(&self, nameserver: &str, zone: &str) -> &Self {
+        self.lock()
+            .serials
+            .insert((normalize(nameserver), normalize(zone)), None);
+        self
+    }
+
+    fn add_caa(&self, name: &str, record: CAA) -> &Self {
+        self.lock()
+            .caa
+            .entry(normalize(name))
+            .or_default()
+            .push(record);
+        self
+    }
+
+    fn script(&self, nameserver: &str, name: &str, answers: Vec<Option<Vec<String>>>) -> &Self {
+        self.lock()
+            .txt
+            .insert((normalize(nameserver), normalize(name)), answers.into());
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Answers> {
+        self.answers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn addresses(&self, host_name: &str, ipv4: bool) -> Vec<IpAddr> {
+        self.lock()
+            .addresses
+            .get(&normalize(host_name))
+            .into_iter()
+            .flatten()
+            .filter(|address| address.is_ipv4() == ipv4)
+            .copied()
+            .collect()
+    }
+}
+
+impl Lookup for MockResolver {
+    fn txt(&self, name: &str) -> Result<Vec<String>, Error> {
+        let nameserver = self.nameserver.clone().unwrap_or_default();
+        let mut answers = self.lock();
+        let answer = match answers.txt.get_mut(&(nameserver.clone(), normalize(name))) {
+            Some(script) if script.len() > 1 => script.pop_front().flatten(),
+            Some(script) => script.front().cloned().flatten(),
+            None => Some(vec![]),
+        };
+        answer.ok_or(Error::Response {
+            nameserver,
+            response_code: ResponseCode::ServFail,
+        })
+    }
+
+    fn ns(&self, name: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .lock()
+            .nameservers
+            .get(&normalize(name))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn a(&self, host_name: &str) -> Result<Vec<IpAddr>, Error> {
+        Ok(self.addresses(host_name, true))
+    }
+
+    fn aaaa(&self, host_name: &str) -> Result<Vec<IpAddr>, Error> {
+        Ok(self.addresses(host_name, false))
+    }
+
+    fn caa(&self, name: &str) -> Result<Vec<CAA>, Error> {
+        Ok(self
+            .lock()
+            .caa
+            .get(&normalize(name))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, Error> {
+        let name = normalize(name);
+        let at_or_below = |other: &String| {
+            other == &name
+                || other
+                    .strip_suffix(name.as_str())
+                    .map_or(false, |prefix| prefix.ends_with('.'))
+        };
+        let answers = self.lock();
+        Ok(answers.nameservers.keys().any(at_or_below)
+            || answers.addresses.keys().any(at_or_below)
+            || answers.txt.keys().any(|(_, other)| at_or_below(other))
+            || answers.caa.keys().any(at_or_below)
+            || answers.cnames.keys().any(at_or_below))
+    }
+
+    fn cname(&self, name: &str) -> Result<Option<String>, Error> {
+        Ok(self.lock().cnames.get(&normalize(name)).cloned())
+    }
+
+    fn soa(&self, zone: &str) -> Result<Option<SOA>, Error> {
+        let zone = normalize(zone);
+        let answers = self.lock();
+        let Some(primary) = answers
+            .nameservers
+            .get(&zone)
+            .and_then(|nameservers| nameservers.first())
+        else {
+            return Ok(None);
+        };
+        let nameserver = self.nameserver.clone().unwrap_or_default();
+        let serial = match answers.serials.get(&(nameserver, zone.clone())) {
+            Some(serial) => *serial,
+            None => Some(1),
+        };
+        serial
+            .map(|serial| {
+                Ok(SOA::new(
+                    Name::from_ascii(primary).map_err(Error::proto)?,
+                    Name::from_ascii(format!("hostmaster.{zone}")).map_err(Error::proto)?,
+                    serial,
+                    3600,
+                    600,
+                    604_800,
+                    NEGATIVE_TTL,
+                ))
+            })
+            .transpose()
+    }
+
+    fn negative_ttl(&self, name: &str) -> Result<Option<Duration>, Error> {
+        Lookup::txt(self, name).map(|values| {
+            values
+                .is_empty()
+                .then(|| Duration::from_secs(NEGATIVE_TTL.into()))
+        })
+    }
+
+    fn records(&self, name: &str, record_type: RecordType) -> Result<Vec<Record>, Error> {
+        let rdata = match record_type {
+            RecordType::A => self.a(name)?.into_iter().map(address).collect(),
+            RecordType::AAAA => self.aaaa(name)?.into_iter().map(address).collect(),
+            RecordType::NS => self
+                .ns(name)?
+                .iter()
+                .map(|host_name| Name::from_ascii(host_name).map(|name| RData::NS(NS(name))))
+                .collect::<Result<_, _>>()
+                .map_err(Error::proto)?,
+            RecordType::CNAME => Lookup::cname(self, name)?
+                .map(|target| Name::from_ascii(target).map(|name| RData::CNAME(CNAME(name))))
+                .transpose()
+                .map_err(Error::proto)?
+                .into_iter()
+                .collect(),
+            RecordType::SOA => self.soa(name)?.map(RData::SOA).into_iter().collect(),
+            RecordType::TXT => Lookup::txt(self, name)?
+                .into_iter()
+                .map(|value| RData::TXT(TXT::new(vec![value])))
+                .collect(),
+            RecordType::CAA => self.caa(name)?.into_iter().map(RData::CAA).collect(),
+            _ => Vec::<RData>::new(),
+        };
+        let name = Name::from_ascii(name).map_err(Error::proto)?;
+        Ok(rdata
+            .into_iter()
+            .map(|rdata| Record::from_rdata(name.clone(), TTL, rdata))
+            .collect())
+    }
+
+    fn authoritive(
+        &self,
+        name: &str,
+        _addresses: &[IpAddr],
+        _options: &QueryOptions,
+    ) -> Result<Arc<dyn Lookup>, Error> {
+        Ok(Arc::new(Self {
+            answers: self.answers.clone(),
+            nameserver: Some(normalize(name)),
+        }))
+    }
+}
+
+impl fmt::Debug for MockResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MockResolver")
+    }
+}
+
+impl PartialEq for MockResolver {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.answers, &other.answers) && self.nameserver == other.nameserver
+    }
+}
+
+impl Eq for MockResolver {}
+
+/// TTL of the records of [`Lookup::records`].
+const TTL: u32 = 3600;
+/// Minimum of the SOA records, the TTL of a negative answer.
+const NEGATIVE_TTL: u32 = 300;
+
+fn address(address: IpAddr) -> RData {
+    match address {
+        IpAddr::V4(address) => RData::A(A(address)),
+        IpAddr::V6(address) => RData::AAAA(AAAA(address)),
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn issuer_name(issuer: &str) -> Option<Name> {
+    Some(issuer)
+        .filter(|issuer| !issuer.is_empty())
+        .and_then(|issuer| Name::from_ascii(issuer).ok())
+}
+
+fn owned(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use hickory_resolver::proto::rr::RecordType;
+
+    use super::MockResolver;
+    use crate::{lookup::Lookup, query::QueryOptions, Error};
+
+    #[test]
+    fn scripted_answers() {
+        let mock = MockResolver::new();
+        mock.nameserver(
+            "paulmin.nl.",
+            "ns1.transip.nl.",
+            "195.135.195.195".parse().unwrap(),
+        )
+        .txt_after(
+            "ns1.transip.nl",
+            "_acme-challenge.paulmin.nl",
+            1,
+            &["JaJaNeeNee"],
+        )
+        .fail("ns1.transip.nl", "_acme-challenge.transip.nl");
+        assert_eq!(mock.ns("PaulMin.nl").unwrap(), vec!["ns1.transip.nl."]);
+        assert_eq!(mock.a("ns1.transip.nl.").unwrap().len(), 1);
+        assert!(mock.aaaa("ns1.transip.nl.").unwrap().is_empty());
+
+        let nameserver: Arc<dyn Lookup> = mock
+            .authoritive("ns1.transip.nl.", &[], &QueryOptions::default())
+            .unwrap();
+        let txt = || nameserver.txt("_acme-challenge.paulmin.nl.").unwrap();
+        assert!(txt().is_empty());
+        assert_eq!(txt(), vec!["JaJaNeeNee"]);
+        assert_eq!(txt(), vec!["JaJaNeeNee"]);
+        assert!(matches!(
+            nameserver.txt("_acme-challenge.transip.nl"),
+            Err(Error::Response { .. })
+        ));
+        assert!(Lookup::txt(&mock, "_acme-challenge.paulmin.nl")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn scripted_zone() {
+        let mock = MockResolver::new();
+        mock.nameserver(
+            "paulmin.nl.",
+            "ns1.transip.nl.",
+            "195.135.195.195".parse().unwrap(),
+        )
+        .serial("ns1.transip.nl", "paulmin.nl", 2024)
+        .cname(
+            "_acme-challenge.www.paulmin.nl",
+            "_acme-challenge.paulmin.nl.",
+        );
+        assert!(mock.exists("www.paulmin.nl").unwrap());
+        assert!(!mock.exists("nonexistent.paulmin.nl").unwrap());
+        assert_eq!(
+            Lookup::cname(&mock, "_acme-challenge.www.paulmin.nl").unwrap(),
+            Some("_acme-challenge.paulmin.nl.".to_owned())
+        );
+        assert_eq!(mock.soa("paulmin.nl").unwrap().unwrap().serial(), 1);
+        assert!(mock.soa("www.paulmin.nl").unwrap().is_none());
+
+        let nameserver = mock
+            .authoritive("ns1.transip.nl.", &[], &QueryOptions::default())
+            .unwrap();
+        assert_eq!(
+            nameserver.soa("paulmin.nl").unwrap().unwrap().serial(),
+            2024
+        );
+        mock.lame("ns1.transip.nl", "paulmin.nl");
+        assert!(nameserver.soa("paulmin.nl").unwrap().is_none());
+
+        let records = mock.records("paulmin.nl", RecordType::NS).unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].to_string().ends_with("NS ns1.transip.nl."));
+        assert!(mock
+            .records("paulmin.nl", RecordType::MX)
+            .unwrap()
+            .is_empty());
+    }
+}
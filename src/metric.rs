@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+use crate::Error;
+
+const ATTEMPTS: &str = "acme_propagation_attempts_total";
+const QUERY_DURATION: &str = "acme_propagation_query_duration_seconds";
+const PROPAGATION_DURATION: &str = "acme_propagation_duration_seconds";
+const FAILURES: &str = "acme_propagation_failures_total";
+
+/// Counts a round of checking all nameservers.
+pub(crate) fn attempt() {
+    metrics::counter!(ATTEMPTS).increment(1);
+}
+
+/// Records how long checking the nameserver took.
+pub(crate) fn query(nameserver: &str, elapsed: Duration) {
+    metrics::histogram!(QUERY_DURATION, "nameserver" => nameserver.to_owned())
+        .record(elapsed.as_secs_f64());
+}
+
+/// Records the time from the start of the wait until all nameservers were up to date.
+pub(crate) fn propagated(duration: Duration) {
+    metrics::histogram!(PROPAGATION_DURATION).record(duration.as_secs_f64());
+}
+
+/// Counts a wait that ended without propagation, labeled with the kind of error.
+pub(crate) fn failure(error: &Error) {
+    metrics::counter!(FAILURES, "kind" => kind(error)).increment(1);
+}
+
+fn kind(error: &Error) -> &'static str {
+    match error {
+        Error::AcmeChallege(_)
+        | Error::AcmeChallengePresent(_)
+        | Error::SerialNotConverged(_)
+        | Error::RecordNotPropagated(_) => "timeout",
+        Error::Cancelled => "cancelled",
+        Error::Resolve(_) | Error::Proto(_) => "resolve",
+        Error::IO(_) => "io",
+        Error::Response { .. } | Error::NotAuthoritative(_) | Error::Lame { .. } => "nameserver",
+        Error::Unsigned(_) | Error::InvalidSignature(_) | Error::SignatureExpired(_) => "dnssec",
+        Error::InvalidDomain(_)
+        | Error::InvalidChallenge(_)
+        | Error::NotDigest(_)
+        | Error::InvalidKey(_)
+        | Error::NoDnsChallenge(_) => "invalid",
+        Error::NoZone(_) | Error::NoNameservers | Error::NoAddresses(_) => "discovery",
+        Error::Provider(_) => "provider",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::kind;
+    use crate::Error;
+
+    #[test]
+    fn failure_kinds() {
+        assert_eq!(kind(&Error::AcmeChallege(vec![])), "timeout");
+        assert_eq!(kind(&Error::Cancelled), "cancelled");
+        assert_eq!(kind(&Error::NoZone("paulmin.nl.".to_owned())), "discovery");
+        assert_eq!(kind(&Error::MultipleAcme), "other");
+    }
+}
@@ -0,0 +1,256 @@
+use std::net::{IpAddr, SocketAddr};
+#[cfg(feature = "tsig")]
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "tsig")]
+use hickory_resolver::proto::rr::dnssec::{rdata::tsig::TsigAlgorithm as Algorithm, tsig::TSigner};
+use hickory_resolver::proto::{
+    op::{Message, ResponseCode},
+    rr::{Name, RData, RecordType},
+};
+
+#[cfg(feature = "tsig")]
+use crate::query::{request, tcp_raw};
+use crate::{
+    query::{query, Connections, QueryOptions},
+    resolver::txt_value,
+    Error, Result,
+};
+
+/// Allowed difference in seconds between the clocks of this host and the primary.
+#[cfg(feature = "tsig")]
+const FUDGE: u16 = 300;
+
+/// Algorithm of a TSIG key.
+#[cfg(feature = "tsig")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TsigAlgorithm {
+    HmacSha256,
+    HmacSha384,
+    HmacSha512,
+}
+
+#[cfg(feature = "tsig")]
+impl TsigAlgorithm {
+    fn hickory(self) -> Algorithm {
+        match self {
+            TsigAlgorithm::HmacSha256 => Algorithm::HmacSha256,
+            TsigAlgorithm::HmacSha384 => Algorithm::HmacSha384,
+            TsigAlgorithm::HmacSha512 => Algorithm::HmacSha512,
+        }
+    }
+}
+
+/// Key used to sign the queries to the hidden primary nameserver with TSIG.
+#[cfg(feature = "tsig")]
+#[derive(Clone)]
+pub struct TsigKey {
+    name: Name,
+    algorithm: TsigAlgorithm,
+    secret: Vec<u8>,
+}
+
+#[cfg(feature = "tsig")]
+impl TsigKey {
+    /// Key with the name and the base64 encoded secret, as found in the key file of BIND.
+    pub fn new<S>(name: S, algorithm: TsigAlgorithm, secret: S) -> Result<Self>
+    where
+        S: AsRef<str>,
+    {
+        let secret = data_encoding::BASE64
+            .decode(secret.as_ref().trim().as_bytes())
+            .map_err(|_| Error::InvalidKey(name.as_ref().to_owned()))?;
+        Ok(Self {
+            name: Name::from_ascii(name.as_ref()).map_err(Error::proto)?,
+            algorithm,
+            secret,
+        })
+    }
+
+    fn signer(&self) -> Result<TSigner> {
+        TSigner::new(
+            self.secret.clone(),
+            self.algorithm.hickory(),
+            self.name.clone(),
+            FUDGE,
+        )
+        .map_err(Error::proto)
+    }
+}
+
+/// The secret is left out.
+#[cfg(feature = "tsig")]
+impl fmt::Debug for TsigKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TsigKey")
+            .field("name", &self.name)
+            .field("algorithm", &self.algorithm)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The primary nameserver the authoritive nameservers transfer the zone from.
+pub(crate) struct Primary {
+    server: SocketAddr,
+    #[cfg(feature = "tsig")]
+    key: Option<TsigKey>,
+}
+
+impl Primary {
+    pub(crate) fn new(address: IpAddr, options: &QueryOptions) -> Self {
+        Self {
+            server: SocketAddr::new(address, options.port),
+            #[cfg(feature = "tsig")]
+            key: None,
+        }
+    }
+
+    /// Sign the queries with the key.
+    #[cfg(feature = "tsig")]
+    pub(crate) fn with_key(mut self, key: Option<TsigKey>) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// The TXT values the primary serves for the record name.
+    pub(crate) fn txt_values(
+        &self,
+        record_name: &str,
+        options: &QueryOptions,
+    ) -> Result<Vec<String>> {
+        let name = Name::from_ascii(record_name).map_err(Error::proto)?;
+        self.answer(&name, RecordType::TXT, options)
+            .map(|response| {
+                response
+                    .answers()
+                    .iter()
+                    .filter_map(|record| record.data().and_then(RData::as_txt))
+                    .map(txt_value)
+                    .collect()
+            })
+    }
+
+    /// The serial of the zone on the primary.
+    pub(crate) fn serial(&self, zone: &str, options: &QueryOptions) -> Result<u32> {
+        let name = Name::from_ascii(zone).map_err(Error::proto)?;
+        self.answer(&name, RecordType::SOA, options)?
+            .answers()
+            .iter()
+            .find_map(|record| record.data().and_then(RData::as_soa))
+            .map(|soa| soa.serial())
+            .ok_or_else(|| Error::NoZone(zone.to_owned()))
+    }
+
+    fn answer(
+        &self,
+        name: &Name,
+        record_type: RecordType,
+        options: &QueryOptions,
+    ) -> Result<Message> {
+        let response = self.query(name, record_type, options)?;
+        match response.response_code() {
+            ResponseCode::NoError | ResponseCode::NXDomain => Ok(response),
+            response_code => Err(Error::response(self.server.ip().to_string(), response_code)),
+        }
+    }
+
+    #[cfg(feature = "tsig")]
+    fn query(
+        &self,
+        name: &Name,
+        record_type: RecordType,
+        options: &QueryOptions,
+    ) -> Result<Message> {
+        match &self.key {
+            Some(key) => signed_query(self.server, name, record_type, options, key),
+            None => query(
+                self.server,
+                name,
+                record_type,
+                options,
+                &Connections::default(),
+            ),
+        }
+    }
+
+    #[cfg(not(feature = "tsig"))]
+    fn query(
+        &self,
+        name: &Name,
+        record_type: RecordType,
+        options: &QueryOptions,
+    ) -> Result<Message> {
+        query(
+            self.server,
+            name,
+            record_type,
+            options,
+            &Connections::default(),
+        )
+    }
+}
+
+/// Sends a query signed with the key over tcp and verifies the signature of the response.
+#[cfg(feature = "tsig")]
+fn signed_query(
+    server: SocketAddr,
+    name: &Name,
+    record_type: RecordType,
+    options: &QueryOptions,
+    key: &TsigKey,
+) -> Result<Message> {
+    let mut request = request(name, record_type, options);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let verifier = request
+        .finalize(&key.signer()?, u32::try_from(now).unwrap_or(u32::MAX))
+        .map_err(Error::proto)?;
+    let (response, bytes) = tcp_raw(server, &request, options)?;
+    if let Some(mut verify) = verifier {
+        verify(&bytes).map_err(Error::proto)?;
+    }
+    Ok(response)
+}
+
+/// The serial has reached target, using serial number arithmetic so wrapped serials are newer.
+pub(crate) fn serial_reached(serial: u32, target: u32) -> bool {
+    serial.wrapping_sub(target) < 1 << 31
+}
+
+#[cfg(test)]
+mod test {
+    use super::serial_reached;
+
+    #[test]
+    fn serial_arithmetic() {
+        assert!(serial_reached(2024010101, 2024010101));
+        assert!(serial_reached(2024010102, 2024010101));
+        assert!(!serial_reached(2024010100, 2024010101));
+        assert!(serial_reached(5, u32::MAX - 5));
+        assert!(!serial_reached(u32::MAX - 5, 5));
+    }
+
+    #[cfg(feature = "tsig")]
+    #[test]
+    fn tsig_key() {
+        use super::{TsigAlgorithm, TsigKey};
+        use crate::Error;
+
+        let key = TsigKey::new(
+            "acme-update.",
+            TsigAlgorithm::HmacSha256,
+            "c2VjcmV0IGtleSBmb3IgdGhlIHByaW1hcnk=",
+        )
+        .unwrap();
+        assert_eq!(key.secret, b"secret key for the primary");
+        assert!(!format!("{:?}", key).contains("secret"));
+        assert!(matches!(
+            TsigKey::new("acme-update.", TsigAlgorithm::HmacSha256, "not base64!"),
+            Err(Error::InvalidKey(_))
+        ));
+    }
+}
@@ -0,0 +1,2482 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{mpsc::channel, Arc, Mutex},
+    thread::{scope, sleep, spawn},
+    time::{Duration, Instant},
+};
+
+use tracing::field::Empty;
+
+#[cfg(feature = "dnssec")]
+use crate::dnssec::verified_txt_values;
+#[cfg(feature = "metrics")]
+use crate::metric;
+#[cfg(feature = "tsig")]
+use crate::primary::TsigKey;
+use crate::{
+    caa::{self, CaaReport},
+    cancel::CancellationToken,
+    challenge::{check_format, dns_account_label, dns_value, validate, ChallengeFormat},
+    delegation::{delegation, glue, host_addresses},
+    diagnosis::{Diagnosis, NameserverDiagnosis, ZoneDelegation},
+    domain::normalize,
+    error::Error,
+    event::{self, Observer, PropagationEvent},
+    filter::NameserverFilter,
+    handle::{PropagationStatus, WaitHandle},
+    limit::{QueryLimit, RateLimiter},
+    primary::{serial_reached, Primary},
+    provider::DnsProvider,
+    query::QueryOptions,
+    record::RecordType,
+    report::{NameserverReport, PropagationReport},
+    resolver::{
+        ancestors, record_name, serves_challenges, AuthoritiveResolver, RecursiveResolver,
+        ResolverType, ACME_CHALLENGE_LABEL,
+    },
+    LookupIpStrategy, Result, ServerOrderingStrategy,
+};
+
+const MAX_RETRIES: usize = 720;
+const WAIT_SECONDS: u64 = 5;
+const INITIAL_DELAY_SECONDS: u64 = 1;
+const CONCURRENCY: usize = 4;
+const DISCOVERY_BACKOFF_SECONDS: u64 = 1;
+#[cfg(feature = "dnssec")]
+const SIGNATURE_WINDOW_SECONDS: u64 = 86_400;
+
+/// Checks the TXT values served by a nameserver, returns true when it is up to date.
+type Check<'a> = &'a dyn Fn(&[String]) -> Result<bool>;
+/// Creates the error for the nameservers that are not up to date when waiting times out.
+type Timeout = fn(Vec<NameserverReport>) -> Error;
+/// A [`Check`] that can be sent to the blocking threads of tokio.
+#[cfg(feature = "tokio")]
+type AsyncCheck = Arc<dyn Fn(&Propagation, &[String]) -> Result<bool> + Send + Sync>;
+
+/// Propagation holds the settings used while waiting for an acme challenge record.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use acme_validation_propagation::Propagation;
+///
+/// Propagation::new()
+///     .retries(60)
+///     .interval(Duration::from_secs(10))
+///     .wait("example.com", "89823875")
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Propagation {
+    retries: usize,
+    interval: Duration,
+    initial_delay: Duration,
+    cancellation: Option<CancellationToken>,
+    observer: Option<Observer>,
+    quorum: Option<usize>,
+    filter: NameserverFilter,
+    recheck: bool,
+    ignore_extra: bool,
+    concurrency: usize,
+    label: String,
+    follow_cname: bool,
+    rediscover: Option<Duration>,
+    discovery_retries: usize,
+    discovery_backoff: Duration,
+    preflight: bool,
+    adaptive_interval: Option<(Duration, Duration)>,
+    error_tolerance: Option<Duration>,
+    require_authoritative: bool,
+    challenge_format: ChallengeFormat,
+    per_address: bool,
+    cross_check: bool,
+    resolver: ResolverType,
+    fallback_resolvers: Vec<ResolverType>,
+    query_options: QueryOptions,
+    check_delegation: bool,
+    use_glue: bool,
+    authoritative_addresses: bool,
+    servers: Vec<IpAddr>,
+    primary: Option<IpAddr>,
+    /// Type of the records checked, only other than TXT in wait_record
+    record_type: RecordType,
+    #[cfg(feature = "tsig")]
+    tsig_key: Option<TsigKey>,
+    #[cfg(feature = "dnssec")]
+    signature_window: Duration,
+}
+
+impl Default for Propagation {
+    fn default() -> Self {
+        Self {
+            retries: MAX_RETRIES,
+            interval: Duration::from_secs(WAIT_SECONDS),
+            initial_delay: Duration::from_secs(INITIAL_DELAY_SECONDS),
+            cancellation: None,
+            observer: None,
+            quorum: None,
+            filter: NameserverFilter::default(),
+            recheck: false,
+            ignore_extra: false,
+            concurrency: CONCURRENCY,
+            label: ACME_CHALLENGE_LABEL.to_owned(),
+            follow_cname: true,
+            rediscover: None,
+            discovery_retries: 0,
+            discovery_backoff: Duration::from_secs(DISCOVERY_BACKOFF_SECONDS),
+            preflight: false,
+            adaptive_interval: None,
+            error_tolerance: None,
+            require_authoritative: false,
+            challenge_format: ChallengeFormat::default(),
+            per_address: false,
+            cross_check: false,
+            resolver: ResolverType::default(),
+            fallback_resolvers: vec![],
+            query_options: QueryOptions::default(),
+            check_delegation: false,
+            use_glue: false,
+            authoritative_addresses: false,
+            servers: vec![],
+            primary: None,
+            record_type: RecordType::Txt,
+            #[cfg(feature = "tsig")]
+            tsig_key: None,
+            #[cfg(feature = "dnssec")]
+            signature_window: Duration::from_secs(SIGNATURE_WINDOW_SECONDS),
+        }
+    }
+}
+
+impl Propagation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of failed attempts after which waiting is abandoned.
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Time between two attempts.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Time to wait before the first attempt, defaults to one second.
+    /// With [`Duration::ZERO`] the first attempt is made immediately.
+    pub fn initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    /// Token that abandons the wait with [`Error::Cancelled`] when cancelled.
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Number of authoritive nameservers that must serve the challenge.
+    /// By default all of them must serve it.
+    /// A quorum larger than the number of nameservers also requires all of them.
+    pub fn quorum(mut self, quorum: usize) -> Self {
+        self.quorum = Some(quorum);
+        self
+    }
+
+    /// Only check the authoritive nameservers matching the host name or ip address.
+    /// Can be called multiple times.
+    pub fn include<S: AsRef<str>>(mut self, nameserver: S) -> Self {
+        self.filter.include(nameserver);
+        self
+    }
+
+    /// Skip the authoritive nameservers matching the host name or ip address.
+    /// Can be called multiple times.
+    pub fn exclude<S: AsRef<str>>(mut self, nameserver: S) -> Self {
+        self.filter.exclude(nameserver);
+        self
+    }
+
+    /// Nameservers that served the challenge are not queried again in later attempts.
+    /// With recheck they are queried once more before the wait succeeds.
+    pub fn recheck(mut self, recheck: bool) -> Self {
+        self.recheck = recheck;
+        self
+    }
+
+    /// Succeed when the expected challenges are served, even if other stale
+    /// challenge records are served as well.
+    /// By default other records fail the wait with [`Error::MultipleAcme`].
+    pub fn ignore_extra_records(mut self, ignore_extra: bool) -> Self {
+        self.ignore_extra = ignore_extra;
+        self
+    }
+
+    /// Maximum number of domains checked at the same time by [`Propagation::wait_all`].
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Label put in front of the domain name to get the name of the challenge record.
+    /// Defaults to `_acme-challenge`, an empty label checks the domain name itself.
+    pub fn label<S: AsRef<str>>(mut self, label: S) -> Self {
+        self.label = label.as_ref().to_owned();
+        self
+    }
+
+    /// Follow a CNAME on the challenge record and check the target on the authoritive
+    /// nameservers of the target zone. Enabled by default.
+    pub fn follow_cname(mut self, follow_cname: bool) -> Self {
+        self.follow_cname = follow_cname;
+        self
+    }
+
+    /// Repeat the discovery of the authoritive nameservers when the last discovery
+    /// is longer ago than the given duration, so added or removed nameservers are noticed.
+    /// Nameservers that served the challenge before keep that state.
+    pub fn rediscover(mut self, every: Duration) -> Self {
+        self.rediscover = Some(every);
+        self
+    }
+
+    /// Repeat the discovery of the authoritive nameservers at most retries times when it fails
+    /// with a resolve error, like a timeout of the recursive resolver. The first retry is after
+    /// backoff, every next retry waits twice as long. By default a failed discovery ends the wait.
+    pub fn discovery_retries(mut self, retries: usize, backoff: Duration) -> Self {
+        self.discovery_retries = retries;
+        self.discovery_backoff = backoff;
+        self
+    }
+
+    /// Check that every authoritive nameserver answers the SOA query for the zone
+    /// before the first attempt, failing with [`Error::Lame`] if one does not.
+    pub fn preflight(mut self, preflight: bool) -> Self {
+        self.preflight = preflight;
+        self
+    }
+
+    /// Derive the time between two attempts from the SOA record of the zone,
+    /// one tenth of the smaller of the refresh and retry values, bounded by min and max.
+    /// Replaces the fixed [`Propagation::interval`] unless the SOA record can not be found.
+    pub fn adaptive_interval(mut self, min: Duration, max: Duration) -> Self {
+        self.adaptive_interval = Some((min, max.max(min)));
+        self
+    }
+
+    /// Treat resolve errors of a single nameserver, like SERVFAIL, REFUSED or a timeout,
+    /// as not yet propagated as long as the nameserver fails for less than window.
+    /// By default any resolve error ends the wait.
+    pub fn tolerate_errors(mut self, window: Duration) -> Self {
+        self.error_tolerance = Some(window);
+        self
+    }
+
+    /// Query the nameservers directly and require the authoritive answer flag in the answers,
+    /// failing with [`Error::NotAuthoritative`] otherwise.
+    pub fn require_authoritative(mut self, require: bool) -> Self {
+        self.require_authoritative = require;
+        self
+    }
+
+    /// Compare the NS records of the zone in the parent zone with those in the zone itself
+    /// and log a warning when they differ. The resolvers of the acme server may ask
+    /// other nameservers than the ones that are checked.
+    pub fn check_delegation(mut self, check_delegation: bool) -> Self {
+        self.check_delegation = check_delegation;
+        self
+    }
+
+    /// Take the addresses of the authoritive nameservers from the glue records in the parent
+    /// zone when it has them, instead of from the recursive resolver,
+    /// which can have stale addresses in its cache.
+    pub fn use_glue(mut self, use_glue: bool) -> Self {
+        self.use_glue = use_glue;
+        self
+    }
+
+    /// Ask the addresses of the authoritive nameservers to the nameservers of their own zone,
+    /// instead of to the recursive resolver, so renumbered nameservers are reached at
+    /// their new addresses. Glue records are preferred when [`Propagation::use_glue`] is set.
+    pub fn authoritative_addresses(mut self, authoritative_addresses: bool) -> Self {
+        self.authoritative_addresses = authoritative_addresses;
+        self
+    }
+
+    /// Check the authoritive nameservers at these addresses instead of discovering
+    /// the nameservers of the zone, every address on its own.
+    /// The challenge record is not followed when it is an alias, and the domain name is used
+    /// as zone for [`Propagation::preflight`] and [`Propagation::adaptive_interval`].
+    pub fn servers(mut self, addresses: &[IpAddr]) -> Self {
+        self.servers = addresses.to_vec();
+        self
+    }
+
+    /// Compare the serial of the zone on the authoritive nameservers with the serial on the
+    /// hidden primary nameserver at address, for operators running their own zones.
+    /// Once the primary serves the challenge, a nameserver is up to date as soon as it has
+    /// transferred the zone with that serial, without asking it for the challenge record.
+    pub fn primary(mut self, address: IpAddr) -> Self {
+        self.primary = Some(address);
+        self
+    }
+
+    /// Sign the queries to the [`Propagation::primary`] with the TSIG key,
+    /// for primaries that only answer to signed queries. Signed queries are sent over tcp.
+    #[cfg(feature = "tsig")]
+    pub fn tsig_key(mut self, key: TsigKey) -> Self {
+        self.tsig_key = Some(key);
+        self
+    }
+
+    /// Check every address of every authoritive nameserver on its own, instead of
+    /// accepting the answer of the first address that responds.
+    /// The include and exclude filters then match the individual addresses.
+    pub fn per_address(mut self, per_address: bool) -> Self {
+        self.per_address = per_address;
+        self
+    }
+
+    /// Recursive resolver used to find the zone and the authoritive nameservers.
+    /// Defaults to the public resolver of Google.
+    pub fn resolver(mut self, resolver: ResolverType) -> Self {
+        self.resolver = resolver;
+        self
+    }
+
+    /// Recursive resolver used when finding the authoritive nameservers with the
+    /// [`Propagation::resolver`] fails, for example during an outage of the upstream.
+    /// Can be called multiple times, the fallbacks are tried in order.
+    pub fn fallback_resolver(mut self, resolver: ResolverType) -> Self {
+        self.fallback_resolvers.push(resolver);
+        self
+    }
+
+    /// Query the authoritive nameservers over tcp only,
+    /// for networks where outgoing udp on port 53 is blocked.
+    pub fn tcp_only(mut self, tcp_only: bool) -> Self {
+        self.query_options.tcp_only = tcp_only;
+        self
+    }
+
+    /// Keep the tcp connections to the authoritive nameservers open between attempts,
+    /// instead of connecting for every query, saving the handshake on long waits.
+    /// Used for the direct queries made with [`Propagation::require_authoritative`]
+    /// over tcp; the resolvers already reuse their connections while they are open.
+    pub fn keep_alive(mut self, keep_alive: bool) -> Self {
+        self.query_options.keep_alive = keep_alive;
+        self
+    }
+
+    /// Port the authoritive nameservers are queried on, defaults to 53.
+    /// Useful for test nameservers running on another port.
+    pub fn nameserver_port(mut self, port: u16) -> Self {
+        self.query_options.port = port;
+        self
+    }
+
+    /// Time to wait for the answer of a nameserver, defaults to five seconds.
+    /// Applies to both the recursive resolver and the authoritive nameservers.
+    pub fn query_timeout(mut self, timeout: Duration) -> Self {
+        self.query_options.timeout = timeout;
+        self
+    }
+
+    /// Number of times a query is sent before it fails, defaults to two.
+    /// Applies to both the recursive resolver and the authoritive nameservers.
+    pub fn query_attempts(mut self, attempts: usize) -> Self {
+        self.query_options.attempts = attempts.max(1);
+        self
+    }
+
+    /// Number of addresses of a resolver that are queried at the same time, defaults to two.
+    /// Applies to both the recursive resolver and the authoritive nameservers.
+    pub fn concurrent_requests(mut self, concurrent_requests: usize) -> Self {
+        self.query_options.concurrent_requests = concurrent_requests.max(1);
+        self
+    }
+
+    /// Address families used to reach the authoritive nameservers.
+    /// By default both the ipv6 and ipv4 addresses of a nameserver are used,
+    /// [`LookupIpStrategy::Ipv4Only`] is needed on hosts without ipv6 connectivity.
+    pub fn ip_strategy(mut self, ip_strategy: LookupIpStrategy) -> Self {
+        self.query_options.ip_strategy = ip_strategy;
+        self
+    }
+
+    /// Local address the queries are sent from, for hosts with multiple uplinks
+    /// where only one of them can reach the nameservers.
+    /// Only used for nameservers of the same address family as the local address.
+    pub fn bind_address(mut self, address: IpAddr) -> Self {
+        self.query_options.bind_address = Some(address);
+        self
+    }
+
+    /// Udp payload size advertised with EDNS0, lower it for networks that drop fragmented
+    /// udp answers. The exact size is only used for the direct queries made with
+    /// [`Propagation::require_authoritative`], the resolvers enable EDNS0 with their own size.
+    pub fn edns_payload(mut self, payload: u16) -> Self {
+        self.query_options.edns_payload = Some(payload.max(512));
+        self
+    }
+
+    /// Randomize the case of the letters in the record name and reject answers that do not
+    /// repeat it exactly (dns 0x20), making spoofed answers harder.
+    /// Only used for the direct queries made with [`Propagation::require_authoritative`],
+    /// the resolvers do not support it.
+    pub fn case_randomization(mut self, case_randomization: bool) -> Self {
+        self.query_options.case_randomization = case_randomization;
+        self
+    }
+
+    /// Send at most queries_per_second queries to the authoritive nameservers, with bursts
+    /// of at most burst queries, so many waits at the same time do not trigger the response
+    /// rate limiting of the nameservers. Queries wait until they fit within the limit.
+    /// The limit is shared by [`Propagation::wait_all`] and by clones of this Propagation.
+    pub fn rate_limit(mut self, queries_per_second: u32, burst: u32) -> Self {
+        self.query_options.rate_limit = Some(RateLimiter::new(queries_per_second, burst));
+        self
+    }
+
+    /// Maximum number of queries to the authoritive nameservers in flight at the same time,
+    /// across all domains checked by [`Propagation::wait_all`] and clones of this Propagation.
+    /// By default every wait sends its queries one after the other without a global limit.
+    pub fn max_queries_in_flight(mut self, max_in_flight: usize) -> Self {
+        self.query_options.in_flight = Some(QueryLimit::new(max_in_flight));
+        self
+    }
+
+    /// Order in which the recursive resolver tries its upstream nameservers.
+    /// By default the fastest nameserver is preferred, [`ServerOrderingStrategy::UserProvidedOrder`]
+    /// keeps the configured order, which makes tests deterministic.
+    pub fn server_ordering(mut self, strategy: ServerOrderingStrategy) -> Self {
+        self.query_options.server_ordering = strategy;
+        self
+    }
+
+    /// Query the nameservers directly and, when the zone is signed, require a valid
+    /// signature over the TXT records, failing with [`Error::Unsigned`] or [`Error::InvalidSignature`].
+    /// A record that is propagated but not signed yet fails the validation of the acme server.
+    #[cfg(feature = "dnssec")]
+    pub fn dnssec(mut self, dnssec: bool) -> Self {
+        self.query_options.dnssec_ok = dnssec;
+        self
+    }
+
+    /// With [`Propagation::dnssec`], log a warning when the signature of the TXT records
+    /// expires within the window, defaults to one day. Expired signatures fail with
+    /// [`Error::SignatureExpired`].
+    #[cfg(feature = "dnssec")]
+    pub fn signature_expiry_window(mut self, window: Duration) -> Self {
+        self.signature_window = window;
+        self
+    }
+
+    /// After the authoritive nameservers are up to date, also require that the public
+    /// recursive resolvers of Google and Cloudflare return the challenge,
+    /// which is closer to what the resolvers of the acme server will see.
+    pub fn cross_check(mut self, cross_check: bool) -> Self {
+        self.cross_check = cross_check;
+        self
+    }
+
+    /// Check that the challenges look like the digest of a key authorization.
+    /// By default a warning is logged for challenges that do not.
+    pub fn challenge_format(mut self, format: ChallengeFormat) -> Self {
+        self.challenge_format = format;
+        self
+    }
+
+    /// Use the dns-account-01 record label derived from the acme account uri.
+    pub fn account<S: AsRef<str>>(self, account_uri: S) -> Self {
+        self.label(dns_account_label(account_uri))
+    }
+
+    /// Callback that receives every [`PropagationEvent`] while waiting.
+    pub fn on_event<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&PropagationEvent) + Send + Sync + 'static,
+    {
+        self.observer = Some(Observer::new(f));
+        self
+    }
+
+    /// wait checks the authoritive nameservers periodically.
+    /// It returns Ok(()) when all nameservers have the challenge.
+    /// It returns an error after the configured number of attempts failed.
+    pub fn wait<S>(&self, domain_name: S, challenge: S) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.wait_values(domain_name, &[challenge])
+    }
+
+    /// wait_report behaves like [`Propagation::wait`] but returns a report
+    /// with timing details when all nameservers have the challenge.
+    pub fn wait_report<S>(&self, domain_name: S, challenge: S) -> Result<PropagationReport>
+    where
+        S: AsRef<str>,
+    {
+        self.check_challenge(challenge.as_ref())?;
+        self.run(domain_name.as_ref(), None, Error::AcmeChallege, &|values| {
+            self.serves(values, &[challenge.as_ref()])
+        })
+    }
+
+    /// wait_async behaves like [`Propagation::wait`] without blocking the async runtime.
+    /// The queries of an attempt run on the blocking threads of tokio,
+    /// the time between two attempts is spent in [`tokio::time::sleep`],
+    /// which a cancel of the [`CancellationToken`] ends immediately.
+    /// Dropping the future abandons the wait after the current attempt.
+    #[cfg(feature = "tokio")]
+    pub async fn wait_async<S>(&self, domain_name: S, challenge: S) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.wait_report_async(domain_name, challenge)
+            .await
+            .map(drop)
+    }
+
+    /// wait_report_async behaves like [`Propagation::wait_async`] but returns a report
+    /// with timing details when all nameservers have the challenge.
+    #[cfg(feature = "tokio")]
+    pub async fn wait_report_async<S>(
+        &self,
+        domain_name: S,
+        challenge: S,
+    ) -> Result<PropagationReport>
+    where
+        S: AsRef<str>,
+    {
+        self.report_async(domain_name.as_ref(), challenge.as_ref(), None)
+            .await
+    }
+
+    /// wait_until_async behaves like [`Propagation::wait_async`] but also gives up when
+    /// the deadline has passed, like [`Propagation::wait_until`].
+    #[cfg(feature = "tokio")]
+    pub async fn wait_until_async<S>(
+        &self,
+        domain_name: S,
+        challenge: S,
+        deadline: Instant,
+    ) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.report_async(domain_name.as_ref(), challenge.as_ref(), Some(deadline))
+            .await
+            .map(drop)
+    }
+
+    /// wait_absent_async behaves like [`Propagation::wait_absent`] without blocking
+    /// the async runtime, like [`Propagation::wait_async`].
+    #[cfg(feature = "tokio")]
+    pub async fn wait_absent_async<S>(&self, domain_name: S, challenge: S) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.check_challenge(challenge.as_ref())?;
+        let challenge = challenge.as_ref().to_owned();
+        self.run_async(
+            domain_name.as_ref(),
+            None,
+            Error::AcmeChallengePresent,
+            Arc::new(move |_, values| Ok(!values.contains(&challenge))),
+        )
+        .await
+        .map(drop)
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn report_async(
+        &self,
+        domain_name: &str,
+        challenge: &str,
+        deadline: Option<Instant>,
+    ) -> Result<PropagationReport> {
+        self.check_challenge(challenge)?;
+        let challenge = challenge.to_owned();
+        self.run_async(
+            domain_name,
+            deadline,
+            Error::AcmeChallege,
+            Arc::new(move |propagation, values| propagation.serves(values, &[challenge.as_str()])),
+        )
+        .await
+    }
+
+    /// Async version of run, the check runs on the blocking threads with the attempt.
+    #[cfg(feature = "tokio")]
+    async fn run_async(
+        &self,
+        domain_name: &str,
+        deadline: Option<Instant>,
+        timeout: Timeout,
+        check: AsyncCheck,
+    ) -> Result<PropagationReport> {
+        let start = Instant::now();
+        let result = self.poll_async(domain_name, deadline, timeout, check).await;
+        self.failed(&result, domain_name, start);
+        result
+    }
+
+    /// wait_values behaves like [`Propagation::wait`] but waits until all challenges are served,
+    /// for example the challenges for both `example.com` and `*.example.com`.
+    pub fn wait_values<S>(&self, domain_name: S, challenges: &[S]) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        challenges
+            .iter()
+            .try_for_each(|challenge| self.check_challenge(challenge.as_ref()))?;
+        let challenges = challenges.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        self.run(domain_name.as_ref(), None, Error::AcmeChallege, &|values| {
+            self.serves(values, &challenges)
+        })
+        .map(drop)
+    }
+
+    /// wait_for_key_authorization behaves like [`Propagation::wait`] but computes
+    /// the challenge from the acme key authorization.
+    pub fn wait_for_key_authorization<S>(&self, domain_name: S, key_authorization: S) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.wait(domain_name.as_ref(), dns_value(key_authorization).as_str())
+    }
+
+    /// wait_absent checks the authoritive nameservers periodically until none of them
+    /// serves the challenge anymore, for example after the challenge record was removed.
+    /// It returns an error after the configured number of attempts failed.
+    pub fn wait_absent<S>(&self, domain_name: S, challenge: S) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.check_challenge(challenge.as_ref())?;
+        self.run(
+            domain_name.as_ref(),
+            None,
+            Error::AcmeChallengePresent,
+            &|values| Ok(!values.iter().any(|value| value == challenge.as_ref())),
+        )
+        .map(drop)
+    }
+
+    /// wait_absent_until behaves like [`Propagation::wait_absent`] but also gives up
+    /// when the deadline has passed.
+    pub fn wait_absent_until<S>(
+        &self,
+        domain_name: S,
+        challenge: S,
+        deadline: Instant,
+    ) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.check_challenge(challenge.as_ref())?;
+        self.run(
+            domain_name.as_ref(),
+            Some(deadline),
+            Error::AcmeChallengePresent,
+            &|values| Ok(!values.iter().any(|value| value == challenge.as_ref())),
+        )
+        .map(drop)
+    }
+
+    /// wait_all checks the challenges of several domains, at most
+    /// [`Propagation::concurrency`] domains at the same time.
+    /// It returns the result of every domain when all of them have finished.
+    pub fn wait_all<K, V>(&self, challenges: &HashMap<K, V>) -> HashMap<String, Result<()>>
+    where
+        K: AsRef<str> + Sync,
+        V: AsRef<str> + Sync,
+    {
+        let queue = Mutex::new(challenges.iter());
+        let results = Mutex::new(HashMap::with_capacity(challenges.len()));
+        scope(|scope| {
+            for _ in 0..self.concurrency.min(challenges.len()) {
+                scope.spawn(|| loop {
+                    let next = queue.lock().unwrap_or_else(|e| e.into_inner()).next();
+                    let Some((domain_name, challenge)) = next else {
+                        break;
+                    };
+                    let result = self.wait(domain_name.as_ref(), challenge.as_ref());
+                    results
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .insert(domain_name.as_ref().to_owned(), result);
+                });
+            }
+        });
+        results.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// negative_cache_ttl asks the recursive resolver for the challenge record and
+    /// returns how long it may keep answering that the record does not exist,
+    /// based on the SOA minimum of the zone. Returns None if the record is visible.
+    /// Useful after a successful wait to decide if triggering the validation should be delayed.
+    pub fn negative_cache_ttl<S>(&self, domain_name: S) -> Result<Option<Duration>>
+    where
+        S: AsRef<str>,
+    {
+        let domain_name = normalize(domain_name.as_ref())?;
+        self.resolver
+            .recursive_resolver(false, &self.query_options)
+            .and_then(|resolver| resolver.negative_ttl(record_name(&self.label, &domain_name)))
+    }
+
+    /// wait_until behaves like [`Propagation::wait`] but also gives up when the deadline
+    /// has passed, even if that happens while sleeping between two attempts.
+    pub fn wait_until<S>(&self, domain_name: S, challenge: S, deadline: Instant) -> Result<()>
+    where
+        S: AsRef<str>,
+    {
+        self.check_challenge(challenge.as_ref())?;
+        self.run(
+            domain_name.as_ref(),
+            Some(deadline),
+            Error::AcmeChallege,
+            &|values| self.serves(values, &[challenge.as_ref()]),
+        )
+        .map(drop)
+    }
+
+    /// wait_report_until behaves like [`Propagation::wait_until`] but returns a report
+    /// with timing details when all nameservers have the challenge.
+    pub fn wait_report_until<S>(
+        &self,
+        domain_name: S,
+        challenge: S,
+        deadline: Instant,
+    ) -> Result<PropagationReport>
+    where
+        S: AsRef<str>,
+    {
+        self.check_challenge(challenge.as_ref())?;
+        self.run(
+            domain_name.as_ref(),
+            Some(deadline),
+            Error::AcmeChallege,
+            &|values| self.serves(values, &[challenge.as_ref()]),
+        )
+    }
+
+    /// provision_and_wait creates the challenge record with the provider and behaves like
+    /// [`Propagation::wait_report`]. When waiting fails the record is deleted again.
+    pub fn provision_and_wait<P, S>(
+        &self,
+        provider: &P,
+        domain_name: S,
+        challenge: S,
+    ) -> Result<PropagationReport>
+    where
+        P: DnsProvider,
+        S: AsRef<str>,
+    {
+        self.check_challenge(challenge.as_ref())?;
+        let name = self.provider_record(domain_name.as_ref())?;
+        provider
+            .create_txt(&name, challenge.as_ref())
+            .map_err(|error| Error::Provider(error.into()))?;
+        let result = self.wait_report(domain_name.as_ref(), challenge.as_ref());
+        if result.is_err() {
+            if let Err(error) = provider.delete_txt(&name, challenge.as_ref()) {
+                tracing::warn!("Deleting challenge record {} failed: {}", name, error);
+            }
+        }
+        result
+    }
+
+    /// cleanup_and_wait deletes the challenge record with the provider and behaves like
+    /// [`Propagation::wait_absent`], to verify that the cleanup reached all nameservers.
+    pub fn cleanup_and_wait<P, S>(&self, provider: &P, domain_name: S, challenge: S) -> Result<()>
+    where
+        P: DnsProvider,
+        S: AsRef<str>,
+    {
+        self.check_challenge(challenge.as_ref())?;
+        let name = self.provider_record(domain_name.as_ref())?;
+        provider
+            .delete_txt(&name, challenge.as_ref())
+            .map_err(|error| Error::Provider(error.into()))?;
+        self.wait_absent(domain_name.as_ref(), challenge.as_ref())
+    }
+
+    /// wait_serial_converged checks the authoritive nameservers of the zone of the domain
+    /// periodically until they serve the serial of the SOA record on the primary nameserver.
+    /// The primary is the [`Propagation::primary`] when set, otherwise the MNAME of the SOA record.
+    /// It returns an error after the configured number of attempts failed.
+    pub fn wait_serial_converged<S>(&self, domain_name: S) -> Result<PropagationReport>
+    where
+        S: AsRef<str>,
+    {
+        let domain_name = normalize(domain_name.as_ref())?;
+        let propagation = self.clone().label("").follow_cname(false);
+        let propagation = match self.primary {
+            Some(_) => propagation,
+            None => propagation.primary(self.zone_primary(&domain_name)?),
+        };
+        propagation.run(&domain_name, None, Error::SerialNotConverged, &|_| Ok(true))
+    }
+
+    /// The address of the primary nameserver named in the SOA record of the zone of the domain.
+    fn zone_primary(&self, domain_name: &str) -> Result<IpAddr> {
+        let recursive = self
+            .resolver
+            .recursive_resolver(false, &self.query_options)?;
+        let zone = recursive.zone(domain_name)?;
+        let primary = recursive
+            .authoritive_resolvers(&zone)?
+            .iter()
+            .find_map(|resolver| resolver.soa(&zone).ok().flatten())
+            .map(|soa| soa.mname().to_string())
+            .ok_or_else(|| Error::NoZone(zone.clone()))?;
+        tracing::info!("Primary nameserver of zone {} is {}", zone, primary);
+        recursive
+            .authoritive_resolver(&primary)?
+            .addresses()
+            .first()
+            .copied()
+            .ok_or(Error::NoAddresses(primary))
+    }
+
+    /// wait_record checks the authoritive nameservers of the zone of the name periodically
+    /// until all of them serve the expected values in the records of the type at the name,
+    /// to wait for other dns changes than acme challenges.
+    /// Other values may only be served with [`Propagation::ignore_extra_records`].
+    /// The hidden primary, the cross check and DNSSEC validation are used for TXT records only.
+    /// It returns an error after the configured number of attempts failed.
+    pub fn wait_record<S>(
+        &self,
+        name: S,
+        record_type: RecordType,
+        expected_values: &[S],
+    ) -> Result<PropagationReport>
+    where
+        S: AsRef<str>,
+    {
+        let expected = expected_values
+            .iter()
+            .map(|value| record_type.normalize(value.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        let mut propagation = self.clone().label("").follow_cname(false);
+        propagation.record_type = record_type;
+        if record_type != RecordType::Txt {
+            propagation.primary = None;
+            propagation.cross_check = false;
+        }
+        propagation.run(name.as_ref(), None, Error::RecordNotPropagated, &|values| {
+            Ok((self.ignore_extra || values.len() == expected.len())
+                && expected.iter().all(|value| values.contains(value)))
+        })
+    }
+
+    /// wait_txt behaves like [`Propagation::wait_record`] for a TXT record with any name,
+    /// like the ownership records `_github-challenge-<org>.example.com` or `_mta-sts.example.com`.
+    /// Other values at the name are allowed, as domain verification tokens often share a name.
+    pub fn wait_txt<S>(&self, name: S, expected_value: S) -> Result<PropagationReport>
+    where
+        S: AsRef<str>,
+    {
+        self.clone().ignore_extra_records(true).wait_record(
+            name.as_ref(),
+            RecordType::Txt,
+            &[expected_value.as_ref()],
+        )
+    }
+
+    /// wait_tlsa behaves like [`Propagation::wait_record`] for the TLSA records of the tcp port
+    /// of the domain, like `_443._tcp.example.com`, to wait for the new certificate association
+    /// before a rotated certificate is put into use.
+    pub fn wait_tlsa<S>(
+        &self,
+        domain_name: S,
+        port: u16,
+        expected_values: &[S],
+    ) -> Result<PropagationReport>
+    where
+        S: AsRef<str>,
+    {
+        let expected_values = expected_values
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<_>>();
+        self.wait_record(
+            format!("_{}._tcp.{}", port, domain_name.as_ref()).as_str(),
+            RecordType::Tlsa,
+            &expected_values,
+        )
+    }
+
+    /// check_caa walks up from the domain like a certification authority does and reports
+    /// whether the first CAA records found permit the certification authority with
+    /// the issuer domain name ca, like `letsencrypt.org`, to issue a certificate for the domain.
+    /// The issuewild records are used for a wildcard domain name.
+    pub fn check_caa<S>(&self, domain_name: S, ca: S) -> Result<CaaReport>
+    where
+        S: AsRef<str>,
+    {
+        let recursive = self
+            .resolver
+            .recursive_resolver(false, &self.query_options)?;
+        caa::check_caa(&recursive, domain_name.as_ref(), ca.as_ref())
+    }
+
+    /// diagnose finds the authoritive nameservers like a wait does and returns the delegation
+    /// chain, the nameservers with their addresses and the TXT values each of them serves
+    /// for the challenge record, to find out why a wait does not finish.
+    pub fn diagnose<S>(&self, domain_name: S) -> Result<Diagnosis>
+    where
+        S: AsRef<str>,
+    {
+        let domain_name = normalize(domain_name.as_ref())?;
+        let recursives = self.recursives()?;
+        let discovery = self.discover_any(&recursives, &domain_name)?;
+        let (_, recursive) = &recursives[0];
+        let mut delegations = vec![];
+        for zone in ancestors(&discovery.zone)
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+        {
+            if recursive.is_zone(zone)? {
+                delegations.push(ZoneDelegation {
+                    zone: zone.to_owned(),
+                    nameservers: recursive.nameservers(zone)?,
+                });
+            }
+        }
+        let nameservers = discovery
+            .resolvers
+            .iter()
+            .map(|resolver| {
+                let (values, error) = match self.values(resolver, &discovery.record_name) {
+                    Ok(values) => (values, None),
+                    Err(error) => (vec![], Some(error.to_string())),
+                };
+                NameserverDiagnosis {
+                    name: resolver.name().to_owned(),
+                    addresses: resolver.addresses().to_vec(),
+                    values,
+                    error,
+                }
+            })
+            .collect();
+        Ok(Diagnosis {
+            domain_name,
+            record_name: discovery.record_name,
+            zone: discovery.zone,
+            delegations,
+            nameservers,
+        })
+    }
+
+    /// Full name of the challenge record as given to a provider.
+    fn provider_record(&self, domain_name: &str) -> Result<String> {
+        normalize(domain_name)
+            .map(|domain_name| record_name(&self.label, domain_name.trim_end_matches('.')))
+    }
+
+    fn check_challenge(&self, challenge: &str) -> Result<()> {
+        validate(challenge)?;
+        check_format(challenge, self.challenge_format)
+    }
+
+    fn serves(&self, values: &[String], challenges: &[&str]) -> Result<bool> {
+        serves_challenges(values, challenges, self.ignore_extra)
+    }
+
+    fn values(&self, resolver: &AuthoritiveResolver, record_name: &str) -> Result<Vec<String>> {
+        if self.record_type != RecordType::Txt {
+            return resolver.record_values(
+                self.record_type,
+                record_name,
+                self.require_authoritative,
+            );
+        }
+        #[cfg(feature = "dnssec")]
+        if self.query_options.dnssec_ok {
+            return verified_txt_values(resolver, record_name, self.signature_window);
+        }
+        if self.require_authoritative {
+            resolver.authoritative_txt_values(record_name)
+        } else {
+            resolver.txt_values(record_name)
+        }
+    }
+
+    /// Returns the zone to find the authoritive nameservers for and the name of the record to check.
+    /// The record name is the target of the CNAME when the challenge record is an alias.
+    /// The zone is the closest enclosing zone of the record, which is the record name itself
+    /// when the record is delegated with NS records.
+    fn target(&self, resolver: &RecursiveResolver, domain_name: &str) -> Result<(String, String)> {
+        let mut record_name = record_name(&self.label, domain_name);
+        if self.follow_cname {
+            if let Some(target) = resolver.cname_target(&record_name)? {
+                tracing::info!("{} is an alias for {}", record_name, target);
+                record_name = target;
+            }
+        }
+        let domain = domain_name.strip_prefix("*.").unwrap_or(domain_name);
+        if !resolver.exists(domain)? {
+            return Err(Error::NoZone(domain.to_owned()));
+        }
+        let zone = resolver.zone(&record_name)?;
+        tracing::debug!("{} is part of zone {}", record_name, zone);
+        Ok((zone, record_name))
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .map_or(false, CancellationToken::is_cancelled)
+    }
+
+    fn pause(&self, duration: Duration, deadline: Option<Instant>) -> bool {
+        pause(duration, deadline, self.cancellation.as_ref())
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn pause_async(&self, duration: Duration, deadline: Option<Instant>) -> bool {
+        pause_async(duration, deadline, self.cancellation.as_ref()).await
+    }
+
+    fn has_quorum(&self, verified: &[bool]) -> bool {
+        let required = self
+            .quorum
+            .map_or(verified.len(), |quorum| quorum.min(verified.len()));
+        verified.iter().filter(|verified| **verified).count() >= required
+    }
+
+    /// Runs the check on a single nameserver.
+    /// Errors are treated as a failed check while within the error tolerance window.
+    fn query(
+        &self,
+        nameserver: &mut Nameserver,
+        record_name: &str,
+        check: Check<'_>,
+    ) -> Result<bool> {
+        let result = self
+            .values(&nameserver.resolver, record_name)
+            .and_then(|values| check(&values));
+        self.tolerated(nameserver, result)
+    }
+
+    fn hidden_primary(&self) -> Option<Primary> {
+        let primary = Primary::new(self.primary?, &self.query_options);
+        #[cfg(feature = "tsig")]
+        let primary = primary.with_key(self.tsig_key.clone());
+        Some(primary)
+    }
+
+    /// Returns if the serial of the zone on the nameserver has reached the serial of the primary.
+    fn has_serial(&self, nameserver: &mut Nameserver, zone: &str, serial: u32) -> Result<bool> {
+        let result = nameserver
+            .resolver
+            .soa(zone)
+            .map(|soa| soa.map_or(false, |soa| serial_reached(soa.serial(), serial)));
+        self.tolerated(nameserver, result)
+    }
+
+    /// The serial of the zone on the primary, None while the primary does not pass the check.
+    fn primary_serial(
+        &self,
+        primary: &Primary,
+        zone: &str,
+        record_name: &str,
+        check: Check<'_>,
+    ) -> Result<Option<u32>> {
+        if !check(&primary.txt_values(record_name, &self.query_options)?)? {
+            tracing::warn!("Primary does not pass the check for {} yet", record_name);
+            return Ok(None);
+        }
+        let serial = primary.serial(zone, &self.query_options)?;
+        tracing::info!("Waiting for serial {} of zone {}", serial, zone);
+        Ok(Some(serial))
+    }
+
+    /// Treats errors as a failed check while the nameserver is within the error tolerance window.
+    fn tolerated(&self, nameserver: &mut Nameserver, result: Result<bool>) -> Result<bool> {
+        match result {
+            Ok(verified) => {
+                nameserver.failing_since = None;
+                Ok(verified)
+            }
+            Err(error) if error.is_transient() => {
+                let failing_since = *nameserver.failing_since.get_or_insert_with(Instant::now);
+                match self.error_tolerance {
+                    Some(window) if failing_since.elapsed() < window => {
+                        tracing::warn!("{} failed: {}", nameserver.resolver.name(), error);
+                        Ok(false)
+                    }
+                    _ => Err(error),
+                }
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Queries the nameservers that were skipped in the last attempt once more.
+    /// Returns if there is still a quorum.
+    fn recheck_skipped(
+        &self,
+        nameservers: &mut [Nameserver],
+        record_name: &str,
+        check: Check<'_>,
+    ) -> Result<bool> {
+        for nameserver in nameservers.iter_mut().filter(|ns| ns.skipped) {
+            nameserver.verified = self.query(nameserver, record_name, check)?;
+            if !nameserver.verified {
+                tracing::warn!("{} no longer passes the check", nameserver.resolver.name());
+            }
+        }
+        Ok(self.has_quorum(&verified(nameservers)))
+    }
+
+    /// Runs the check on the values returned by the public recursive resolvers.
+    /// The challenge record itself is queried, so the recursive resolvers follow a CNAME.
+    fn cross_checked(
+        &self,
+        public: &[(ResolverType, RecursiveResolver)],
+        record_name: &str,
+        check: Check<'_>,
+    ) -> Result<bool> {
+        for (resolver_type, resolver) in public {
+            if !check(&resolver.txt_values(record_name)?)? {
+                tracing::warn!("{} not yet up to date via {:?}", record_name, resolver_type);
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// The recursive resolver followed by the fallback resolvers.
+    fn recursives(&self) -> Result<Vec<(ResolverType, RecursiveResolver)>> {
+        std::iter::once(&self.resolver)
+            .chain(&self.fallback_resolvers)
+            .map(|resolver_type| {
+                resolver_type
+                    .recursive_resolver(false, &self.query_options)
+                    .map(|resolver| (resolver_type.clone(), resolver))
+            })
+            .collect()
+    }
+
+    /// Repeats a discovery that failed with a resolve error, waiting twice as long every time.
+    fn discover_retrying(
+        &self,
+        recursives: &[(ResolverType, RecursiveResolver)],
+        domain_name: &str,
+        deadline: Option<Instant>,
+    ) -> Result<Discovery> {
+        let mut backoff = self.discovery_backoff;
+        let mut retries = self.discovery_retries;
+        loop {
+            match self.discover_any(recursives, domain_name) {
+                Err(error @ (Error::Resolve(_) | Error::IO(_))) if retries > 0 => {
+                    tracing::warn!("Discovery failed, retrying in {:?}: {}", backoff, error);
+                    if !self.pause(backoff, deadline) {
+                        return Err(if self.is_cancelled() {
+                            Error::Cancelled
+                        } else {
+                            error
+                        });
+                    }
+                    retries -= 1;
+                    backoff = backoff.saturating_mul(2);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Finds the authoritive nameservers with the first recursive resolver that succeeds.
+    fn discover_any(
+        &self,
+        recursives: &[(ResolverType, RecursiveResolver)],
+        domain_name: &str,
+    ) -> Result<Discovery> {
+        let mut last_error = Error::NoNameservers;
+        for (resolver_type, recursive) in recursives {
+            match self.discover(recursive, domain_name) {
+                Ok(discovery) => return Ok(discovery),
+                Err(error) => {
+                    tracing::warn!("Discovery via {:?} failed: {}", resolver_type, error);
+                    last_error = error;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Finds the authoritive nameservers to check and the name of the record to check.
+    fn discover(&self, recursive: &RecursiveResolver, domain_name: &str) -> Result<Discovery> {
+        if !self.servers.is_empty() {
+            let resolvers = self
+                .servers
+                .iter()
+                .map(|address| {
+                    recursive.authoritive_resolver_at(&address.to_string(), vec![*address])
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let zone = domain_name.strip_prefix("*.").unwrap_or(domain_name);
+            return self.checked(
+                zone.to_owned(),
+                record_name(&self.label, domain_name),
+                resolvers,
+            );
+        }
+        let (zone, record_name) = self.target(recursive, domain_name)?;
+        let mut resolvers = self.authoritive_resolvers(recursive, &zone)?;
+        if self.check_delegation {
+            match delegation(recursive, &zone, &resolvers) {
+                Ok(Some(delegation)) if !delegation.is_consistent() => tracing::warn!(
+                    zone = %zone,
+                    parent_only = ?delegation.parent_only(),
+                    child_only = ?delegation.child_only(),
+                    "NS records in parent and child zone differ"
+                ),
+                Ok(_) => {}
+                Err(error) => tracing::warn!("Checking delegation of {} failed: {}", zone, error),
+            }
+        }
+        if self.per_address {
+            resolvers = resolvers
+                .iter()
+                .map(AuthoritiveResolver::per_address)
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .flatten()
+                .collect();
+        }
+        self.checked(zone, record_name, resolvers)
+    }
+
+    /// Applies the nameserver filter and the preflight check to the nameservers to check.
+    fn checked(
+        &self,
+        zone: String,
+        record_name: String,
+        resolvers: Vec<AuthoritiveResolver>,
+    ) -> Result<Discovery> {
+        let resolvers = resolvers
+            .into_iter()
+            .filter(|resolver| self.filter.allows(resolver.name(), resolver.addresses()))
+            .collect::<Vec<_>>();
+        if resolvers.is_empty() {
+            return Err(Error::NoNameservers);
+        }
+        if self.preflight {
+            for resolver in &resolvers {
+                preflight(resolver, &zone)?;
+            }
+        }
+        Ok(Discovery {
+            zone,
+            record_name,
+            resolvers,
+        })
+    }
+
+    /// The authoritive nameservers of the zone, with the addresses from the glue records
+    /// or from the nameservers of their own zone when enabled.
+    fn authoritive_resolvers(
+        &self,
+        recursive: &RecursiveResolver,
+        zone: &str,
+    ) -> Result<Vec<AuthoritiveResolver>> {
+        if !self.use_glue && !self.authoritative_addresses {
+            return recursive.authoritive_resolvers(zone);
+        }
+        let mut glue = if self.use_glue {
+            glue(recursive, zone)?
+        } else {
+            HashMap::new()
+        };
+        recursive
+            .nameservers(zone)?
+            .into_iter()
+            .map(
+                |host_name| match glue.remove(&host_name.to_ascii_lowercase()) {
+                    Some(addresses) => recursive.authoritive_resolver_at(&host_name, addresses),
+                    None if self.authoritative_addresses => {
+                        let addresses = host_addresses(recursive, &host_name)?;
+                        recursive.authoritive_resolver_at(&host_name, addresses)
+                    }
+                    None => recursive.authoritive_resolver(&host_name),
+                },
+            )
+            .collect()
+    }
+
+    /// The polling interval derived from the SOA record of the zone,
+    /// or the configured interval if the SOA record can not be found.
+    fn zone_interval(&self, discovery: &Discovery) -> Duration {
+        match self.adaptive_interval {
+            Some((min, max)) => discovery
+                .resolvers
+                .first()
+                .and_then(|resolver| resolver.soa(&discovery.zone).ok().flatten())
+                .map_or(self.interval, |soa| {
+                    let interval = soa_interval(soa.refresh(), soa.retry(), min, max);
+                    tracing::info!("Polling zone {} every {:?}", discovery.zone, interval);
+                    interval
+                }),
+            None => self.interval,
+        }
+    }
+
+    fn emit(&self, event: PropagationEvent, domain_name: &str, start: Instant) {
+        event::log(&event, domain_name, start.elapsed());
+        if let Some(observer) = &self.observer {
+            observer.notify(&event);
+        }
+    }
+
+    fn run(
+        &self,
+        domain_name: &str,
+        deadline: Option<Instant>,
+        timeout: Timeout,
+        check: Check<'_>,
+    ) -> Result<PropagationReport> {
+        let start = Instant::now();
+        let span = tracing::info_span!("wait", domain = domain_name, outcome = Empty);
+        let _entered = span.enter();
+        let result = normalize(domain_name)
+            .and_then(|domain_name| self.poll(&domain_name, deadline, timeout, check));
+        span.record("outcome", outcome(&result));
+        self.failed(&result, domain_name, start);
+        result
+    }
+
+    fn failed(&self, result: &Result<PropagationReport>, domain_name: &str, start: Instant) {
+        if let Err(error) = result {
+            #[cfg(feature = "metrics")]
+            metric::failure(error);
+            if !error.is_timeout() {
+                self.emit(
+                    PropagationEvent::Failed {
+                        error: error.to_string(),
+                    },
+                    domain_name,
+                    start,
+                );
+            }
+        }
+    }
+
+    fn poll(
+        &self,
+        domain_name: &str,
+        deadline: Option<Instant>,
+        timeout: Timeout,
+        check: Check<'_>,
+    ) -> Result<PropagationReport> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        let mut poll = self.begin(domain_name, deadline)?;
+        let mut in_time = self.pause(self.initial_delay, deadline);
+        while in_time {
+            match self.attempt(&mut poll, domain_name, deadline, check)? {
+                Step::Done(report) => return Ok(report),
+                Step::Retry => in_time = self.pause(poll.interval, deadline),
+                Step::Exhausted => break,
+            }
+        }
+        Err(self.timed_out(poll, domain_name, timeout))
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn poll_async(
+        &self,
+        domain_name: &str,
+        deadline: Option<Instant>,
+        timeout: Timeout,
+        check: AsyncCheck,
+    ) -> Result<PropagationReport> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        let propagation = Arc::new(self.clone());
+        let domain_name: Arc<str> = normalize(domain_name)?.into();
+        let mut poll = blocking({
+            let propagation = propagation.clone();
+            let domain_name = domain_name.clone();
+            move || propagation.begin(&domain_name, deadline)
+        })
+        .await?;
+        let mut in_time = self.pause_async(self.initial_delay, deadline).await;
+        while in_time {
+            let (returned, step) = blocking({
+                let propagation = propagation.clone();
+                let domain_name = domain_name.clone();
+                let check = check.clone();
+                move || {
+                    let step = propagation.attempt(&mut poll, &domain_name, deadline, &|values| {
+                        check(&propagation, values)
+                    });
+                    (poll, step)
+                }
+            })
+            .await;
+            poll = returned;
+            match step? {
+                Step::Done(report) => return Ok(report),
+                Step::Retry => in_time = self.pause_async(poll.interval, deadline).await,
+                Step::Exhausted => break,
+            }
+        }
+        Err(self.timed_out(poll, &domain_name, timeout))
+    }
+
+    /// Finds the nameservers to check, before the first attempt.
+    fn begin(&self, domain_name: &str, deadline: Option<Instant>) -> Result<Poll> {
+        let start = Instant::now();
+        let recursives = self.recursives()?;
+        let discovery = self.discover_retrying(&recursives, domain_name, deadline)?;
+        let interval = self.zone_interval(&discovery);
+        let public = if self.cross_check {
+            [ResolverType::Google, ResolverType::Cloudflare]
+                .into_iter()
+                .map(|resolver_type| {
+                    resolver_type
+                        .recursive_resolver(false, &self.query_options)
+                        .map(|resolver| (resolver_type, resolver))
+                })
+                .collect::<Result<Vec<_>>>()?
+        } else {
+            vec![]
+        };
+        Ok(Poll {
+            start,
+            recursives,
+            interval,
+            public,
+            challenge_record: record_name(&self.label, domain_name),
+            primary: self.hidden_primary(),
+            serial: None,
+            zone: discovery.zone,
+            record_name: discovery.record_name,
+            nameservers: discovery
+                .resolvers
+                .into_iter()
+                .map(Nameserver::from)
+                .collect(),
+            discovered: Instant::now(),
+            retried: 0,
+        })
+    }
+
+    /// Checks the nameservers once, the nameservers verified before are skipped.
+    fn attempt(
+        &self,
+        poll: &mut Poll,
+        domain_name: &str,
+        deadline: Option<Instant>,
+        check: Check<'_>,
+    ) -> Result<Step> {
+        if self
+            .rediscover
+            .map_or(false, |every| poll.discovered.elapsed() >= every)
+        {
+            let discovery = self.discover_retrying(&poll.recursives, domain_name, deadline)?;
+            if discovery.zone != poll.zone {
+                poll.serial = None;
+                poll.zone = discovery.zone;
+            }
+            poll.record_name = discovery.record_name;
+            poll.nameservers = merge(
+                std::mem::take(&mut poll.nameservers),
+                discovery.resolvers,
+                self.per_address,
+            );
+            poll.discovered = Instant::now();
+        }
+
+        let start = poll.start;
+        let attempt = poll.retried + 1;
+        let attempt_span = tracing::info_span!("attempt", attempt, verified = Empty);
+        let _entered = attempt_span.enter();
+        self.emit(
+            PropagationEvent::AttemptStarted { attempt },
+            domain_name,
+            start,
+        );
+        #[cfg(feature = "metrics")]
+        metric::attempt();
+        if let Some(primary) = poll.primary.as_ref().filter(|_| poll.serial.is_none()) {
+            poll.serial = self.primary_serial(primary, &poll.zone, &poll.record_name, check)?;
+        }
+        for nameserver in poll.nameservers.iter_mut() {
+            nameserver.skipped = nameserver.verified;
+            let query_span = tracing::info_span!(
+                "query",
+                nameserver = nameserver.resolver.name(),
+                outcome = Empty
+            );
+            let _query = query_span.enter();
+            if !nameserver.verified {
+                #[cfg(feature = "metrics")]
+                let queried = Instant::now();
+                nameserver.verified = match (&poll.primary, poll.serial) {
+                    (None, _) => self.query(nameserver, &poll.record_name, check)?,
+                    (Some(_), Some(serial)) => self.has_serial(nameserver, &poll.zone, serial)?,
+                    (Some(_), None) => false,
+                };
+                #[cfg(feature = "metrics")]
+                metric::query(nameserver.resolver.name(), queried.elapsed());
+            }
+            query_span.record(
+                "outcome",
+                match (nameserver.skipped, nameserver.verified) {
+                    (true, _) => "skipped",
+                    (false, true) => "verified",
+                    (false, false) => "lagging",
+                },
+            );
+            let name = nameserver.resolver.name().to_owned();
+            if nameserver.verified {
+                nameserver
+                    .report
+                    .propagated_after
+                    .get_or_insert_with(|| start.elapsed());
+                self.emit(
+                    PropagationEvent::NameserverVerified {
+                        attempt,
+                        nameserver: name,
+                    },
+                    domain_name,
+                    start,
+                );
+            } else {
+                self.emit(
+                    PropagationEvent::NameserverLagging {
+                        attempt,
+                        nameserver: name,
+                    },
+                    domain_name,
+                    start,
+                );
+            }
+        }
+        attempt_span.record(
+            "verified",
+            poll.nameservers.iter().filter(|ns| ns.verified).count(),
+        );
+        if self.has_quorum(&verified(&poll.nameservers))
+            && (!self.recheck
+                || self.recheck_skipped(&mut poll.nameservers, &poll.record_name, check)?)
+            && self.cross_checked(&poll.public, &poll.challenge_record, check)?
+        {
+            self.emit(
+                PropagationEvent::Done { attempts: attempt },
+                domain_name,
+                start,
+            );
+            #[cfg(feature = "metrics")]
+            metric::propagated(start.elapsed());
+            return Ok(Step::Done(PropagationReport {
+                domain_name: domain_name.to_owned(),
+                attempts: attempt,
+                duration: start.elapsed(),
+                nameservers: std::mem::take(&mut poll.nameservers)
+                    .into_iter()
+                    .map(|ns| ns.report)
+                    .collect(),
+            }));
+        }
+        if poll.retried >= self.retries {
+            return Ok(Step::Exhausted);
+        }
+        poll.retried += 1;
+        let (up_to_date, lagging): (Vec<_>, Vec<_>) =
+            poll.nameservers.iter().partition(|ns| ns.verified);
+        tracing::warn!(
+            attempt = poll.retried,
+            lagging = ?names(&lagging),
+            verified = ?names(&up_to_date),
+            "Attempt failed"
+        );
+        Ok(Step::Retry)
+    }
+
+    /// The error when the attempts are used up, the deadline has passed or the wait is cancelled.
+    fn timed_out(&self, poll: Poll, domain_name: &str, timeout: Timeout) -> Error {
+        if self.is_cancelled() {
+            tracing::warn!("Waiting for acme challenge record cancelled");
+            return Error::Cancelled;
+        }
+        let lagging = poll
+            .nameservers
+            .into_iter()
+            .filter_map(|ns| (!ns.verified).then_some(ns.report))
+            .collect::<Vec<_>>();
+        tracing::error!("Timeout checking acme challenge record");
+        self.emit(
+            PropagationEvent::TimedOut {
+                attempts: poll.retried,
+            },
+            domain_name,
+            poll.start,
+        );
+        timeout(lagging)
+    }
+
+    /// wait_events runs [`Propagation::wait`] on a background thread and
+    /// returns a blocking iterator over the progress events,
+    /// see [`Propagation::wait_stream`] for async code.
+    /// The iterator ends after [`PropagationEvent::Done`], [`PropagationEvent::TimedOut`]
+    /// or [`PropagationEvent::Failed`].
+    pub fn wait_events<S>(
+        &self,
+        domain_name: S,
+        challenge: S,
+    ) -> impl Iterator<Item = PropagationEvent>
+    where
+        S: AsRef<str>,
+    {
+        let (sender, receiver) = channel();
+        let sender = Mutex::new(sender);
+        let propagation = self.clone().on_event(move |event| {
+            if let Ok(sender) = sender.lock() {
+                sender.send(event.clone()).ok();
+            }
+        });
+        let domain_name = domain_name.as_ref().to_owned();
+        let challenge = challenge.as_ref().to_owned();
+        spawn(move || propagation.wait(domain_name, challenge));
+        receiver.into_iter()
+    }
+
+    /// wait_stream runs [`Propagation::wait_async`] on a task of the current tokio runtime
+    /// and returns a stream of the progress events, it panics outside a tokio runtime.
+    /// The stream ends after [`PropagationEvent::Done`], [`PropagationEvent::TimedOut`]
+    /// or [`PropagationEvent::Failed`].
+    #[cfg(feature = "tokio")]
+    pub fn wait_stream<S>(
+        &self,
+        domain_name: S,
+        challenge: S,
+    ) -> impl tokio_stream::Stream<Item = PropagationEvent>
+    where
+        S: AsRef<str>,
+    {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let propagation = self.clone().on_event(move |event| {
+            sender.send(event.clone()).ok();
+        });
+        let domain_name = domain_name.as_ref().to_owned();
+        let challenge = challenge.as_ref().to_owned();
+        tokio::spawn(async move { propagation.wait_async(domain_name, challenge).await });
+        tokio_stream::wrappers::UnboundedReceiverStream::new(receiver)
+    }
+
+    /// spawn_wait runs [`Propagation::wait`] on a background thread.
+    /// The returned handle gives access to the current status and can abort the wait.
+    pub fn spawn_wait<S>(&self, domain_name: S, challenge: S) -> WaitHandle
+    where
+        S: AsRef<str>,
+    {
+        let status = Arc::new(Mutex::new(PropagationStatus::default()));
+        let cancellation = self.cancellation.clone().unwrap_or_default();
+        let previous = self.observer.clone();
+        let shared = status.clone();
+        let propagation = self
+            .clone()
+            .cancellation(cancellation.clone())
+            .on_event(move |event| {
+                if let Some(observer) = &previous {
+                    observer.notify(event);
+                }
+                shared
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .apply(event);
+            });
+        let domain_name = domain_name.as_ref().to_owned();
+        let challenge = challenge.as_ref().to_owned();
+        WaitHandle {
+            status,
+            cancellation,
+            thread: spawn(move || propagation.wait(domain_name, challenge)),
+        }
+    }
+}
+
+/// Result of the discovery of the authoritive nameservers
+struct Discovery {
+    zone: String,
+    record_name: String,
+    resolvers: Vec<AuthoritiveResolver>,
+}
+
+/// State of a wait between two attempts.
+struct Poll {
+    start: Instant,
+    recursives: Vec<(ResolverType, RecursiveResolver)>,
+    interval: Duration,
+    /// The public resolvers of the cross check
+    public: Vec<(ResolverType, RecursiveResolver)>,
+    challenge_record: String,
+    primary: Option<Primary>,
+    /// Serial of the hidden primary once it serves the record
+    serial: Option<u32>,
+    zone: String,
+    record_name: String,
+    nameservers: Vec<Nameserver>,
+    discovered: Instant,
+    /// Attempts that failed and were followed by another attempt
+    retried: usize,
+}
+
+/// Outcome of a single attempt.
+enum Step {
+    Done(PropagationReport),
+    Retry,
+    Exhausted,
+}
+
+/// One tenth of the smaller of the SOA refresh and retry values, bounded by min and max.
+fn soa_interval(refresh: i32, retry: i32, min: Duration, max: Duration) -> Duration {
+    let seconds = u64::try_from(refresh.min(retry)).unwrap_or_default();
+    Duration::from_secs(seconds / 10).clamp(min, max)
+}
+
+/// Fails if the nameserver does not answer authoritively for the zone.
+fn preflight(resolver: &AuthoritiveResolver, zone: &str) -> Result<()> {
+    let lame = || Error::Lame {
+        nameserver: resolver.name().to_owned(),
+        zone: zone.to_owned(),
+    };
+    match resolver.soa(zone) {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(lame()),
+        Err(error) => {
+            tracing::error!(
+                "SOA query for {} on {} failed: {}",
+                zone,
+                resolver.name(),
+                error
+            );
+            Err(lame())
+        }
+    }
+}
+
+/// State of a single authoritive nameserver while waiting.
+struct Nameserver {
+    resolver: AuthoritiveResolver,
+    report: NameserverReport,
+    verified: bool,
+    /// Not queried in the current attempt because it was verified before
+    skipped: bool,
+    /// Start of the current series of failed queries
+    failing_since: Option<Instant>,
+}
+
+impl From<AuthoritiveResolver> for Nameserver {
+    fn from(resolver: AuthoritiveResolver) -> Self {
+        Self {
+            report: NameserverReport::from(&resolver),
+            resolver,
+            verified: false,
+            skipped: false,
+            failing_since: None,
+        }
+    }
+}
+
+/// Outcome of a wait as recorded on its span.
+fn outcome<T>(result: &Result<T>) -> &'static str {
+    match result {
+        Ok(_) => "propagated",
+        Err(error) if error.is_timeout() => "timeout",
+        Err(Error::Cancelled) => "cancelled",
+        Err(_) => "failed",
+    }
+}
+
+fn names<'a>(nameservers: &[&'a Nameserver]) -> Vec<&'a str> {
+    nameservers.iter().map(|ns| ns.resolver.name()).collect()
+}
+
+fn verified(nameservers: &[Nameserver]) -> Vec<bool> {
+    nameservers.iter().map(|ns| ns.verified).collect()
+}
+
+/// Replaces the nameservers by the rediscovered ones.
+/// Nameservers that were already known keep their state,
+/// when checking per address only if the address is unchanged.
+fn merge(
+    mut known: Vec<Nameserver>,
+    resolvers: Vec<AuthoritiveResolver>,
+    per_address: bool,
+) -> Vec<Nameserver> {
+    let merged = resolvers
+        .into_iter()
+        .map(|resolver| {
+            match known.iter().position(|ns| {
+                ns.resolver.name() == resolver.name()
+                    && (!per_address || ns.resolver.addresses() == resolver.addresses())
+            }) {
+                Some(index) => {
+                    let mut nameserver = known.swap_remove(index);
+                    nameserver.report.addresses = resolver.addresses().to_vec();
+                    nameserver.resolver = resolver;
+                    nameserver
+                }
+                None => {
+                    tracing::info!("Nameserver {} added", resolver.name());
+                    Nameserver::from(resolver)
+                }
+            }
+        })
+        .collect();
+    for nameserver in known {
+        tracing::info!("Nameserver {} removed", nameserver.resolver.name());
+    }
+    merged
+}
+
+/// Runs f on the blocking threads of tokio, panics of f are passed on.
+#[cfg(feature = "tokio")]
+async fn blocking<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(error) => std::panic::resume_unwind(error.into_panic()),
+    }
+}
+
+/// Sleeps for duration, but never past the deadline.
+/// Returns false if the deadline has passed or the token was cancelled.
+fn pause(
+    duration: Duration,
+    deadline: Option<Instant>,
+    cancellation: Option<&CancellationToken>,
+) -> bool {
+    let duration = until(duration, deadline);
+    let not_cancelled = match cancellation {
+        Some(token) => token.sleep(duration),
+        None => {
+            if !duration.is_zero() {
+                sleep(duration);
+            }
+            true
+        }
+    };
+    not_cancelled && deadline.map_or(true, |deadline| Instant::now() < deadline)
+}
+
+/// Async version of [`pause`], a cancel wakes the sleep immediately.
+#[cfg(feature = "tokio")]
+async fn pause_async(
+    duration: Duration,
+    deadline: Option<Instant>,
+    cancellation: Option<&CancellationToken>,
+) -> bool {
+    let duration = until(duration, deadline);
+    let not_cancelled = match cancellation {
+        Some(token) => tokio::time::timeout(duration, token.cancelled())
+            .await
+            .is_err(),
+        None => {
+            tokio::time::sleep(duration).await;
+            true
+        }
+    };
+    not_cancelled && deadline.map_or(true, |deadline| Instant::now() < deadline)
+}
+
+/// Shortens duration so it does not end past the deadline.
+fn until(duration: Duration, deadline: Option<Instant>) -> Duration {
+    match deadline {
+        Some(deadline) => duration.min(deadline.saturating_duration_since(Instant::now())),
+        None => duration,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        io,
+        sync::Mutex,
+        thread::spawn,
+        time::{Duration, Instant},
+    };
+
+    use super::{outcome, pause, soa_interval, Propagation};
+    use crate::{
+        cancel::CancellationToken, error::Error, event::PropagationEvent,
+        handle::PropagationStatus, DnsProvider, MockResolver, RecordType, ResolverType,
+    };
+
+    const RECORD: &str = "_acme-challenge.paulmin.nl";
+
+    /// Three nameservers of paulmin.nl, the first two serve the challenge.
+    fn mock() -> MockResolver {
+        let mock = MockResolver::new();
+        mock.nameserver(
+            "paulmin.nl",
+            "ns0.transip.net.",
+            "195.135.195.195".parse().unwrap(),
+        )
+        .nameserver(
+            "paulmin.nl",
+            "ns1.transip.nl.",
+            "195.8.195.195".parse().unwrap(),
+        )
+        .nameserver(
+            "paulmin.nl",
+            "ns2.transip.eu.",
+            "37.97.199.195".parse().unwrap(),
+        )
+        .txt("ns0.transip.net", RECORD, &["JaJaNeeNee"])
+        .txt("ns1.transip.nl", RECORD, &["JaJaNeeNee"]);
+        mock
+    }
+
+    fn offline(mock: &MockResolver) -> Propagation {
+        Propagation::new()
+            .resolver(ResolverType::Mock(mock.clone()))
+            .initial_delay(Duration::ZERO)
+            .interval(Duration::ZERO)
+    }
+
+    /// Records the calls, creating fails when asked.
+    #[derive(Default)]
+    struct Provider {
+        fail: bool,
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl DnsProvider for Provider {
+        type Error = io::Error;
+
+        fn create_txt(&self, name: &str, value: &str) -> io::Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("create {name} {value}"));
+            if self.fail {
+                Err(io::Error::new(io::ErrorKind::Other, "quota exceeded"))
+            } else {
+                Ok(())
+            }
+        }
+
+        fn delete_txt(&self, name: &str, value: &str) -> io::Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("delete {name} {value}"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn span_outcome() {
+        assert_eq!(outcome(&Ok(())), "propagated");
+        assert_eq!(outcome::<()>(&Err(Error::AcmeChallege(vec![]))), "timeout");
+        assert_eq!(outcome::<()>(&Err(Error::Cancelled)), "cancelled");
+        assert_eq!(outcome::<()>(&Err(Error::NoNameservers)), "failed");
+    }
+
+    #[test]
+    fn defaults() {
+        let propagation = Propagation::default();
+        assert_eq!(propagation.retries, 720);
+        assert_eq!(propagation.interval, Duration::from_secs(5));
+        assert_eq!(propagation.initial_delay, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn builder() {
+        let propagation = Propagation::new()
+            .retries(3)
+            .interval(Duration::from_millis(100))
+            .initial_delay(Duration::ZERO);
+        assert_eq!(propagation.retries, 3);
+        assert_eq!(propagation.interval, Duration::from_millis(100));
+        assert_eq!(propagation.initial_delay, Duration::ZERO);
+    }
+
+    #[test]
+    fn servers_skip_discovery() {
+        let address = "2a01:7c8:7000:195::195".parse().unwrap();
+        let propagation = Propagation::new().servers(&[address]);
+        let recursive = propagation
+            .resolver
+            .recursive_resolver(false, &propagation.query_options)
+            .unwrap();
+        let discovery = propagation.discover(&recursive, "paulmin.nl.").unwrap();
+        assert_eq!(discovery.zone, "paulmin.nl.");
+        assert_eq!(discovery.record_name, "_acme-challenge.paulmin.nl.");
+        assert_eq!(discovery.resolvers.len(), 1);
+        assert_eq!(discovery.resolvers[0].addresses(), &[address]);
+    }
+
+    #[test]
+    fn offline_lagging_nameserver() {
+        let mock = mock();
+        mock.txt_after("ns2.transip.eu", RECORD, 2, &["JaJaNeeNee"]);
+        let report = offline(&mock)
+            .wait_report("paulmin.nl", "JaJaNeeNee")
+            .unwrap();
+        assert_eq!(report.attempts, 3);
+        assert_eq!(report.nameservers.len(), 3);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn offline_async() {
+        let mock = mock();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let propagation = offline(&mock).retries(1);
+        let result = runtime.block_on(async move {
+            tokio::spawn(async move { propagation.wait_async("paulmin.nl", "JaJaNeeNee").await })
+                .await
+                .unwrap()
+        });
+        assert!(matches!(result, Err(Error::AcmeChallege(lagging)) if lagging.len() == 1));
+
+        mock.txt_after("ns2.transip.eu", RECORD, 2, &["JaJaNeeNee"]);
+        let report = runtime
+            .block_on(offline(&mock).wait_report_async("paulmin.nl", "JaJaNeeNee"))
+            .unwrap();
+        assert_eq!(report.attempts, 3);
+        assert_eq!(report.nameservers.len(), 3);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn offline_async_cancelled_and_deadline() {
+        let mock = mock();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let token = CancellationToken::new();
+        let propagation = offline(&mock)
+            .initial_delay(Duration::from_secs(3600))
+            .cancellation(token.clone());
+        let start = Instant::now();
+        let cancelling = spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        });
+        let result = runtime.block_on(propagation.wait_async("paulmin.nl", "JaJaNeeNee"));
+        cancelling.join().unwrap();
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let result = runtime.block_on(
+            offline(&mock)
+                .initial_delay(Duration::from_secs(3600))
+                .wait_until_async("paulmin.nl", "JaJaNeeNee", deadline),
+        );
+        assert!(matches!(result, Err(Error::AcmeChallege(lagging)) if lagging.len() == 3));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn offline_retries_and_quorum() {
+        let mock = mock();
+        match offline(&mock).retries(1).wait("paulmin.nl", "JaJaNeeNee") {
+            Err(Error::AcmeChallege(lagging)) => {
+                assert_eq!(lagging.len(), 1);
+                assert_eq!(lagging[0].name, "ns2.transip.eu.");
+            }
+            other => panic!("not a timeout: {:?}", other),
+        }
+        let report = offline(&mock)
+            .quorum(2)
+            .wait_report("paulmin.nl", "JaJaNeeNee")
+            .unwrap();
+        assert_eq!(report.attempts, 1);
+    }
+
+    #[test]
+    fn offline_no_zone_above_domain() {
+        let mock = mock();
+        mock.nameserver("nl", "ns1.dns.nl.", "194.0.28.53".parse().unwrap())
+            .txt("ns1.dns.nl", "undelegated.nl", &["v=spf1 -all"]);
+        assert!(matches!(
+            offline(&mock).wait("undelegated.nl", "JaJaNeeNee"),
+            Err(Error::NoZone(name)) if name == "_acme-challenge.undelegated.nl"
+        ));
+        assert!(matches!(
+            offline(&mock).wait("nonexistent.nl", "JaJaNeeNee"),
+            Err(Error::NoZone(name)) if name == "nonexistent.nl"
+        ));
+    }
+
+    #[test]
+    fn offline_preflight() {
+        let mock = mock();
+        assert!(matches!(
+            offline(&mock).preflight(true).retries(1).wait("paulmin.nl", "JaJaNeeNee"),
+            Err(Error::AcmeChallege(lagging)) if lagging.len() == 1
+        ));
+        mock.lame("ns2.transip.eu", "paulmin.nl");
+        assert!(matches!(
+            offline(&mock).preflight(true).wait("paulmin.nl", "JaJaNeeNee"),
+            Err(Error::Lame { nameserver, .. }) if nameserver == "ns2.transip.eu."
+        ));
+    }
+
+    #[test]
+    fn offline_cname() {
+        let mock = mock();
+        let target = "_acme-challenge.validation.example.net";
+        mock.cname(RECORD, target)
+            .nameserver(
+                "example.net",
+                "ns1.example.net.",
+                "192.0.2.1".parse().unwrap(),
+            )
+            .txt("ns1.example.net", target, &["JaJaNeeNee"]);
+        let report = offline(&mock)
+            .wait_report("paulmin.nl", "JaJaNeeNee")
+            .unwrap();
+        assert_eq!(report.nameservers.len(), 1);
+        assert_eq!(report.nameservers[0].name, "ns1.example.net.");
+        assert!(matches!(
+            offline(&mock).follow_cname(false).retries(1).wait("paulmin.nl", "JaJaNeeNee"),
+            Err(Error::AcmeChallege(lagging)) if lagging.len() == 1
+        ));
+    }
+
+    #[test]
+    fn offline_negative_cache_ttl() {
+        assert_eq!(
+            offline(&mock()).negative_cache_ttl("paulmin.nl").unwrap(),
+            Some(Duration::from_secs(300))
+        );
+    }
+
+    #[test]
+    fn offline_nameserver_recovers() {
+        let mock = mock();
+        mock.fail_before("ns2.transip.eu", RECORD, 2, &["JaJaNeeNee"]);
+        let report = offline(&mock)
+            .tolerate_errors(Duration::from_secs(60))
+            .wait_report("paulmin.nl", "JaJaNeeNee")
+            .unwrap();
+        assert_eq!(report.attempts, 3);
+        assert_eq!(report.nameservers.len(), 3);
+    }
+
+    #[test]
+    fn offline_nameserver_error() {
+        let mock = mock();
+        mock.fail("ns2.transip.eu", RECORD);
+        assert!(matches!(
+            offline(&mock).wait("paulmin.nl", "JaJaNeeNee"),
+            Err(Error::Response { nameserver, .. }) if nameserver == "ns2.transip.eu"
+        ));
+    }
+
+    #[test]
+    fn provision_deletes_after_failure() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let provider = Provider::default();
+        let result = Propagation::new().cancellation(token).provision_and_wait(
+            &provider,
+            "*.paulmin.nl.",
+            "JaJaNeeNee",
+        );
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(
+            provider.calls.into_inner().unwrap(),
+            vec![
+                "create _acme-challenge.paulmin.nl JaJaNeeNee",
+                "delete _acme-challenge.paulmin.nl JaJaNeeNee",
+            ]
+        );
+    }
+
+    #[test]
+    fn provision_fails() {
+        let provider = Provider {
+            fail: true,
+            ..Provider::default()
+        };
+        let result = Propagation::new().provision_and_wait(&provider, "paulmin.nl", "JaJaNeeNee");
+        assert!(matches!(result, Err(Error::Provider(_))));
+        assert!(matches!(
+            Propagation::new().provision_and_wait(&provider, "paul min.nl", "JaJaNeeNee"),
+            Err(Error::InvalidDomain(_))
+        ));
+        assert_eq!(provider.calls.into_inner().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn offline_record() {
+        let mock = mock();
+        mock.txt("ns2.transip.eu", RECORD, &["JaJaNeeNee", "NeeNeeJaJa"]);
+        let propagation = offline(&mock).retries(1);
+        match propagation.wait_record(RECORD, RecordType::Txt, &["JaJaNeeNee"]) {
+            Err(Error::RecordNotPropagated(lagging)) => {
+                assert_eq!(lagging.len(), 1);
+                assert_eq!(lagging[0].name, "ns2.transip.eu.");
+            }
+            other => panic!("not a timeout: {:?}", other),
+        }
+        let report = propagation
+            .ignore_extra_records(true)
+            .wait_record(RECORD, RecordType::Txt, &["JaJaNeeNee"])
+            .unwrap();
+        assert_eq!(report.attempts, 1);
+    }
+
+    #[test]
+    fn offline_ownership_record() {
+        const OWNERSHIP: &str = "_github-challenge-paulusminus.paulmin.nl";
+        let mock = mock();
+        for nameserver in ["ns0.transip.net", "ns1.transip.nl", "ns2.transip.eu"] {
+            mock.txt(nameserver, OWNERSHIP, &["v=spf1 -all", "5ad4e1b8d0"]);
+        }
+        let report = offline(&mock).wait_txt(OWNERSHIP, "5ad4e1b8d0").unwrap();
+        assert_eq!(report.attempts, 1);
+        assert_eq!(report.domain_name, OWNERSHIP);
+    }
+
+    #[test]
+    fn offline_diagnose() {
+        let mock = mock();
+        mock.fail("ns2.transip.eu", RECORD);
+        let diagnosis = offline(&mock).diagnose("paulmin.nl").unwrap();
+        assert_eq!(diagnosis.delegations.len(), 1);
+        assert_eq!(diagnosis.delegations[0].nameservers.len(), 3);
+        let nameservers = diagnosis
+            .nameservers
+            .iter()
+            .map(|ns| (ns.name.as_str(), ns.values.len(), ns.error.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            nameservers,
+            vec![
+                ("ns0.transip.net.", 1, false),
+                ("ns1.transip.nl.", 1, false),
+                ("ns2.transip.eu.", 0, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn caa_without_records() {
+        let report = offline(&mock())
+            .check_caa("*.paulmin.nl", "letsencrypt.org")
+            .unwrap();
+        assert!(report.permitted);
+        assert_eq!(report.name, None);
+    }
+
+    #[test]
+    fn caa_records() {
+        let mock = mock();
+        mock.caa_issue("paulmin.nl", "letsencrypt.org")
+            .caa_issuewild("paulmin.nl", "sectigo.com")
+            .caa_issue("forbidden.paulmin.nl", "");
+        let caa = |domain_name, ca| offline(&mock).check_caa(domain_name, ca).unwrap();
+
+        let report = caa("paulmin.nl", "letsencrypt.org");
+        assert!(report.permitted);
+        assert_eq!(report.name.as_deref(), Some("paulmin.nl"));
+        assert_eq!(report.records.len(), 2);
+        assert!(!caa("paulmin.nl", "sectigo.com").permitted);
+
+        assert!(caa("*.paulmin.nl", "sectigo.com").permitted);
+        assert!(!caa("*.paulmin.nl", "letsencrypt.org").permitted);
+
+        let inherited = caa("www.paulmin.nl", "letsencrypt.org");
+        assert!(inherited.permitted);
+        assert_eq!(inherited.name.as_deref(), Some("paulmin.nl"));
+
+        let forbidden = caa("www.forbidden.paulmin.nl", "letsencrypt.org");
+        assert!(!forbidden.permitted);
+        assert_eq!(forbidden.name.as_deref(), Some("forbidden.paulmin.nl"));
+    }
+
+    #[test]
+    fn serial_converged_needs_soa() {
+        assert!(matches!(
+            offline(&mock()).wait_serial_converged("paulmin.nl"),
+            Err(Error::NoZone(_))
+        ));
+    }
+
+    #[test]
+    fn wait_all_reports_every_domain() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let challenges = HashMap::from([
+            ("paulmin.nl.", "JaJaNeeNee"),
+            ("transip.nl.", "NeeNeeJaJa"),
+            ("example.com.", "JaNee"),
+        ]);
+        let results = Propagation::new()
+            .concurrency(2)
+            .cancellation(token)
+            .wait_all(&challenges);
+        assert_eq!(results.len(), 3);
+        assert!(results
+            .values()
+            .all(|result| matches!(result, Err(Error::Cancelled))));
+    }
+
+    #[test]
+    fn quorum() {
+        let all = Propagation::new();
+        assert!(all.has_quorum(&[true, true, true]));
+        assert!(!all.has_quorum(&[true, false, true]));
+
+        let two = Propagation::new().quorum(2);
+        assert!(two.has_quorum(&[true, false, true]));
+        assert!(!two.has_quorum(&[false, false, true]));
+
+        let too_many = Propagation::new().quorum(5);
+        assert!(too_many.has_quorum(&[true, true, true]));
+        assert!(!too_many.has_quorum(&[true, false, true]));
+    }
+
+    #[test]
+    fn interval_from_soa() {
+        let min = Duration::from_secs(2);
+        let max = Duration::from_secs(60);
+        assert_eq!(soa_interval(3600, 300, min, max), Duration::from_secs(30));
+        assert_eq!(soa_interval(14400, 3600, min, max), max);
+        assert_eq!(soa_interval(10, 10, min, max), min);
+        assert_eq!(soa_interval(-1, 300, min, max), min);
+    }
+
+    #[test]
+    fn pause_stops_at_deadline() {
+        let start = Instant::now();
+        let in_time = pause(
+            Duration::from_secs(10),
+            Some(start + Duration::from_millis(50)),
+            None,
+        );
+        assert!(!in_time);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn pause_zero_returns_immediately() {
+        let start = Instant::now();
+        assert!(pause(Duration::ZERO, None, Some(&CancellationToken::new())));
+        assert!(start.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn pause_without_deadline() {
+        assert!(pause(Duration::ZERO, None, None));
+    }
+
+    #[test]
+    fn pause_cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+        assert!(!pause(Duration::from_secs(10), None, Some(&token)));
+    }
+
+    #[test]
+    fn wait_cancelled_before_first_attempt() {
+        let token = CancellationToken::new();
+        let propagation = offline(&mock())
+            .initial_delay(Duration::from_secs(3600))
+            .cancellation(token.clone());
+        let start = Instant::now();
+        let waiting = spawn(move || propagation.wait("paulmin.nl.", "JaJaNeeNee"));
+        token.cancel();
+        assert!(matches!(waiting.join().unwrap(), Err(Error::Cancelled)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn events_end_with_failed() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let events = Propagation::new()
+            .cancellation(token)
+            .wait_events("paulmin.nl.", "JaJaNeeNee")
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![PropagationEvent::Failed {
+                error: Error::Cancelled.to_string()
+            }]
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn offline_stream() {
+        use tokio_stream::StreamExt;
+
+        let mock = mock();
+        mock.txt("ns2.transip.eu", RECORD, &["JaJaNeeNee"]);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let events = runtime.block_on(async {
+            offline(&mock)
+                .wait_stream("paulmin.nl", "JaJaNeeNee")
+                .collect::<Vec<_>>()
+                .await
+        });
+        assert_eq!(
+            events.first(),
+            Some(&PropagationEvent::AttemptStarted { attempt: 1 })
+        );
+        assert_eq!(events.last(), Some(&PropagationEvent::Done { attempts: 1 }));
+    }
+
+    #[test]
+    fn spawn_wait_aborted() {
+        let handle = offline(&mock())
+            .initial_delay(Duration::from_secs(3600))
+            .spawn_wait("paulmin.nl.", "JaJaNeeNee");
+        handle.abort();
+        assert!(matches!(handle.join(), Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn spawn_wait_reports_failure() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let handle = Propagation::new()
+            .cancellation(token)
+            .spawn_wait("paulmin.nl.", "JaJaNeeNee");
+        while !handle.is_finished() {
+            std::thread::yield_now();
+        }
+        assert_eq!(
+            handle.status(),
+            PropagationStatus::Failed {
+                error: Error::Cancelled.to_string()
+            }
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use std::error::Error as StdError;
+
+#[cfg(feature = "cloudflare")]
+pub use self::cloudflare::CloudflareProvider;
+#[cfg(feature = "transip")]
+pub use self::transip::TransipProvider;
+
+#[cfg(feature = "cloudflare")]
+mod cloudflare;
+#[cfg(feature = "transip")]
+mod transip;
+
+/// Creates and deletes the challenge records at the dns hosting provider of a zone,
+/// see [`Propagation::provision_and_wait`](crate::Propagation::provision_and_wait).
+pub trait DnsProvider {
+    type Error: StdError + Send + Sync + 'static;
+
+    /// Adds a TXT record with the value to the records of the name,
+    /// the full name of the record without trailing dot.
+    fn create_txt(&self, name: &str, value: &str) -> Result<(), Self::Error>;
+
+    /// Removes the TXT record with the value, leaving other values of the name alone.
+    fn delete_txt(&self, name: &str, value: &str) -> Result<(), Self::Error>;
+}
+
+impl<P: DnsProvider + ?Sized> DnsProvider for &P {
+    type Error = P::Error;
+
+    fn create_txt(&self, name: &str, value: &str) -> Result<(), Self::Error> {
+        (**self).create_txt(name, value)
+    }
+
+    fn delete_txt(&self, name: &str, value: &str) -> Result<(), Self::Error> {
+        (**self).delete_txt(name, value)
+    }
+}
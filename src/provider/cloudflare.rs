@@ -0,0 +1,214 @@
+use std::env;
+
+use serde_json::{json, Value};
+
+use super::DnsProvider;
+use crate::Error;
+
+const API: &str = "https://api.cloudflare.com/client/v4";
+/// Read by [`CloudflareProvider::from_environment`].
+const TOKEN_VARIABLE: &str = "CLOUDFLARE_API_TOKEN";
+/// Time to live of the challenge records, the minimum of Cloudflare besides automatic.
+const TTL: u32 = 60;
+
+/// Creates and deletes the challenge records with the api of Cloudflare, in the zones
+/// the api token has the Zone:Read and DNS:Edit permissions for.
+///
+/// The records are served by the anycast nameservers of Cloudflare, which are checked
+/// like any other authoritive nameservers while waiting.
+pub struct CloudflareProvider {
+    token: String,
+}
+
+impl CloudflareProvider {
+    pub fn new<S: AsRef<str>>(token: S) -> Self {
+        Self {
+            token: token.as_ref().to_owned(),
+        }
+    }
+
+    /// Uses the api token in CLOUDFLARE_API_TOKEN.
+    pub fn from_environment() -> Result<Self, Error> {
+        env::var(TOKEN_VARIABLE)
+            .map(Self::new)
+            .map_err(|error| Error::Provider(format!("{TOKEN_VARIABLE}: {error}").into()))
+    }
+
+    /// Id of the zone of the name, the longest name of a zone the name is in.
+    fn zone(&self, name: &str) -> Result<String, Error> {
+        for zone in zones(name) {
+            let zones = self.request("GET", "/zones", &[("name", zone)], None)?;
+            if let Some(id) = zones
+                .as_array()
+                .and_then(|zones| zones.first())
+                .and_then(|zone| zone["id"].as_str())
+            {
+                return Ok(id.to_owned());
+            }
+        }
+        Err(Error::NoZone(name.to_owned()))
+    }
+
+    /// Sends a request to the api, the query parameters are encoded.
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> Result<Value, Error> {
+        let request = self.build(method, path, query);
+        let response = match body {
+            Some(body) => request.send_string(&body.to_string()),
+            None => request.call(),
+        };
+        let text = match response {
+            Ok(response) => response.into_string(),
+            Err(ureq::Error::Status(_, response)) => response.into_string(),
+            Err(error) => return Err(Error::Provider(error.into())),
+        }?;
+        serde_json::from_str(&text)
+            .map_err(|error| Error::Provider(error.into()))
+            .and_then(result)
+    }
+
+    fn build(&self, method: &str, path: &str, query: &[(&str, &str)]) -> ureq::Request {
+        query
+            .iter()
+            .fold(
+                ureq::request(method, &format!("{API}{path}")),
+                |request, (name, value)| request.query(name, value),
+            )
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .set("Content-Type", "application/json")
+    }
+}
+
+impl DnsProvider for CloudflareProvider {
+    type Error = Error;
+
+    fn create_txt(&self, name: &str, value: &str) -> Result<(), Error> {
+        let zone = self.zone(name)?;
+        let record = json!({"type": "TXT", "name": name, "content": value, "ttl": TTL});
+        self.request(
+            "POST",
+            &format!("/zones/{zone}/dns_records"),
+            &[],
+            Some(record),
+        )
+        .map(drop)
+    }
+
+    fn delete_txt(&self, name: &str, value: &str) -> Result<(), Error> {
+        let zone = self.zone(name)?;
+        let records = self.request(
+            "GET",
+            &format!("/zones/{zone}/dns_records"),
+            &[("type", "TXT"), ("name", name)],
+            None,
+        )?;
+        records
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|record| record["content"].as_str().map(unquote) == Some(value))
+            .filter_map(|record| record["id"].as_str())
+            .try_for_each(|id| {
+                self.request(
+                    "DELETE",
+                    &format!("/zones/{zone}/dns_records/{id}"),
+                    &[],
+                    None,
+                )
+                .map(drop)
+            })
+    }
+}
+
+/// The names a zone of the name could have, longest first.
+fn zones(name: &str) -> impl Iterator<Item = &str> {
+    let name = name.trim_end_matches('.');
+    name.match_indices('.')
+        .map(move |(index, _)| &name[index + 1..])
+        .filter(|zone| zone.contains('.'))
+}
+
+/// The result of a response of the api, or the messages of its errors.
+fn result(mut response: Value) -> Result<Value, Error> {
+    if response["success"].as_bool() == Some(true) {
+        Ok(response["result"].take())
+    } else {
+        let messages = response["errors"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|error| error["message"].as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        Err(Error::Provider(format!("Cloudflare: {messages}").into()))
+    }
+}
+
+/// Cloudflare can return the content of TXT records between quotes.
+fn unquote(content: &str) -> &str {
+    content
+        .strip_prefix('"')
+        .and_then(|content| content.strip_suffix('"'))
+        .unwrap_or(content)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::{result, unquote, zones, CloudflareProvider};
+    use crate::Error;
+
+    #[test]
+    fn zone_candidates() {
+        assert_eq!(
+            zones("_acme-challenge.www.paulmin.nl.").collect::<Vec<_>>(),
+            vec!["www.paulmin.nl", "paulmin.nl"]
+        );
+        assert_eq!(zones("paulmin.nl").count(), 0);
+    }
+
+    #[test]
+    fn response() {
+        assert_eq!(
+            result(json!({"success": true, "errors": [], "result": [{"id": "42"}]})).unwrap(),
+            json!([{"id": "42"}])
+        );
+        match result(
+            json!({"success": false, "errors": [{"code": 9109, "message": "Invalid access token"}]}),
+        ) {
+            Err(Error::Provider(error)) => {
+                assert_eq!(error.to_string(), "Cloudflare: Invalid access token")
+            }
+            other => panic!("not a provider error: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn query_encoded() {
+        let request = CloudflareProvider::new("token").build(
+            "GET",
+            "/zones/42/dns_records",
+            &[
+                ("type", "TXT"),
+                ("name", "_acme-challenge.paulmin.nl&per_page=1"),
+            ],
+        );
+        assert_eq!(
+            request.request_url().unwrap().as_url().as_str(),
+            "https://api.cloudflare.com/client/v4/zones/42/dns_records\
+             ?type=TXT&name=_acme-challenge.paulmin.nl%26per_page%3D1"
+        );
+    }
+
+    #[test]
+    fn quoted_content() {
+        assert_eq!(unquote("\"JaJaNeeNee\""), "JaJaNeeNee");
+        assert_eq!(unquote("JaJaNeeNee"), "JaJaNeeNee");
+    }
+}
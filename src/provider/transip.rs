@@ -0,0 +1,119 @@
+use std::sync::Mutex;
+
+use transip::{
+    api::{
+        dns::{DnsApi, DnsEntry},
+        domain::DomainApi,
+    },
+    configuration_from_environment, Client,
+};
+
+use super::DnsProvider;
+use crate::Error;
+
+/// Time to live of the challenge records, the minimum of the Transip api.
+const EXPIRE: u32 = 60;
+const TXT: &str = "TXT";
+
+/// Creates and deletes the challenge records with the api of Transip,
+/// in the domains of the account.
+pub struct TransipProvider {
+    client: Mutex<Client>,
+}
+
+impl TransipProvider {
+    pub fn new(client: Client) -> Self {
+        Self {
+            client: Mutex::new(client),
+        }
+    }
+
+    /// Uses the account and the key named by the environment variables of the transip crate.
+    pub fn from_environment() -> Result<Self, Error> {
+        configuration_from_environment()
+            .and_then(Client::try_from)
+            .map(Self::new)
+            .map_err(provider_error)
+    }
+
+    fn entry<F>(&self, name: &str, value: &str, f: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut Client, &str, DnsEntry) -> transip::Result<()>,
+    {
+        let mut client = self.client.lock().unwrap_or_else(|e| e.into_inner());
+        let domains = client.domain_list().map_err(provider_error)?;
+        let (domain, label) = split(name, domains.iter().map(|domain| domain.name.as_str()))
+            .ok_or_else(|| Error::NoZone(name.to_owned()))?;
+        let entry = DnsEntry {
+            name: label.to_owned(),
+            expire: EXPIRE,
+            entry_type: TXT.to_owned(),
+            content: value.to_owned(),
+        };
+        tracing::debug!("Challenge record {} in domain {}", label, domain);
+        f(&mut client, domain, entry).map_err(provider_error)
+    }
+}
+
+impl DnsProvider for TransipProvider {
+    type Error = Error;
+
+    fn create_txt(&self, name: &str, value: &str) -> Result<(), Error> {
+        self.entry(name, value, |client, domain, entry| {
+            client.dns_entry_insert(domain, entry)
+        })
+    }
+
+    fn delete_txt(&self, name: &str, value: &str) -> Result<(), Error> {
+        self.entry(name, value, |client, domain, entry| {
+            client.dns_entry_delete(domain, entry)
+        })
+    }
+}
+
+/// The errors of the transip crate can not be sent between threads, only their message is kept.
+fn provider_error(error: transip::Error) -> Error {
+    Error::Provider(error.to_string().into())
+}
+
+/// Splits the name in the longest of the domains it is in and the label relative to it,
+/// `@` for the domain itself.
+fn split<'a, 'b, I>(name: &'a str, domains: I) -> Option<(&'b str, &'a str)>
+where
+    I: IntoIterator<Item = &'b str>,
+{
+    domains
+        .into_iter()
+        .filter_map(|domain| {
+            if name.eq_ignore_ascii_case(domain) {
+                Some((domain, "@"))
+            } else {
+                let index = name.len().checked_sub(domain.len() + 1)?;
+                (name.is_char_boundary(index)
+                    && name[index..].starts_with('.')
+                    && name[index + 1..].eq_ignore_ascii_case(domain))
+                .then(|| (domain, &name[..index]))
+            }
+        })
+        .max_by_key(|(domain, _)| domain.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::split;
+
+    #[test]
+    fn longest_domain() {
+        let domains = ["paulmin.nl", "sub.paulmin.nl", "min.nl"];
+        assert_eq!(
+            split("_acme-challenge.www.sub.paulmin.nl", domains),
+            Some(("sub.paulmin.nl", "_acme-challenge.www"))
+        );
+        assert_eq!(
+            split("_acme-challenge.paulmin.nl", domains),
+            Some(("paulmin.nl", "_acme-challenge"))
+        );
+        assert_eq!(split("paulmin.nl", domains), Some(("paulmin.nl", "@")));
+        assert_eq!(split("_acme-challenge.transip.nl", domains), None);
+    }
+}
@@ -0,0 +1,153 @@
+//! Python module, built with maturin: `maturin build --features python`.
+
+use std::time::{Duration, Instant};
+
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+};
+
+use crate::{Error, NameserverReport, Propagation, PropagationReport, ResolverType};
+
+create_exception!(
+    acme_validation_propagation,
+    PropagationError,
+    PyException,
+    "Waiting failed."
+);
+create_exception!(
+    acme_validation_propagation,
+    PropagationTimeout,
+    PropagationError,
+    "The nameservers were not up to date in time."
+);
+
+/// Summary of a successful wait.
+#[pyclass(name = "PropagationReport", frozen, get_all)]
+struct PyPropagationReport {
+    domain_name: String,
+    attempts: usize,
+    /// Seconds between the start of the wait and the successful attempt
+    duration: f64,
+    nameservers: Vec<PyNameserverReport>,
+}
+
+/// Result for a single authoritive nameserver.
+#[pyclass(name = "NameserverReport", frozen, get_all)]
+#[derive(Clone)]
+struct PyNameserverReport {
+    name: String,
+    addresses: Vec<String>,
+    /// Seconds until the nameserver served the challenge, None if it never did
+    propagated_after: Option<f64>,
+}
+
+impl From<PropagationReport> for PyPropagationReport {
+    fn from(report: PropagationReport) -> Self {
+        Self {
+            domain_name: report.domain_name,
+            attempts: report.attempts,
+            duration: report.duration.as_secs_f64(),
+            nameservers: report.nameservers.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<NameserverReport> for PyNameserverReport {
+    fn from(report: NameserverReport) -> Self {
+        Self {
+            name: report.name,
+            addresses: report.addresses.iter().map(ToString::to_string).collect(),
+            propagated_after: report.propagated_after.as_ref().map(Duration::as_secs_f64),
+        }
+    }
+}
+
+/// Waits until all authoritive nameservers serve the challenge for the domain.
+/// Gives up after timeout seconds, or after the default number of attempts.
+#[pyfunction]
+#[pyo3(signature = (domain, challenge, timeout = None, resolver = None))]
+fn wait(
+    py: Python<'_>,
+    domain: &str,
+    challenge: &str,
+    timeout: Option<f64>,
+    resolver: Option<Vec<String>>,
+) -> PyResult<PyPropagationReport> {
+    let propagation = propagation(resolver)?;
+    let deadline = deadline(timeout)?;
+    py.allow_threads(|| match deadline {
+        Some(deadline) => propagation.wait_report_until(domain, challenge, deadline),
+        None => propagation.wait_report(domain, challenge),
+    })
+    .map(PyPropagationReport::from)
+    .map_err(exception)
+}
+
+/// Waits until none of the authoritive nameservers serves the challenge anymore.
+#[pyfunction]
+#[pyo3(signature = (domain, challenge, timeout = None, resolver = None))]
+fn wait_absent(
+    py: Python<'_>,
+    domain: &str,
+    challenge: &str,
+    timeout: Option<f64>,
+    resolver: Option<Vec<String>>,
+) -> PyResult<()> {
+    let propagation = propagation(resolver)?;
+    let deadline = deadline(timeout)?;
+    py.allow_threads(|| match deadline {
+        Some(deadline) => propagation.wait_absent_until(domain, challenge, deadline),
+        None => propagation.wait_absent(domain, challenge),
+    })
+    .map_err(exception)
+}
+
+/// The recursive resolvers at the addresses find the nameservers, Google by default.
+fn propagation(resolver: Option<Vec<String>>) -> PyResult<Propagation> {
+    let propagation = Propagation::new();
+    match resolver {
+        Some(addresses) => addresses
+            .iter()
+            .map(|address| address.parse())
+            .collect::<Result<Vec<_>, _>>()
+            .map(|addresses| propagation.resolver(ResolverType::Custom(addresses)))
+            .map_err(|error| PropagationError::new_err(format!("Invalid resolver: {error}"))),
+        None => Ok(propagation),
+    }
+}
+
+/// The instant timeout seconds from now, None without timeout or for a timeout too large
+/// to be an instant, like infinity.
+fn deadline(timeout: Option<f64>) -> PyResult<Option<Instant>> {
+    match timeout {
+        Some(seconds) if seconds.is_nan() || seconds < 0.0 => {
+            Err(PyValueError::new_err(format!("Invalid timeout {seconds}")))
+        }
+        Some(seconds) => Ok(Duration::try_from_secs_f64(seconds)
+            .ok()
+            .and_then(|timeout| Instant::now().checked_add(timeout))),
+        None => Ok(None),
+    }
+}
+
+fn exception(error: Error) -> PyErr {
+    if error.is_timeout() {
+        PropagationTimeout::new_err(error.to_string())
+    } else {
+        PropagationError::new_err(error.to_string())
+    }
+}
+
+#[pymodule]
+fn acme_validation_propagation(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    module.add_function(wrap_pyfunction!(wait, module)?)?;
+    module.add_function(wrap_pyfunction!(wait_absent, module)?)?;
+    module.add_class::<PyPropagationReport>()?;
+    module.add_class::<PyNameserverReport>()?;
+    module.add("PropagationError", py.get_type::<PropagationError>())?;
+    module.add("PropagationTimeout", py.get_type::<PropagationTimeout>())?;
+    Ok(())
+}
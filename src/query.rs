@@ -0,0 +1,590 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket},
+    sync::Mutex,
+    time::Duration,
+};
+
+use hickory_resolver::{
+    config::{self, NameServerConfigGroup, ResolverOpts},
+    proto::{
+        op::{Edns, Message, MessageType, OpCode, Query},
+        rr::{Name, RecordType},
+    },
+};
+use rand::{rngs::OsRng, Rng, RngCore};
+use socket2::{Domain, Socket, Type};
+
+use crate::{
+    limit::{Permit, QueryLimit, RateLimiter},
+    Error,
+};
+
+const MAX_UDP_RESPONSE: usize = 4096;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+const DNS_PORT: u16 = 53;
+const ATTEMPTS: usize = 2;
+const CONCURRENT_REQUESTS: usize = 2;
+const EDNS_PAYLOAD: u16 = 1232;
+const MAX_NAME_LENGTH: usize = 255;
+
+/// Address families used to reach a nameserver.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LookupIpStrategy {
+    /// Only the ipv4 addresses
+    Ipv4Only,
+    /// Only the ipv6 addresses
+    Ipv6Only,
+    /// Both the ipv4 and the ipv6 addresses
+    #[default]
+    Ipv4AndIpv6,
+    /// The ipv6 addresses, the ipv4 addresses if there are none
+    Ipv6thenIpv4,
+    /// The ipv4 addresses, the ipv6 addresses if there are none
+    Ipv4thenIpv6,
+}
+
+impl LookupIpStrategy {
+    pub(crate) fn hickory(self) -> config::LookupIpStrategy {
+        match self {
+            LookupIpStrategy::Ipv4Only => config::LookupIpStrategy::Ipv4Only,
+            LookupIpStrategy::Ipv6Only => config::LookupIpStrategy::Ipv6Only,
+            LookupIpStrategy::Ipv4AndIpv6 => config::LookupIpStrategy::Ipv4AndIpv6,
+            LookupIpStrategy::Ipv6thenIpv4 => config::LookupIpStrategy::Ipv6thenIpv4,
+            LookupIpStrategy::Ipv4thenIpv6 => config::LookupIpStrategy::Ipv4thenIpv6,
+        }
+    }
+}
+
+/// Order in which a resolver tries its nameservers.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ServerOrderingStrategy {
+    /// The fastest nameserver first, based on the earlier queries
+    #[default]
+    QueryStatistics,
+    /// The nameservers in the configured order
+    UserProvidedOrder,
+}
+
+impl ServerOrderingStrategy {
+    fn hickory(self) -> config::ServerOrderingStrategy {
+        match self {
+            ServerOrderingStrategy::QueryStatistics => {
+                config::ServerOrderingStrategy::QueryStatistics
+            }
+            ServerOrderingStrategy::UserProvidedOrder => {
+                config::ServerOrderingStrategy::UserProvidedOrder
+            }
+        }
+    }
+}
+
+/// How the nameservers are queried.
+/// The tuning of the timeout, attempts and concurrent requests also applies to the recursive resolver.
+#[derive(Clone, Debug)]
+pub(crate) struct QueryOptions {
+    /// Only use tcp, for networks where outgoing udp on port 53 is blocked
+    pub(crate) tcp_only: bool,
+    pub(crate) port: u16,
+    pub(crate) timeout: Duration,
+    /// Number of times a query is sent before giving up
+    pub(crate) attempts: usize,
+    /// Number of nameservers of the same resolver queried at the same time
+    pub(crate) concurrent_requests: usize,
+    /// Address families used to reach nameservers found by host name
+    pub(crate) ip_strategy: LookupIpStrategy,
+    /// Local address queries are sent from, for hosts with multiple uplinks.
+    /// Only used for nameservers of the same address family.
+    pub(crate) bind_address: Option<IpAddr>,
+    /// Udp payload size advertised with EDNS0 in direct queries, None to leave out EDNS0
+    pub(crate) edns_payload: Option<u16>,
+    /// Randomize the case of the name in direct queries and require it back unchanged (dns 0x20)
+    pub(crate) case_randomization: bool,
+    /// Order in which the resolvers try their nameservers
+    pub(crate) server_ordering: ServerOrderingStrategy,
+    /// Ask for the DNSSEC records with the DO bit in direct queries
+    pub(crate) dnssec_ok: bool,
+    /// Limit on the queries sent to the authoritive nameservers, shared by all clones
+    pub(crate) rate_limit: Option<RateLimiter>,
+    /// Limit on the queries in flight to the authoritive nameservers, shared by all clones
+    pub(crate) in_flight: Option<QueryLimit>,
+    /// Keep the tcp connections of direct queries open for the next query
+    pub(crate) keep_alive: bool,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            tcp_only: false,
+            port: DNS_PORT,
+            timeout: QUERY_TIMEOUT,
+            attempts: ATTEMPTS,
+            concurrent_requests: CONCURRENT_REQUESTS,
+            ip_strategy: LookupIpStrategy::Ipv4AndIpv6,
+            bind_address: None,
+            edns_payload: None,
+            case_randomization: false,
+            server_ordering: ServerOrderingStrategy::QueryStatistics,
+            dnssec_ok: false,
+            rate_limit: None,
+            in_flight: None,
+            keep_alive: false,
+        }
+    }
+}
+
+impl QueryOptions {
+    pub(crate) fn tune(&self, options: &mut ResolverOpts) {
+        options.timeout = self.timeout;
+        options.attempts = self.attempts;
+        options.num_concurrent_reqs = self.concurrent_requests;
+        options.ip_strategy = self.ip_strategy.hickory();
+        options.server_ordering_strategy = self.server_ordering.hickory();
+        if self.edns_payload.is_some() {
+            options.edns0 = true;
+        }
+    }
+
+    /// Blocks until the limits allow the next query to an authoritive nameserver.
+    /// The query counts as in flight until the returned permit is dropped.
+    pub(crate) fn throttle(&self) -> Option<Permit> {
+        let permit = self.in_flight.as_ref().map(QueryLimit::acquire);
+        if let Some(limiter) = &self.rate_limit {
+            limiter.acquire();
+        }
+        permit
+    }
+
+    /// Sets the local address of the nameservers in the group.
+    pub(crate) fn bind(&self, group: NameServerConfigGroup) -> NameServerConfigGroup {
+        match self.bind_address {
+            Some(_) => NameServerConfigGroup::from(
+                group
+                    .into_inner()
+                    .into_iter()
+                    .map(|mut config| {
+                        config.bind_addr = self.local_address(config.socket_addr);
+                        config
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            None => group,
+        }
+    }
+
+    /// The bind address for server, if one is configured for the address family of server.
+    fn local_address(&self, server: SocketAddr) -> Option<SocketAddr> {
+        self.bind_address
+            .filter(|address| address.is_ipv4() == server.is_ipv4())
+            .map(|address| SocketAddr::new(address, 0))
+    }
+}
+
+/// Tcp connections kept open between direct queries, by server address.
+#[derive(Debug, Default)]
+pub(crate) struct Connections {
+    streams: Mutex<HashMap<SocketAddr, TcpStream>>,
+}
+
+impl Connections {
+    fn take(&self, server: SocketAddr) -> Option<TcpStream> {
+        self.streams
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&server)
+    }
+
+    fn put(&self, server: SocketAddr, stream: TcpStream) {
+        self.streams
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(server, stream);
+    }
+}
+
+/// Sends a single non recursive query to server, without any caching.
+/// The query is repeated over tcp if the udp response is truncated.
+/// With keep alive, tcp connections are taken from and returned to connections.
+pub(crate) fn query(
+    server: SocketAddr,
+    name: &Name,
+    record_type: RecordType,
+    options: &QueryOptions,
+    connections: &Connections,
+) -> Result<Message, Error> {
+    let name = if options.case_randomization {
+        randomize_case(name)?
+    } else {
+        name.clone()
+    };
+    let request = request(&name, record_type, options);
+    let response = if options.tcp_only {
+        tcp(server, &request, options, connections)?
+    } else {
+        let response = udp(server, &request, options)?;
+        if response.truncated() {
+            tcp(server, &request, options, connections)?
+        } else {
+            response
+        }
+    };
+    if options.case_randomization && !same_question(&request, &response) {
+        return Err(Error::QuestionMismatch(name.to_string()));
+    }
+    Ok(response)
+}
+
+/// The name with the case of every letter chosen at random.
+/// The mask comes from the operating system, so it can not be derived from earlier queries.
+fn randomize_case(name: &Name) -> Result<Name, Error> {
+    let mut mask = [0u8; MAX_NAME_LENGTH / 8 + 1];
+    OsRng.fill_bytes(&mut mask);
+    let randomized = name
+        .to_ascii()
+        .chars()
+        .enumerate()
+        .map(|(index, c)| {
+            if mask[index / 8] >> (index % 8) & 1 == 0 {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            }
+        })
+        .collect::<String>();
+    Name::from_ascii(randomized).map_err(Error::proto)
+}
+
+/// The question in the response is exactly the question asked, including the case of the name.
+fn same_question(request: &Message, response: &Message) -> bool {
+    request.queries().len() == response.queries().len()
+        && request
+            .queries()
+            .iter()
+            .zip(response.queries())
+            .all(|(asked, answered)| {
+                asked.query_type() == answered.query_type()
+                    && asked.name().to_ascii() == answered.name().to_ascii()
+            })
+}
+
+pub(crate) fn request(name: &Name, record_type: RecordType, options: &QueryOptions) -> Message {
+    let mut request = Message::new();
+    request
+        .set_id(id())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(false)
+        .add_query(Query::query(name.clone(), record_type));
+    if options.edns_payload.is_some() || options.dnssec_ok {
+        let mut edns = Edns::new();
+        edns.set_max_payload(options.edns_payload.unwrap_or(EDNS_PAYLOAD));
+        edns.set_dnssec_ok(options.dnssec_ok);
+        request.set_edns(edns);
+    }
+    request
+}
+
+/// Transaction id that an off path attacker can not guess.
+fn id() -> u16 {
+    OsRng.gen()
+}
+
+fn unspecified(server: SocketAddr) -> SocketAddr {
+    if server.is_ipv4() {
+        SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+    } else {
+        SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0))
+    }
+}
+
+pub(crate) fn udp(
+    server: SocketAddr,
+    request: &Message,
+    options: &QueryOptions,
+) -> Result<Message, Error> {
+    let local = options
+        .local_address(server)
+        .unwrap_or_else(|| unspecified(server));
+    let socket = UdpSocket::bind(local)?;
+    socket.set_read_timeout(Some(options.timeout))?;
+    socket.connect(server)?;
+    socket.send(&request.to_vec().map_err(Error::proto)?)?;
+    let capacity = options.edns_payload.map_or(MAX_UDP_RESPONSE, |payload| {
+        MAX_UDP_RESPONSE.max(payload.into())
+    });
+    let mut buffer = vec![0; capacity];
+    loop {
+        let size = socket.recv(&mut buffer)?;
+        let response = Message::from_vec(&buffer[..size]).map_err(Error::proto)?;
+        if response.id() == request.id() {
+            return Ok(response);
+        }
+    }
+}
+
+pub(crate) fn tcp(
+    server: SocketAddr,
+    request: &Message,
+    options: &QueryOptions,
+    connections: &Connections,
+) -> Result<Message, Error> {
+    if options.keep_alive {
+        if let Some(mut stream) = connections.take(server) {
+            match exchange(&mut stream, request) {
+                Ok(response) => {
+                    connections.put(server, stream);
+                    return Ok(response);
+                }
+                Err(error) => tracing::debug!("Connection to {} closed: {}", server, error),
+            }
+        }
+    }
+    let mut stream = connect(server, options)?;
+    let response = exchange(&mut stream, request)?;
+    if options.keep_alive {
+        connections.put(server, stream);
+    }
+    Ok(response)
+}
+
+/// Sends the request over a new tcp connection and returns the response
+/// with the bytes it was read from, as needed to verify a signed response.
+#[cfg(feature = "tsig")]
+pub(crate) fn tcp_raw(
+    server: SocketAddr,
+    request: &Message,
+    options: &QueryOptions,
+) -> Result<(Message, Vec<u8>), Error> {
+    exchange_raw(&mut connect(server, options)?, request)
+}
+
+fn connect(server: SocketAddr, options: &QueryOptions) -> Result<TcpStream, Error> {
+    let timeout = options.timeout;
+    let stream = match options.local_address(server) {
+        Some(local) => {
+            let socket = Socket::new(Domain::for_address(server), Type::STREAM, None)?;
+            socket.bind(&local.into())?;
+            socket.connect_timeout(&server.into(), timeout)?;
+            TcpStream::from(socket)
+        }
+        None => TcpStream::connect_timeout(&server, timeout)?,
+    };
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    Ok(stream)
+}
+
+/// Sends the request over the stream and reads the response with the same id.
+fn exchange(stream: &mut TcpStream, request: &Message) -> Result<Message, Error> {
+    exchange_raw(stream, request).map(|(response, _)| response)
+}
+
+fn exchange_raw(stream: &mut TcpStream, request: &Message) -> Result<(Message, Vec<u8>), Error> {
+    let bytes = request.to_vec().map_err(Error::proto)?;
+    let length = u16::try_from(bytes.len()).map_err(|_| Error::Truncated)?;
+    stream.write_all(&length.to_be_bytes())?;
+    stream.write_all(&bytes)?;
+    loop {
+        let mut length = [0; 2];
+        stream.read_exact(&mut length)?;
+        let mut buffer = vec![0; u16::from_be_bytes(length).into()];
+        stream.read_exact(&mut buffer)?;
+        let response = Message::from_vec(&buffer).map_err(Error::proto)?;
+        if response.id() == request.id() {
+            return Ok((response, buffer));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpListener},
+        thread,
+        time::Duration,
+    };
+
+    use hickory_resolver::proto::{
+        op::{Message, MessageType, OpCode},
+        rr::{Name, RecordType},
+    };
+
+    use hickory_resolver::config::{self, ResolverOpts};
+
+    use super::{
+        randomize_case, request, same_question, tcp, unspecified, Connections, LookupIpStrategy,
+        QueryOptions, ServerOrderingStrategy,
+    };
+
+    #[test]
+    fn non_recursive_request() {
+        let name = Name::from_ascii("_acme-challenge.paulmin.nl.").unwrap();
+        let request = request(&name, RecordType::TXT, &QueryOptions::default());
+        assert_eq!(request.message_type(), MessageType::Query);
+        assert_eq!(request.op_code(), OpCode::Query);
+        assert!(!request.recursion_desired());
+        assert_eq!(request.queries().len(), 1);
+        assert_eq!(request.queries()[0].name(), &name);
+        assert_eq!(request.queries()[0].query_type(), RecordType::TXT);
+        assert!(request.extensions().is_none());
+    }
+
+    #[test]
+    fn case_randomization() {
+        let name = Name::from_ascii("_acme-challenge.paulmin.nl.").unwrap();
+        let randomized = randomize_case(&name).unwrap();
+        assert_eq!(randomized, name);
+        assert_eq!(
+            randomized.to_ascii().to_ascii_lowercase(),
+            "_acme-challenge.paulmin.nl."
+        );
+
+        let asked = request(&randomized, RecordType::TXT, &QueryOptions::default());
+        assert!(same_question(&asked, &asked));
+        let upper = request(
+            &Name::from_ascii("_ACME-challenge.paulmin.nl.").unwrap(),
+            RecordType::TXT,
+            &QueryOptions::default(),
+        );
+        let lower = request(
+            &Name::from_ascii("_acme-challenge.paulmin.nl.").unwrap(),
+            RecordType::TXT,
+            &QueryOptions::default(),
+        );
+        assert!(!same_question(&upper, &lower));
+    }
+
+    #[test]
+    fn case_randomization_unlinked() {
+        let name = Name::from_ascii(
+            "_acme-challenge.averylongsubdomainwithmanyletters.anotherlonglabel.paulmin.nl.",
+        )
+        .unwrap();
+        let first = randomize_case(&name).unwrap().to_ascii();
+        let second = randomize_case(&name).unwrap().to_ascii();
+        assert!(first.eq_ignore_ascii_case(&second));
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn edns_payload() {
+        let name = Name::from_ascii("_acme-challenge.paulmin.nl.").unwrap();
+        let options = QueryOptions {
+            edns_payload: Some(1400),
+            ..Default::default()
+        };
+        let request = request(&name, RecordType::TXT, &options);
+        assert_eq!(
+            request.extensions().as_ref().map(|edns| edns.max_payload()),
+            Some(1400)
+        );
+    }
+
+    #[test]
+    fn dnssec_ok() {
+        let name = Name::from_ascii("_acme-challenge.paulmin.nl.").unwrap();
+        let options = QueryOptions {
+            dnssec_ok: true,
+            ..Default::default()
+        };
+        let request = request(&name, RecordType::TXT, &options);
+        let edns = request.extensions().as_ref().unwrap();
+        assert!(edns.dnssec_ok());
+        assert_eq!(edns.max_payload(), 1232);
+    }
+
+    #[test]
+    fn bind_address_family() {
+        assert!(unspecified("8.8.8.8:53".parse::<SocketAddr>().unwrap()).is_ipv4());
+        assert!(unspecified("[2001:4860:4860::8888]:53".parse::<SocketAddr>().unwrap()).is_ipv6());
+    }
+
+    #[test]
+    fn bind_address_for_same_family() {
+        let options = QueryOptions {
+            bind_address: Some("192.0.2.1".parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.local_address("8.8.8.8:53".parse().unwrap()),
+            Some("192.0.2.1:0".parse().unwrap())
+        );
+        assert_eq!(
+            options.local_address("[2001:4860:4860::8888]:53".parse().unwrap()),
+            None
+        );
+    }
+
+    #[test]
+    fn default_tuning_keeps_resolver_defaults() {
+        let mut options = ResolverOpts::default();
+        QueryOptions::default().tune(&mut options);
+        let defaults = ResolverOpts::default();
+        assert_eq!(options.timeout, defaults.timeout);
+        assert_eq!(options.attempts, defaults.attempts);
+        assert_eq!(options.num_concurrent_reqs, defaults.num_concurrent_reqs);
+        assert_eq!(
+            options.server_ordering_strategy,
+            defaults.server_ordering_strategy
+        );
+    }
+
+    #[test]
+    fn tuning() {
+        let mut options = ResolverOpts::default();
+        QueryOptions {
+            timeout: Duration::from_secs(1),
+            attempts: 1,
+            concurrent_requests: 3,
+            ip_strategy: LookupIpStrategy::Ipv6Only,
+            server_ordering: ServerOrderingStrategy::UserProvidedOrder,
+            ..Default::default()
+        }
+        .tune(&mut options);
+        assert_eq!(options.timeout, Duration::from_secs(1));
+        assert_eq!(options.attempts, 1);
+        assert_eq!(options.num_concurrent_reqs, 3);
+        assert_eq!(options.ip_strategy, config::LookupIpStrategy::Ipv6Only);
+        assert_eq!(
+            options.server_ordering_strategy,
+            config::ServerOrderingStrategy::UserProvidedOrder
+        );
+    }
+
+    #[test]
+    fn keep_alive_reuses_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            for _ in 0..2 {
+                let mut length = [0; 2];
+                stream.read_exact(&mut length).unwrap();
+                let mut buffer = vec![0; u16::from_be_bytes(length).into()];
+                stream.read_exact(&mut buffer).unwrap();
+                let mut response = Message::from_vec(&buffer).unwrap();
+                response.set_message_type(MessageType::Response);
+                let bytes = response.to_vec().unwrap();
+                stream
+                    .write_all(&u16::try_from(bytes.len()).unwrap().to_be_bytes())
+                    .unwrap();
+                stream.write_all(&bytes).unwrap();
+            }
+        });
+        let options = QueryOptions {
+            keep_alive: true,
+            timeout: Duration::from_secs(2),
+            ..Default::default()
+        };
+        let connections = Connections::default();
+        let name = Name::from_ascii("_acme-challenge.paulmin.nl.").unwrap();
+        for _ in 0..2 {
+            let asked = request(&name, RecordType::TXT, &options);
+            let response = tcp(server, &asked, &options, &connections).unwrap();
+            assert_eq!(response.id(), asked.id());
+        }
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,116 @@
+use std::net::IpAddr;
+
+use data_encoding::HEXLOWER;
+use hickory_resolver::proto::{
+    op::Message,
+    rr::{self, RData},
+};
+
+use crate::{resolver::txt_value, Result};
+
+/// Type of the records checked by [`crate::Propagation::wait_record`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RecordType {
+    /// Ipv4 addresses
+    A,
+    /// Ipv6 addresses
+    Aaaa,
+    /// The target of an alias
+    Cname,
+    /// Text values, like the acme challenge
+    #[default]
+    Txt,
+    /// Certification authority authorization, in presentation format like `0 issue "letsencrypt.org"`
+    Caa,
+    /// Certificate association for DANE, in presentation format like `3 1 1 <hex>`
+    Tlsa,
+}
+
+impl RecordType {
+    pub(crate) fn hickory(self) -> rr::RecordType {
+        match self {
+            RecordType::A => rr::RecordType::A,
+            RecordType::Aaaa => rr::RecordType::AAAA,
+            RecordType::Cname => rr::RecordType::CNAME,
+            RecordType::Txt => rr::RecordType::TXT,
+            RecordType::Caa => rr::RecordType::CAA,
+            RecordType::Tlsa => rr::RecordType::TLSA,
+        }
+    }
+
+    /// The value as served by a nameserver, so it can be compared with the served values.
+    pub(crate) fn normalize(self, value: &str) -> Result<String> {
+        Ok(match self {
+            RecordType::A | RecordType::Aaaa => value.parse::<IpAddr>()?.to_string(),
+            RecordType::Cname => format!("{}.", value.trim_end_matches('.').to_ascii_lowercase()),
+            RecordType::Txt | RecordType::Caa => value.to_owned(),
+            RecordType::Tlsa => {
+                let mut fields = value.split_whitespace();
+                let parameters = fields.by_ref().take(3).collect::<Vec<_>>().join(" ");
+                format!(
+                    "{} {}",
+                    parameters,
+                    fields.collect::<String>().to_ascii_lowercase()
+                )
+            }
+        })
+    }
+
+    /// The values of the records of this type in the answer.
+    pub(crate) fn values(self, response: &Message) -> Vec<String> {
+        response
+            .answers()
+            .iter()
+            .filter(|record| record.record_type() == self.hickory())
+            .filter_map(|record| match record.data()? {
+                RData::A(a) => Some(a.to_string()),
+                RData::AAAA(aaaa) => Some(aaaa.to_string()),
+                RData::CNAME(cname) => Some(cname.to_ascii().to_ascii_lowercase()),
+                RData::TXT(txt) => Some(txt_value(txt)),
+                RData::CAA(caa) => Some(caa.to_string()),
+                RData::TLSA(tlsa) => Some(format!(
+                    "{} {} {} {}",
+                    u8::from(tlsa.cert_usage()),
+                    u8::from(tlsa.selector()),
+                    u8::from(tlsa.matching()),
+                    HEXLOWER.encode(tlsa.cert_data())
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RecordType;
+
+    #[test]
+    fn normalize() {
+        assert_eq!(
+            RecordType::Aaaa
+                .normalize("2a01:7c8:7000:0195::195")
+                .unwrap(),
+            "2a01:7c8:7000:195::195"
+        );
+        assert!(RecordType::A.normalize("ns1.transip.nl").is_err());
+        assert_eq!(
+            RecordType::Cname
+                .normalize("Paulusminus.GitHub.io")
+                .unwrap(),
+            "paulusminus.github.io."
+        );
+        assert_eq!(
+            RecordType::Caa
+                .normalize(r#"0 issue "letsencrypt.org""#)
+                .unwrap(),
+            r#"0 issue "letsencrypt.org""#
+        );
+        assert_eq!(
+            RecordType::Tlsa
+                .normalize("3 1 1 0C72AC70 B745AC19")
+                .unwrap(),
+            "3 1 1 0c72ac70b745ac19"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use std::{net::IpAddr, time::Duration};
+
+use crate::resolver::AuthoritiveResolver;
+
+/// Summary of a successful wait, returned by [`crate::Propagation::wait_report`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PropagationReport {
+    /// Domain name the challenge was checked for
+    pub domain_name: String,
+    /// Number of attempts needed, including the successful one
+    pub attempts: usize,
+    /// Time between the start of the wait and the successful attempt
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "duration_ms", serialize_with = "millis")
+    )]
+    pub duration: Duration,
+    /// The authoritive nameservers that were queried
+    pub nameservers: Vec<NameserverReport>,
+}
+
+/// Result for a single authoritive nameserver
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NameserverReport {
+    /// Host name of the nameserver
+    pub name: String,
+    /// Addresses the nameserver was queried on
+    pub addresses: Vec<IpAddr>,
+    /// Time between the start of the wait and the first attempt this nameserver served the challenge
+    #[cfg_attr(
+        feature = "serde",
+        serde(rename = "propagated_after_ms", serialize_with = "optional_millis")
+    )]
+    pub propagated_after: Option<Duration>,
+}
+
+#[cfg(feature = "serde")]
+impl PropagationReport {
+    /// The report as a json object, with the durations in milliseconds,
+    /// to archive the timing of the wait.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl From<&AuthoritiveResolver> for NameserverReport {
+    fn from(resolver: &AuthoritiveResolver) -> Self {
+        Self {
+            name: resolver.name().to_owned(),
+            addresses: resolver.addresses().to_vec(),
+            propagated_after: None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+fn millis<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.serialize_u64(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+}
+
+#[cfg(feature = "serde")]
+fn optional_millis<S>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match duration {
+        Some(duration) => millis(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::{NameserverReport, PropagationReport};
+
+    #[test]
+    fn to_json() {
+        let report = PropagationReport {
+            domain_name: "paulmin.nl".to_owned(),
+            attempts: 2,
+            duration: Duration::from_millis(6_500),
+            nameservers: vec![NameserverReport {
+                name: "ns1.transip.nl.".to_owned(),
+                addresses: vec!["195.135.195.195".parse().unwrap()],
+                propagated_after: None,
+            }],
+        };
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&report.to_json()).unwrap(),
+            json!({
+                "domain_name": "paulmin.nl",
+                "attempts": 2,
+                "duration_ms": 6500,
+                "nameservers": [{
+                    "name": "ns1.transip.nl.",
+                    "addresses": ["195.135.195.195"],
+                    "propagated_after_ms": null,
+                }],
+            })
+        );
+    }
+}
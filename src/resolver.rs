@@ -1,24 +1,112 @@
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
 
+#[cfg(unix)]
+use hickory_resolver::system_conf::parse_resolv_conf;
 use hickory_resolver::{
     config::{
-        LookupIpStrategy, NameServerConfigGroup, ResolverConfig, ResolverOpts, CLOUDFLARE_IPS,
-        GOOGLE_IPS,
+        NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts, CLOUDFLARE_IPS, GOOGLE_IPS,
+        QUAD9_IPS,
     },
-    error::ResolveErrorKind,
+    error::{ResolveError, ResolveErrorKind},
     lookup::{Ipv4Lookup, Ipv6Lookup},
-    proto::rr::rdata::{A, AAAA},
+    proto::{
+        op::{Message, ResponseCode},
+        rr::{
+            rdata::{caa::CAA, A, AAAA, SOA, TXT},
+            Name, RData, Record, RecordType,
+        },
+    },
+    system_conf::read_system_conf,
     Resolver,
 };
+#[cfg(unix)]
+use std::path::PathBuf;
+
+#[cfg(any(test, feature = "test-util"))]
+use crate::lookup::MockResolver;
+use crate::{
+    lookup::Lookup,
+    query::{query, Connections, QueryOptions},
+    record, recursive_resolver, Error, LookupIpStrategy,
+};
+
+pub(crate) const ACME_CHALLENGE_LABEL: &str = "_acme-challenge";
+const MAX_CNAME_CHAIN: usize = 8;
 
-use crate::{recursive_resolver, Error};
+const OPENDNS_IPS: &[IpAddr] = &[
+    IpAddr::V4(Ipv4Addr::new(208, 67, 222, 222)),
+    IpAddr::V4(Ipv4Addr::new(208, 67, 220, 220)),
+    IpAddr::V6(Ipv6Addr::new(0x2620, 0x119, 0x35, 0, 0, 0, 0, 0x35)),
+    IpAddr::V6(Ipv6Addr::new(0x2620, 0x119, 0x53, 0, 0, 0, 0, 0x53)),
+];
 
-pub(crate) enum ResolverType {
+const ADGUARD_IPS: &[IpAddr] = &[
+    IpAddr::V4(Ipv4Addr::new(94, 140, 14, 140)),
+    IpAddr::V4(Ipv4Addr::new(94, 140, 14, 141)),
+    IpAddr::V6(Ipv6Addr::new(0x2a10, 0x50c0, 0, 0, 0, 0, 0x1, 0xff)),
+    IpAddr::V6(Ipv6Addr::new(0x2a10, 0x50c0, 0, 0, 0, 0, 0x2, 0xff)),
+];
+
+/// The recursive resolver used to find the authoritive nameservers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ResolverType {
+    /// The public resolver of Google
+    #[default]
     Google,
-    #[allow(dead_code)]
+    /// The public resolver of Cloudflare
     Cloudflare,
-    #[allow(dead_code)]
+    /// The public resolver of Quad9
+    Quad9,
+    /// The public resolver of OpenDNS
+    OpenDNS,
+    /// The public resolver of AdGuard, the unfiltered variant
+    AdGuard,
+    /// A resolver listening on localhost
     Local,
+    /// The resolvers configured for the system, in `/etc/resolv.conf` on unix,
+    /// for networks where only internal resolvers are reachable
+    System,
+    /// The resolvers configured in the given resolv.conf file, for containers and chroots
+    #[cfg(unix)]
+    ResolvConf(PathBuf),
+    /// Resolvers on the given addresses, for networks where public resolvers are not reachable
+    Custom(Vec<IpAddr>),
+    /// Scripted answers of the recursive resolver and the authoritive nameservers,
+    /// for tests without network
+    #[cfg(any(test, feature = "test-util"))]
+    Mock(MockResolver),
+    /// The public resolver of Google over https
+    #[cfg(feature = "dns-over-https")]
+    GoogleHttps,
+    /// The public resolver of Cloudflare over https
+    #[cfg(feature = "dns-over-https")]
+    CloudflareHttps,
+    /// A resolver over https on the given addresses,
+    /// the server name is the host name of the url and is checked against the certificate
+    #[cfg(feature = "dns-over-https")]
+    Https {
+        addresses: Vec<IpAddr>,
+        server_name: String,
+    },
+    /// The public resolver of Google over tls
+    #[cfg(any(feature = "dns-over-tls", feature = "native-tls"))]
+    GoogleTls,
+    /// The public resolver of Cloudflare over tls
+    #[cfg(any(feature = "dns-over-tls", feature = "native-tls"))]
+    CloudflareTls,
+    /// A resolver over tls on the given addresses and port, usually 853.
+    /// The server name is checked against the certificate.
+    #[cfg(any(feature = "dns-over-tls", feature = "native-tls"))]
+    Tls {
+        addresses: Vec<IpAddr>,
+        server_name: String,
+        port: u16,
+    },
 }
 
 impl ResolverType {
@@ -26,26 +114,159 @@ impl ResolverType {
         match self {
             ResolverType::Google => GOOGLE_IPS,
             ResolverType::Cloudflare => CLOUDFLARE_IPS,
+            ResolverType::Quad9 => QUAD9_IPS,
+            ResolverType::OpenDNS => OPENDNS_IPS,
+            ResolverType::AdGuard => ADGUARD_IPS,
             ResolverType::Local => &[
                 IpAddr::V6(Ipv6Addr::LOCALHOST),
                 IpAddr::V4(Ipv4Addr::LOCALHOST),
             ],
+            ResolverType::System => &[],
+            #[cfg(unix)]
+            ResolverType::ResolvConf(_) => &[],
+            ResolverType::Custom(addresses) => addresses,
+            #[cfg(any(test, feature = "test-util"))]
+            ResolverType::Mock(_) => &[],
+            #[cfg(feature = "dns-over-https")]
+            ResolverType::GoogleHttps => GOOGLE_IPS,
+            #[cfg(feature = "dns-over-https")]
+            ResolverType::CloudflareHttps => CLOUDFLARE_IPS,
+            #[cfg(feature = "dns-over-https")]
+            ResolverType::Https { addresses, .. } => addresses,
+            #[cfg(any(feature = "dns-over-tls", feature = "native-tls"))]
+            ResolverType::GoogleTls => GOOGLE_IPS,
+            #[cfg(any(feature = "dns-over-tls", feature = "native-tls"))]
+            ResolverType::CloudflareTls => CLOUDFLARE_IPS,
+            #[cfg(any(feature = "dns-over-tls", feature = "native-tls"))]
+            ResolverType::Tls { addresses, .. } => addresses,
         }
     }
 
-    pub fn resolver(&self, ipv6_only: bool) -> Result<Resolver, Error> {
+    #[cfg(test)]
+    pub(crate) fn resolver(&self, ipv6_only: bool) -> Result<Resolver, Error> {
+        self.tuned_resolver(ipv6_only, &QueryOptions::default())
+    }
+
+    fn tuned_resolver(&self, ipv6_only: bool, options: &QueryOptions) -> Result<Resolver, Error> {
         match self {
-            ResolverType::Google => recursive_resolver(self.nameservers(), ipv6_only),
-            ResolverType::Cloudflare => recursive_resolver(self.nameservers(), ipv6_only),
-            ResolverType::Local => recursive_resolver(self.nameservers(), ipv6_only),
+            ResolverType::Google => recursive_resolver(self.nameservers(), ipv6_only, options),
+            ResolverType::Cloudflare => recursive_resolver(self.nameservers(), ipv6_only, options),
+            ResolverType::Quad9 => recursive_resolver(self.nameservers(), ipv6_only, options),
+            ResolverType::OpenDNS => recursive_resolver(self.nameservers(), ipv6_only, options),
+            ResolverType::AdGuard => recursive_resolver(self.nameservers(), ipv6_only, options),
+            ResolverType::Local => recursive_resolver(self.nameservers(), ipv6_only, options),
+            ResolverType::System => configured_resolver(
+                read_system_conf().map_err(Error::resolve)?,
+                ipv6_only,
+                options,
+            ),
+            #[cfg(unix)]
+            ResolverType::ResolvConf(path) => configured_resolver(
+                parse_resolv_conf(std::fs::read(path)?).map_err(Error::resolve)?,
+                ipv6_only,
+                options,
+            ),
+            ResolverType::Custom(addresses) if addresses.is_empty() => Err(Error::NoNameservers),
+            ResolverType::Custom(addresses) => recursive_resolver(addresses, ipv6_only, options),
+            #[cfg(any(test, feature = "test-util"))]
+            ResolverType::Mock(_) => Err(Error::NoNameservers),
+            #[cfg(feature = "dns-over-https")]
+            ResolverType::GoogleHttps => crate::ipv6_resolver(
+                NameServerConfigGroup::google_https(),
+                true,
+                ipv6_only,
+                options,
+            ),
+            #[cfg(feature = "dns-over-https")]
+            ResolverType::CloudflareHttps => crate::ipv6_resolver(
+                NameServerConfigGroup::cloudflare_https(),
+                true,
+                ipv6_only,
+                options,
+            ),
+            #[cfg(feature = "dns-over-https")]
+            ResolverType::Https { addresses, .. } if addresses.is_empty() => {
+                Err(Error::NoNameservers)
+            }
+            #[cfg(feature = "dns-over-https")]
+            ResolverType::Https {
+                addresses,
+                server_name,
+            } => crate::ipv6_resolver(
+                NameServerConfigGroup::from_ips_https(addresses, 443, server_name.clone(), true),
+                true,
+                ipv6_only,
+                options,
+            ),
+            #[cfg(any(feature = "dns-over-tls", feature = "native-tls"))]
+            ResolverType::GoogleTls => crate::ipv6_resolver(
+                NameServerConfigGroup::google_tls(),
+                true,
+                ipv6_only,
+                options,
+            ),
+            #[cfg(any(feature = "dns-over-tls", feature = "native-tls"))]
+            ResolverType::CloudflareTls => crate::ipv6_resolver(
+                NameServerConfigGroup::cloudflare_tls(),
+                true,
+                ipv6_only,
+                options,
+            ),
+            #[cfg(any(feature = "dns-over-tls", feature = "native-tls"))]
+            ResolverType::Tls { addresses, .. } if addresses.is_empty() => {
+                Err(Error::NoNameservers)
+            }
+            #[cfg(any(feature = "dns-over-tls", feature = "native-tls"))]
+            ResolverType::Tls {
+                addresses,
+                server_name,
+                port,
+            } => crate::ipv6_resolver(
+                NameServerConfigGroup::from_ips_tls(addresses, *port, server_name.clone(), true),
+                true,
+                ipv6_only,
+                options,
+            ),
         }
     }
 
-    pub fn recursive_resolver(&self, ipv6_only: bool) -> Result<RecursiveResolver, Error> {
-        self.resolver(ipv6_only).map(RecursiveResolver::from)
+    /// Recursive resolver creating authoritive resolvers with the same options.
+    pub(crate) fn recursive_resolver(
+        &self,
+        ipv6_only: bool,
+        options: &QueryOptions,
+    ) -> Result<RecursiveResolver, Error> {
+        match self {
+            #[cfg(any(test, feature = "test-util"))]
+            ResolverType::Mock(mock) => Ok(RecursiveResolver {
+                inner: Arc::new(mock.clone()),
+                options: options.clone(),
+            }),
+            _ => self
+                .tuned_resolver(ipv6_only, options)
+                .map(|resolver| RecursiveResolver::new(resolver).with_options(options.clone())),
+        }
     }
 }
 
+/// Resolver with the nameservers and options read from a resolv.conf file.
+fn configured_resolver(
+    (config, mut options): (ResolverConfig, ResolverOpts),
+    ipv6_only: bool,
+    tuning: &QueryOptions,
+) -> Result<Resolver, Error> {
+    tuning.tune(&mut options);
+    if ipv6_only {
+        options.ip_strategy = LookupIpStrategy::Ipv6Only.hickory();
+    }
+    let config = ResolverConfig::from_parts(
+        config.domain().cloned(),
+        config.search().to_vec(),
+        tuning.bind(NameServerConfigGroup::from(config.name_servers().to_vec())),
+    );
+    Resolver::new(config, options).map_err(Error::from)
+}
+
 fn aaaa_to_ipv6(aaaa: AAAA) -> IpAddr {
     IpAddr::V6(*aaaa)
 }
@@ -62,33 +283,173 @@ fn a_mapper(f: fn(A) -> IpAddr) -> impl Fn(Ipv4Lookup) -> Vec<IpAddr> {
     move |lookup| lookup.into_iter().map(f).collect()
 }
 
-fn default_ipv6_resolver_opts(recursion: bool) -> ResolverOpts {
+fn default_ipv6_resolver_opts(recursion: bool, tuning: &QueryOptions) -> ResolverOpts {
     let mut options = ResolverOpts::default();
-    options.ip_strategy = LookupIpStrategy::Ipv6Only;
+    tuning.tune(&mut options);
     options.recursion_desired = recursion;
     options.use_hosts_file = false;
+    if !recursion {
+        // Every attempt must see the records currently served by the authoritive nameserver
+        options.cache_size = 0;
+    }
     options
 }
 
-fn ipv6_resolver(group: NameServerConfigGroup, recursion: bool) -> Result<Resolver, Error> {
+fn ipv6_resolver(
+    group: NameServerConfigGroup,
+    recursion: bool,
+    tuning: &QueryOptions,
+) -> Result<Resolver, Error> {
     Resolver::new(
-        ResolverConfig::from_parts(None, vec![], group),
-        default_ipv6_resolver_opts(recursion),
+        ResolverConfig::from_parts(None, vec![], tuning.bind(group)),
+        default_ipv6_resolver_opts(recursion, tuning),
     )
     .map_err(Error::from)
 }
 
-pub struct RecursiveResolver {
-    inner: Resolver,
+/// Resolver for the authoritive nameserver at the addresses, without cache.
+fn authoritive_lookup(
+    addresses: &[IpAddr],
+    options: &QueryOptions,
+) -> Result<Arc<dyn Lookup>, Error> {
+    ipv6_resolver(authoritive_group(addresses, options), false, options)
+        .map(|resolver| Arc::new(resolver) as Arc<dyn Lookup>)
 }
 
-impl From<Resolver> for RecursiveResolver {
-    fn from(resolver: Resolver) -> Self {
-        Self { inner: resolver }
+impl Lookup for Resolver {
+    fn txt(&self, name: &str) -> Result<Vec<String>, Error> {
+        found(self.txt_lookup(name)).map(|lookup| {
+            lookup.map_or_else(Vec::new, |lookup| lookup.iter().map(txt_value).collect())
+        })
+    }
+
+    fn ns(&self, name: &str) -> Result<Vec<String>, Error> {
+        found(self.ns_lookup(name)).map(|lookup| {
+            lookup.map_or_else(Vec::new, |lookup| {
+                lookup.iter().map(ToString::to_string).collect()
+            })
+        })
+    }
+
+    fn a(&self, host_name: &str) -> Result<Vec<IpAddr>, Error> {
+        found(self.ipv4_lookup(host_name))
+            .map(|lookup| lookup.map(a_mapper(a_to_ipv4)).unwrap_or_default())
+    }
+
+    fn aaaa(&self, host_name: &str) -> Result<Vec<IpAddr>, Error> {
+        found(self.ipv6_lookup(host_name))
+            .map(|lookup| lookup.map(aaaa_mapper(aaaa_to_ipv6)).unwrap_or_default())
+    }
+
+    fn caa(&self, name: &str) -> Result<Vec<CAA>, Error> {
+        found(self.lookup(name, RecordType::CAA)).map(|lookup| {
+            lookup.map_or_else(Vec::new, |lookup| {
+                lookup.iter().filter_map(RData::as_caa).cloned().collect()
+            })
+        })
+    }
+
+    fn authoritive(
+        &self,
+        _name: &str,
+        addresses: &[IpAddr],
+        options: &QueryOptions,
+    ) -> Result<Arc<dyn Lookup>, Error> {
+        authoritive_lookup(addresses, options)
+    }
+
+    fn exists(&self, name: &str) -> Result<bool, Error> {
+        match self.lookup(name, RecordType::SOA) {
+            Ok(_) => Ok(true),
+            Err(error) => match error.kind() {
+                ResolveErrorKind::NoRecordsFound { response_code, .. } => {
+                    Ok(*response_code != ResponseCode::NXDomain)
+                }
+                _ => Err(Error::resolve(error)),
+            },
+        }
+    }
+
+    fn cname(&self, name: &str) -> Result<Option<String>, Error> {
+        found(self.lookup(name, RecordType::CNAME)).map(|lookup| {
+            lookup.and_then(|lookup| {
+                lookup
+                    .iter()
+                    .find_map(RData::as_cname)
+                    .map(|cname| cname.to_string())
+            })
+        })
+    }
+
+    fn soa(&self, zone: &str) -> Result<Option<SOA>, Error> {
+        found(self.soa_lookup(zone))
+            .map(|lookup| lookup.and_then(|lookup| lookup.iter().next().cloned()))
+    }
+
+    fn negative_ttl(&self, name: &str) -> Result<Option<Duration>, Error> {
+        match self.txt_lookup(name) {
+            Ok(_) => Ok(None),
+            Err(error) => match error.kind() {
+                ResolveErrorKind::NoRecordsFound {
+                    negative_ttl, soa, ..
+                } => Ok(negative_ttl
+                    .or_else(|| {
+                        soa.as_ref().and_then(|record| {
+                            record.data().map(|soa| soa.minimum().min(record.ttl()))
+                        })
+                    })
+                    .map(|ttl| Duration::from_secs(ttl.into()))
+                    .or(Some(Duration::ZERO))),
+                _ => Err(Error::resolve(error)),
+            },
+        }
+    }
+
+    fn records(&self, name: &str, record_type: RecordType) -> Result<Vec<Record>, Error> {
+        found(self.lookup(name, record_type))
+            .map(|lookup| lookup.map_or_else(Vec::new, |lookup| lookup.records().to_vec()))
+    }
+
+    fn clear_cache(&self) {
+        Resolver::clear_cache(self);
     }
 }
 
+/// Recursive resolver used to find the zone and the authoritive nameservers of a domain.
+pub struct RecursiveResolver {
+    inner: Arc<dyn Lookup>,
+    options: QueryOptions,
+}
+
 impl RecursiveResolver {
+    fn new(resolver: Resolver) -> Self {
+        Self {
+            inner: Arc::new(resolver),
+            options: QueryOptions::default(),
+        }
+    }
+
+    /// Recursive resolver asking the nameservers at the addresses, on port 53.
+    pub fn from_ips(addresses: &[IpAddr]) -> Result<Self, Error> {
+        ResolverType::Custom(addresses.to_vec()).recursive_resolver(false, &QueryOptions::default())
+    }
+
+    /// Recursive resolver asking the public resolver of Google.
+    pub fn google() -> Result<Self, Error> {
+        ResolverType::Google.recursive_resolver(false, &QueryOptions::default())
+    }
+
+    /// Recursive resolver asking the public resolver of Cloudflare.
+    pub fn cloudflare() -> Result<Self, Error> {
+        ResolverType::Cloudflare.recursive_resolver(false, &QueryOptions::default())
+    }
+
+    /// Options for the authoritive resolvers created by this resolver.
+    pub(crate) fn with_options(mut self, options: QueryOptions) -> Self {
+        self.options = options;
+        self
+    }
+
     pub fn authoritive_resolvers<S>(
         &self,
         domain_name: S,
@@ -104,72 +465,481 @@ impl RecursiveResolver {
         })
     }
 
-    pub fn nameservers<S>(&self, domain_name: S) -> Result<Vec<String>, Error>
+    /// False if the recursive resolver answers NXDOMAIN for name, so no records exist at
+    /// or below name.
+    pub(crate) fn exists(&self, name: &str) -> Result<bool, Error> {
+        self.inner.exists(name)
+    }
+
+    /// Follows the CNAME chain starting at name and returns the final target,
+    /// or None if name is not an alias.
+    pub fn cname_target<S>(&self, name: S) -> Result<Option<String>, Error>
+    where
+        S: AsRef<str>,
+    {
+        let mut target = None;
+        for _ in 0..MAX_CNAME_CHAIN {
+            let current = target.as_deref().unwrap_or(name.as_ref());
+            match self.cname(current)? {
+                Some(next) => target = Some(next),
+                None => return Ok(target),
+            }
+        }
+        Err(Error::CnameChain(name.as_ref().to_owned()))
+    }
+
+    fn cname(&self, name: &str) -> Result<Option<String>, Error> {
+        self.inner.cname(name)
+    }
+
+    /// Checks if name has NS records, meaning it is the apex of a zone.
+    pub fn is_zone<S>(&self, name: S) -> Result<bool, Error>
     where
         S: AsRef<str>,
     {
         self.inner
-            .ns_lookup(domain_name.as_ref())
-            .map_err(Error::from)
-            .map(|lookup| lookup.into_iter().map(|ns| ns.to_string()).collect())
+            .ns(name.as_ref())
+            .map(|nameservers| !nameservers.is_empty())
     }
 
+    /// Finds the zone name belongs to by walking up the labels
+    /// until a name with NS records is found. The top level domain is never the zone,
+    /// the challenge of a domain that does not exist would be checked in its top level domain.
+    pub fn zone<S>(&self, name: S) -> Result<String, Error>
+    where
+        S: AsRef<str>,
+    {
+        for candidate in ancestors(name.as_ref()).filter(|candidate| !is_top_level(candidate)) {
+            if self.is_zone(candidate)? {
+                return Ok(candidate.to_owned());
+            }
+        }
+        Err(Error::NoZone(name.as_ref().to_owned()))
+    }
+
+    /// How long the resolver may keep answering that the TXT record at name does not exist.
+    /// Returns None if the record exists.
+    pub fn negative_ttl<S>(&self, name: S) -> Result<Option<Duration>, Error>
+    where
+        S: AsRef<str>,
+    {
+        self.inner.clear_cache();
+        self.inner.negative_ttl(name.as_ref())
+    }
+
+    /// The TXT values the recursive resolver returns for name, following CNAME records.
+    /// The local cache is cleared first, the cache of the upstream resolver can not be bypassed.
+    pub fn txt_values<S>(&self, name: S) -> Result<Vec<String>, Error>
+    where
+        S: AsRef<str>,
+    {
+        self.inner.clear_cache();
+        self.inner.txt(name.as_ref())
+    }
+
+    /// The records of any type, like `SOA` or `DS`, the recursive resolver returns for name,
+    /// in presentation format. Meant for debugging, empty if name has no such records.
+    pub fn lookup_raw<S>(&self, record_type: &str, name: S) -> Result<Vec<String>, Error>
+    where
+        S: AsRef<str>,
+    {
+        let record_type =
+            RecordType::from_str(&record_type.to_ascii_uppercase()).map_err(Error::proto)?;
+        self.inner
+            .records(name.as_ref(), record_type)
+            .map(|records| records.iter().map(ToString::to_string).collect())
+    }
+
+    /// The CAA records at name, empty if it has none.
+    pub(crate) fn caa(&self, name: &str) -> Result<Vec<CAA>, Error> {
+        self.inner.caa(name)
+    }
+
+    pub fn nameservers<S>(&self, domain_name: S) -> Result<Vec<String>, Error>
+    where
+        S: AsRef<str>,
+    {
+        match self.inner.ns(domain_name.as_ref())? {
+            nameservers if nameservers.is_empty() => Err(Error::NoNameservers),
+            nameservers => Ok(nameservers),
+        }
+    }
+
+    /// Resolver for the addresses of the nameserver host,
+    /// the address families used depend on the ip strategy.
     pub fn authoritive_resolver<S>(&self, host_name: S) -> Result<AuthoritiveResolver, Error>
     where
         S: AsRef<str>,
     {
-        let ipv6_addresses = self
-            .inner
-            .ipv6_lookup(host_name.as_ref())
-            .map_err(Error::from)
-            .map(aaaa_mapper(aaaa_to_ipv6))?;
+        let host_name = host_name.as_ref();
+        let ip_addresess = match self.options.ip_strategy {
+            LookupIpStrategy::Ipv4Only => self.ipv4_addresses(host_name)?,
+            LookupIpStrategy::Ipv6Only => self.ipv6_addresses(host_name)?,
+            LookupIpStrategy::Ipv4AndIpv6 => {
+                let mut addresses = self.ipv6_addresses(host_name)?;
+                addresses.extend(self.ipv4_addresses(host_name)?);
+                addresses
+            }
+            LookupIpStrategy::Ipv6thenIpv4 => match self.ipv6_addresses(host_name) {
+                Ok(addresses) if !addresses.is_empty() => addresses,
+                _ => self.ipv4_addresses(host_name)?,
+            },
+            LookupIpStrategy::Ipv4thenIpv6 => match self.ipv4_addresses(host_name) {
+                Ok(addresses) if !addresses.is_empty() => addresses,
+                _ => self.ipv6_addresses(host_name)?,
+            },
+        };
 
-        let ipv4_addresses = self
-            .inner
-            .ipv4_lookup(host_name.as_ref())
-            .map_err(Error::from)
-            .map(a_mapper(a_to_ipv4))?;
+        if ip_addresess.is_empty() {
+            return Err(Error::NoAddresses(host_name.to_owned()));
+        }
+        self.authoritive_resolver_with(host_name, ip_addresess)
+    }
+
+    /// Resolver for a nameserver with known addresses, for example from glue records.
+    /// The addresses are selected with the ip strategy.
+    pub(crate) fn authoritive_resolver_at(
+        &self,
+        host_name: &str,
+        addresses: Vec<IpAddr>,
+    ) -> Result<AuthoritiveResolver, Error> {
+        let addresses = match self.options.ip_strategy {
+            LookupIpStrategy::Ipv4Only => addresses.into_iter().filter(IpAddr::is_ipv4).collect(),
+            LookupIpStrategy::Ipv6Only => addresses.into_iter().filter(IpAddr::is_ipv6).collect(),
+            _ => addresses,
+        };
+        if addresses.is_empty() {
+            return Err(Error::NoAddresses(host_name.to_owned()));
+        }
+        self.authoritive_resolver_with(host_name, addresses)
+    }
+
+    fn authoritive_resolver_with(
+        &self,
+        host_name: &str,
+        addresses: Vec<IpAddr>,
+    ) -> Result<AuthoritiveResolver, Error> {
+        self.inner
+            .authoritive(host_name, &addresses, &self.options)
+            .map(|inner| {
+                AuthoritiveResolver::new(host_name, addresses, inner, self.options.clone())
+            })
+    }
+
+    /// The ipv6 addresses of the host, empty if it has no AAAA records.
+    fn ipv6_addresses(&self, host_name: &str) -> Result<Vec<IpAddr>, Error> {
+        self.inner.aaaa(host_name)
+    }
 
-        let ip_addresess: Vec<IpAddr> = ipv6_addresses.into_iter().chain(ipv4_addresses).collect();
-        ipv6_resolver(
-            NameServerConfigGroup::from_ips_clear(ip_addresess.as_slice(), 53, false),
-            false,
+    /// The ipv4 addresses of the host, empty if it has no A records.
+    fn ipv4_addresses(&self, host_name: &str) -> Result<Vec<IpAddr>, Error> {
+        self.inner.a(host_name)
+    }
+}
+
+/// Nameserver configuration for the addresses of an authoritive nameserver,
+/// udp with a fallback to tcp unless only tcp should be used.
+fn authoritive_group(addresses: &[IpAddr], options: &QueryOptions) -> NameServerConfigGroup {
+    let group = NameServerConfigGroup::from_ips_clear(addresses, options.port, false);
+    if options.tcp_only {
+        NameServerConfigGroup::from(
+            group
+                .into_inner()
+                .into_iter()
+                .filter(|config| config.protocol == Protocol::Tcp)
+                .collect::<Vec<_>>(),
         )
-        .map(AuthoritiveResolver)
+    } else {
+        group
+    }
+}
+
+/// The value of a TXT record, the concatenation of its character-strings without any quoting.
+/// Invalid utf-8 is replaced, so it never equals a valid challenge.
+pub(crate) fn txt_value(txt: &TXT) -> String {
+    String::from_utf8_lossy(&txt.txt_data().concat()).into_owned()
+}
+
+/// The record does not exist (yet): the name does not exist (NXDOMAIN)
+/// or it has no records of the requested type (NODATA).
+pub(crate) fn is_not_found(error: &ResolveError) -> bool {
+    matches!(error.kind(), ResolveErrorKind::NoRecordsFound { .. })
+}
+
+/// Turns a lookup for a record that does not exist (yet) into None instead of an error.
+fn found<T>(result: Result<T, ResolveError>) -> Result<Option<T>, Error> {
+    match result {
+        Ok(lookup) => Ok(Some(lookup)),
+        Err(error) if is_not_found(&error) => Ok(None),
+        Err(error) => Err(Error::resolve(error)),
+    }
+}
+
+/// A name of a single label, like `com.`.
+fn is_top_level(name: &str) -> bool {
+    !name.trim_end_matches('.').contains('.')
+}
+
+/// The name followed by all its parent domains, excluding the root.
+pub(crate) fn ancestors(name: &str) -> impl Iterator<Item = &str> {
+    let mut next = Some(name).filter(|name| !name.is_empty() && *name != ".");
+    std::iter::from_fn(move || {
+        let current = next?;
+        next = current
+            .split_once('.')
+            .map(|(_, parent)| parent)
+            .filter(|parent| !parent.is_empty());
+        Some(current)
+    })
+}
+
+/// Owner name of the validation record, the label followed by the domain name.
+/// The wildcard label of a wildcard domain name is left out before a label,
+/// without a label the name is the record itself, wildcard included.
+pub(crate) fn record_name(label: &str, domain_name: &str) -> String {
+    if label.is_empty() {
+        domain_name.to_owned()
+    } else {
+        let domain_name = domain_name.strip_prefix("*.").unwrap_or(domain_name);
+        format!("{}.{}", label.trim_end_matches('.'), domain_name)
+    }
+}
+
+pub(crate) fn serves_challenges<S>(
+    values: &[String],
+    challenges: &[S],
+    ignore_extra: bool,
+) -> Result<bool, Error>
+where
+    S: AsRef<str>,
+{
+    if !ignore_extra && values.len() > challenges.len() {
+        Err(Error::MultipleAcme)
+    } else {
+        Ok(challenges
+            .iter()
+            .all(|challenge| values.iter().any(|value| value == challenge.as_ref())))
     }
 }
 
 /// Authoritive nameserver Resolver
-pub struct AuthoritiveResolver(hickory_resolver::Resolver);
+pub struct AuthoritiveResolver {
+    name: String,
+    addresses: Vec<IpAddr>,
+    inner: Arc<dyn Lookup>,
+    options: QueryOptions,
+    connections: Connections,
+}
 
 impl AuthoritiveResolver {
+    fn new(
+        name: &str,
+        addresses: Vec<IpAddr>,
+        inner: Arc<dyn Lookup>,
+        options: QueryOptions,
+    ) -> Self {
+        AuthoritiveResolver {
+            name: name.to_owned(),
+            addresses,
+            inner,
+            options,
+            connections: Connections::default(),
+        }
+    }
+
+    /// Resolver for the authoritive nameserver at the addresses, queried on port 53.
+    /// The nameserver is named after its first address.
+    pub fn from_ips(addresses: &[IpAddr]) -> Result<Self, Error> {
+        match addresses.first() {
+            Some(address) => {
+                let options = QueryOptions::default();
+                authoritive_lookup(addresses, &options).map(|inner| {
+                    Self::new(&address.to_string(), addresses.to_vec(), inner, options)
+                })
+            }
+            None => Err(Error::NoNameservers),
+        }
+    }
+
+    /// Resolver for the authoritive nameserver with the host name,
+    /// its addresses are found with the public resolver of Google.
+    pub fn from_host_name<S>(host_name: S) -> Result<Self, Error>
+    where
+        S: AsRef<str>,
+    {
+        RecursiveResolver::google()?.authoritive_resolver(host_name)
+    }
+
+    /// A resolver for every address of the nameserver, so each address is checked on its own.
+    /// Anycast addresses of the same nameserver can be served by instances that are not in sync.
+    pub fn per_address(&self) -> Result<Vec<AuthoritiveResolver>, Error> {
+        self.addresses
+            .iter()
+            .map(|address| {
+                self.inner
+                    .authoritive(&self.name, &[*address], &self.options)
+                    .map(|inner| {
+                        AuthoritiveResolver::new(
+                            &self.name,
+                            vec![*address],
+                            inner,
+                            self.options.clone(),
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// Host name of the authoritive nameserver
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Addresses of the authoritive nameserver
+    pub fn addresses(&self) -> &[IpAddr] {
+        &self.addresses
+    }
+
     pub fn has_single_acme<S>(&self, domain_name: S, challenge: S) -> Result<bool, Error>
     where
         S: AsRef<str>,
     {
-        self.0.clear_cache();
-        match self
-            .0
-            .txt_lookup(format!("_acme-challenge.{}", domain_name.as_ref()))
-        {
-            Ok(lookup) => {
-                let count = lookup.iter().count();
-                if count == 1 {
-                    Ok(lookup
-                        .iter()
-                        .any(|txt| txt.to_string() == challenge.as_ref()))
-                } else {
-                    Err(Error::MultipleAcme)
-                }
-            }
-            Err(error) => {
-                if let ResolveErrorKind::NoRecordsFound { .. } = error.kind() {
-                    Ok(false)
-                } else {
-                    Err(Error::from(error))
+        self.has_acme(domain_name.as_ref(), &[challenge.as_ref()], false)
+    }
+
+    /// Checks that all challenges are served.
+    /// Unless ignore_extra is set, serving other values is an error.
+    pub fn has_acme<S>(
+        &self,
+        domain_name: S,
+        challenges: &[S],
+        ignore_extra: bool,
+    ) -> Result<bool, Error>
+    where
+        S: AsRef<str>,
+    {
+        self.challenges(domain_name)
+            .and_then(|values| serves_challenges(&values, challenges, ignore_extra))
+    }
+
+    /// The acme challenges currently served for the domain name
+    pub fn challenges<S>(&self, domain_name: S) -> Result<Vec<String>, Error>
+    where
+        S: AsRef<str>,
+    {
+        self.txt_values(record_name(ACME_CHALLENGE_LABEL, domain_name.as_ref()))
+    }
+
+    /// The SOA record of the zone, None if the nameserver has none
+    pub(crate) fn soa<S>(&self, zone: S) -> Result<Option<SOA>, Error>
+    where
+        S: AsRef<str>,
+    {
+        let _permit = self.options.throttle();
+        self.inner.soa(zone.as_ref())
+    }
+
+    /// The TXT values for the record name, queried directly on the addresses of the nameserver.
+    /// Fails with [`Error::NotAuthoritative`] if the answer does not have the authoritive answer flag,
+    /// which happens when a middlebox answers on behalf of the nameserver.
+    pub fn authoritative_txt_values<S>(&self, record_name: S) -> Result<Vec<String>, Error>
+    where
+        S: AsRef<str>,
+    {
+        let name = Name::from_ascii(record_name.as_ref()).map_err(Error::proto)?;
+        self.authoritative_answer(&name, RecordType::TXT)
+            .map(|response| {
+                response
+                    .answers()
+                    .iter()
+                    .filter_map(|record| record.data().and_then(RData::as_txt))
+                    .map(txt_value)
+                    .collect()
+            })
+    }
+
+    /// The values of the records of the type at name, queried directly on the addresses
+    /// of the nameserver. When authoritative the answer must have the authoritive answer flag.
+    pub(crate) fn record_values(
+        &self,
+        record_type: record::RecordType,
+        name: &str,
+        authoritative: bool,
+    ) -> Result<Vec<String>, Error> {
+        let name = Name::from_ascii(name).map_err(Error::proto)?;
+        let response = if authoritative {
+            self.authoritative_answer(&name, record_type.hickory())?
+        } else {
+            self.answered(self.query(&name, record_type.hickory())?)?
+        };
+        Ok(record_type.values(&response))
+    }
+
+    /// Sends a query directly to the nameserver and checks that the answer is authoritive
+    /// and that the name exists or does not exist, so the answers can be trusted.
+    pub(crate) fn authoritative_answer(
+        &self,
+        name: &Name,
+        record_type: RecordType,
+    ) -> Result<Message, Error> {
+        let response = self.query(name, record_type)?;
+        if !response.header().authoritative() {
+            return Err(Error::NotAuthoritative(self.name.clone()));
+        }
+        self.answered(response)
+    }
+
+    /// The response if the name exists or does not exist, an error for an answer like
+    /// SERVFAIL or REFUSED that says nothing about the records.
+    fn answered(&self, response: Message) -> Result<Message, Error> {
+        match response.response_code() {
+            ResponseCode::NoError | ResponseCode::NXDomain => Ok(response),
+            response_code => Err(Error::response(self.name.clone(), response_code)),
+        }
+    }
+
+    /// Sends a query directly to the addresses of the nameserver, until one of them answers.
+    pub(crate) fn query(&self, name: &Name, record_type: RecordType) -> Result<Message, Error> {
+        let mut last_error = Error::NoNameservers;
+        for address in &self.addresses {
+            let _permit = self.options.throttle();
+            match query(
+                SocketAddr::new(*address, self.options.port),
+                name,
+                record_type,
+                &self.options,
+                &self.connections,
+            ) {
+                Ok(response) => return Ok(response),
+                Err(error) => {
+                    tracing::debug!("Query {} on {} failed: {}", name, address, error);
+                    last_error = error;
                 }
             }
         }
+        Err(last_error)
+    }
+
+    /// The records of any type, like `SOA` or `DS`, in the answer of the nameserver for name,
+    /// in presentation format. The query is sent directly to the nameserver. Meant for debugging.
+    pub fn lookup_raw<S>(&self, record_type: &str, name: S) -> Result<Vec<String>, Error>
+    where
+        S: AsRef<str>,
+    {
+        let record_type =
+            RecordType::from_str(&record_type.to_ascii_uppercase()).map_err(Error::proto)?;
+        let name = Name::from_ascii(name.as_ref()).map_err(Error::proto)?;
+        self.query(&name, record_type)
+            .map(|response| response.answers().iter().map(ToString::to_string).collect())
+    }
+
+    /// The TXT values currently served for the record name
+    pub fn txt_values<S>(&self, record_name: S) -> Result<Vec<String>, Error>
+    where
+        S: AsRef<str>,
+    {
+        let _permit = self.options.throttle();
+        self.inner.txt(record_name.as_ref())
     }
 }
 
@@ -177,9 +947,21 @@ impl AuthoritiveResolver {
 mod test {
     use std::convert::identity;
 
-    use crate::{error::Error, ResolverType};
+    use crate::{error::Error, query::QueryOptions, ResolverType};
+
+    use hickory_resolver::{
+        config::Protocol,
+        error::{ResolveError, ResolveErrorKind},
+        proto::{
+            op::{Query, ResponseCode},
+            rr::{rdata::TXT, Name, RecordType},
+        },
+    };
 
-    use super::RecursiveResolver;
+    use super::{
+        ancestors, authoritive_group, default_ipv6_resolver_opts, is_not_found, record_name,
+        serves_challenges, txt_value, AuthoritiveResolver, RecursiveResolver,
+    };
 
     const DOMAIN_NAME: &str = "paulmin.nl.";
 
@@ -187,15 +969,159 @@ mod test {
     fn google_nameserver() {
         let resolver = ResolverType::Google
             .resolver(true)
-            .map(RecursiveResolver::from);
+            .map(RecursiveResolver::new);
         assert!(resolver.is_ok());
     }
 
+    #[test]
+    fn resolver_per_address() {
+        let resolver = ResolverType::Google
+            .resolver(false)
+            .map(RecursiveResolver::new)
+            .unwrap()
+            .authoritive_resolver("ns0.transip.net.")
+            .unwrap();
+        let resolvers = resolver.per_address().unwrap();
+        assert_eq!(resolvers.len(), resolver.addresses().len());
+        for (single, address) in resolvers.iter().zip(resolver.addresses()) {
+            assert_eq!(single.name(), "ns0.transip.net.");
+            assert_eq!(single.addresses(), &[*address]);
+        }
+    }
+
+    #[test]
+    fn public_resolvers() {
+        for resolver_type in [
+            ResolverType::Quad9,
+            ResolverType::OpenDNS,
+            ResolverType::AdGuard,
+        ] {
+            let resolver = resolver_type
+                .resolver(false)
+                .map(RecursiveResolver::new)
+                .unwrap();
+            assert!(resolver.is_zone(DOMAIN_NAME).unwrap());
+        }
+    }
+
+    #[test]
+    fn custom_resolver() {
+        let custom = ResolverType::Custom(vec!["8.8.8.8".parse().unwrap()]);
+        assert!(custom.resolver(false).is_ok());
+        assert!(matches!(
+            ResolverType::Custom(vec![]).resolver(false),
+            Err(Error::NoNameservers)
+        ));
+    }
+
+    #[test]
+    fn authoritive_resolver_from_ips() {
+        let addresses = [
+            "2a01:7c8:7000:195::195".parse().unwrap(),
+            "195.135.195.195".parse().unwrap(),
+        ];
+        let resolver = AuthoritiveResolver::from_ips(&addresses).unwrap();
+        assert_eq!(resolver.name(), "2a01:7c8:7000:195::195");
+        assert_eq!(resolver.addresses(), &addresses);
+        assert!(matches!(
+            AuthoritiveResolver::from_ips(&[]),
+            Err(Error::NoNameservers)
+        ));
+    }
+
+    #[test]
+    fn raw_lookup() {
+        let resolver = RecursiveResolver::google().unwrap();
+        assert!(matches!(
+            resolver.lookup_raw("NOPE", DOMAIN_NAME),
+            Err(Error::Proto(_))
+        ));
+        let records = resolver.lookup_raw("soa", DOMAIN_NAME).unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].starts_with(DOMAIN_NAME));
+    }
+
+    #[test]
+    fn recursive_resolver_constructors() {
+        assert!(RecursiveResolver::google().is_ok());
+        assert!(RecursiveResolver::cloudflare().is_ok());
+        assert!(RecursiveResolver::from_ips(&["9.9.9.9".parse().unwrap()]).is_ok());
+        assert!(matches!(
+            RecursiveResolver::from_ips(&[]),
+            Err(Error::NoNameservers)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolv_conf_path() {
+        let path = std::env::temp_dir().join("acme-validation-propagation-resolv.conf");
+        std::fs::write(
+            &path,
+            "nameserver 8.8.8.8\nnameserver 2001:4860:4860::8888\n",
+        )
+        .unwrap();
+        assert!(ResolverType::ResolvConf(path.clone())
+            .resolver(false)
+            .is_ok());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            ResolverType::ResolvConf(path).resolver(false),
+            Err(Error::IO(_))
+        ));
+    }
+
+    #[test]
+    fn authoritive_resolvers_do_not_cache() {
+        let options = QueryOptions::default();
+        assert_eq!(default_ipv6_resolver_opts(false, &options).cache_size, 0);
+        assert!(default_ipv6_resolver_opts(true, &options).cache_size > 0);
+    }
+
+    #[test]
+    fn tcp_only_group() {
+        let addresses = ["195.135.195.195".parse().unwrap()];
+        let options = QueryOptions {
+            tcp_only: true,
+            ..Default::default()
+        };
+        let group = authoritive_group(&addresses, &options);
+        assert!(!group.is_empty());
+        assert!(group.iter().all(|config| config.protocol == Protocol::Tcp));
+        assert!(authoritive_group(&addresses, &QueryOptions::default())
+            .iter()
+            .any(|config| config.protocol == Protocol::Udp));
+    }
+
+    #[test]
+    fn custom_port_group() {
+        let addresses = ["127.0.0.1".parse().unwrap()];
+        let options = QueryOptions {
+            port: 5353,
+            ..Default::default()
+        };
+        assert!(authoritive_group(&addresses, &options)
+            .iter()
+            .all(|config| config.socket_addr.port() == 5353));
+    }
+
+    #[test]
+    fn nameserver_without_addresses() {
+        let resolver = ResolverType::Google
+            .resolver(false)
+            .map(RecursiveResolver::new)
+            .unwrap();
+        assert!(matches!(
+            resolver.authoritive_resolver("nonexistent.paulmin.nl."),
+            Err(Error::NoAddresses(_))
+        ));
+    }
+
     #[test]
     fn paul_min_nl() {
         let resolver = ResolverType::Google
             .resolver(true)
-            .map(RecursiveResolver::from)
+            .map(RecursiveResolver::new)
             .unwrap();
 
         let mut names = resolver.nameservers(DOMAIN_NAME).unwrap();
@@ -211,11 +1137,118 @@ mod test {
         )
     }
 
+    #[test]
+    fn serves_all_challenges() {
+        let values = vec!["apex".to_owned(), "wildcard".to_owned()];
+        assert!(serves_challenges(&values, &["wildcard", "apex"], false).unwrap());
+        assert!(!serves_challenges(&values, &["apex", "other"], false).unwrap());
+        assert!(!serves_challenges(&values[..1], &["apex", "wildcard"], false).unwrap());
+        assert!(matches!(
+            serves_challenges(&values, &["apex"], false),
+            Err(Error::MultipleAcme)
+        ));
+    }
+
+    fn no_records(response_code: ResponseCode) -> ResolveError {
+        ResolveError::from(ResolveErrorKind::NoRecordsFound {
+            query: Box::new(Query::query(
+                Name::from_ascii("_acme-challenge.paulmin.nl.").unwrap(),
+                RecordType::TXT,
+            )),
+            soa: None,
+            negative_ttl: None,
+            response_code,
+            trusted: true,
+        })
+    }
+
+    #[test]
+    fn multi_string_txt() {
+        let txt = TXT::new(vec![
+            "lCM7cZyQXcVHK2nnW3jjAh".to_owned(),
+            "NT3Fvm18UN-kWZZknKoYM".to_owned(),
+        ]);
+        assert_eq!(
+            txt_value(&txt),
+            "lCM7cZyQXcVHK2nnW3jjAhNT3Fvm18UN-kWZZknKoYM"
+        );
+    }
+
+    #[test]
+    fn txt_with_quote() {
+        let txt = TXT::new(vec!["\"quoted\"".to_owned()]);
+        assert_eq!(txt_value(&txt), "\"quoted\"");
+    }
+
+    #[test]
+    fn not_found_errors() {
+        assert!(is_not_found(&no_records(ResponseCode::NXDomain)));
+        assert!(is_not_found(&no_records(ResponseCode::NoError)));
+        assert!(!is_not_found(&ResolveError::from(
+            ResolveErrorKind::Message("SERVFAIL")
+        )));
+    }
+
+    #[test]
+    fn ancestor_names() {
+        assert_eq!(
+            ancestors("host.sub.example.com.").collect::<Vec<_>>(),
+            vec![
+                "host.sub.example.com.",
+                "sub.example.com.",
+                "example.com.",
+                "com."
+            ]
+        );
+        assert_eq!(
+            ancestors("example.com").collect::<Vec<_>>(),
+            vec!["example.com", "com"]
+        );
+        assert_eq!(ancestors(".").count(), 0);
+    }
+
+    #[test]
+    fn zone_of_subdomain() {
+        let resolver = ResolverType::Google
+            .resolver(true)
+            .map(RecursiveResolver::new)
+            .unwrap();
+        assert_eq!(
+            resolver.zone("_acme-challenge.paulmin.nl.").unwrap(),
+            DOMAIN_NAME
+        );
+    }
+
+    #[test]
+    fn record_names() {
+        assert_eq!(
+            record_name("_acme-challenge", DOMAIN_NAME),
+            "_acme-challenge.paulmin.nl."
+        );
+        assert_eq!(
+            record_name("_delegated-challenge.", DOMAIN_NAME),
+            "_delegated-challenge.paulmin.nl."
+        );
+        assert_eq!(record_name("", DOMAIN_NAME), DOMAIN_NAME);
+        assert_eq!(
+            record_name("_acme-challenge", "*.paulmin.nl."),
+            "_acme-challenge.paulmin.nl."
+        );
+        assert_eq!(record_name("", "*.paulmin.nl."), "*.paulmin.nl.");
+    }
+
+    #[test]
+    fn ignore_extra_challenges() {
+        let values = vec!["stale".to_owned(), "fresh".to_owned()];
+        assert!(serves_challenges(&values, &["fresh"], true).unwrap());
+        assert!(!serves_challenges(&values, &["other"], true).unwrap());
+    }
+
     #[allow(dead_code)]
     fn has_acme_challenge() {
         let resolvers = ResolverType::Google
             .resolver(true)
-            .map(RecursiveResolver::from)
+            .map(RecursiveResolver::new)
             .unwrap()
             .authoritive_resolvers(DOMAIN_NAME)
             .unwrap();
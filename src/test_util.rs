@@ -0,0 +1,301 @@
+//! Authoritive nameservers on localhost with scripted TXT records,
+//! to test waiting without the public dns.
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use hickory_server::{
+    authority::MessageResponseBuilder,
+    proto::{
+        op::{Header, ResponseCode},
+        rr::{rdata::TXT, Name, RData, Record, RecordType},
+    },
+    server::{Request, RequestHandler, ResponseHandler, ResponseInfo},
+    ServerFuture,
+};
+use tokio::{
+    net::{TcpListener, UdpSocket},
+    runtime::Runtime,
+};
+
+use crate::Propagation;
+
+/// Time to live of the answers.
+const TTL: u32 = 1;
+/// Time a tcp connection may stay idle.
+const TCP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Number of nameservers from 127.0.0.1 up to 127.0.0.254.
+const MAX_NAMESERVERS: usize = 254;
+/// Time between two attempts of [`MockNameservers::propagation`].
+const INTERVAL: Duration = Duration::from_millis(100);
+
+/// TXT record served from `from` until `until`.
+struct Entry {
+    name: String,
+    value: String,
+    from: Instant,
+    until: Option<Instant>,
+}
+
+/// An authoritive nameserver of [`MockNameservers`].
+pub struct MockNameserver {
+    address: IpAddr,
+    records: Arc<Mutex<Vec<Entry>>>,
+}
+
+impl MockNameserver {
+    pub fn address(&self) -> IpAddr {
+        self.address
+    }
+
+    /// Serves a TXT record with the value from now on.
+    pub fn txt(&self, name: &str, value: &str) -> &Self {
+        self.txt_after(name, value, Duration::ZERO)
+    }
+
+    /// Serves a TXT record with the value once the delay has passed, like a nameserver
+    /// that has not transferred the zone yet.
+    pub fn txt_after(&self, name: &str, value: &str, delay: Duration) -> &Self {
+        self.lock().push(Entry {
+            name: normalize(name),
+            value: value.to_owned(),
+            from: Instant::now() + delay,
+            until: None,
+        });
+        self
+    }
+
+    /// Stops serving the TXT record with the value once the delay has passed.
+    pub fn remove_txt_after(&self, name: &str, value: &str, delay: Duration) -> &Self {
+        let name = normalize(name);
+        self.lock()
+            .iter_mut()
+            .filter(|entry| entry.name == name && entry.value == value)
+            .for_each(|entry| entry.until = Some(Instant::now() + delay));
+        self
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Entry>> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Authoritive nameservers on 127.0.0.1, 127.0.0.2 and so on, all on the same port,
+/// answering TXT queries over udp and tcp. They stop when dropped.
+///
+/// Binding to other loopback addresses than 127.0.0.1 works on linux,
+/// other platforms may need them to be configured.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use acme_validation_propagation::test_util::MockNameservers;
+///
+/// let nameservers = MockNameservers::start(2).unwrap();
+/// nameservers.txt("_acme-challenge.example.com", "89823875");
+/// nameservers
+///     .server(1)
+///     .txt_after("_acme-challenge.example.com", "89823875", Duration::from_secs(1));
+/// nameservers
+///     .propagation()
+///     .wait("example.com", "89823875")
+///     .unwrap();
+/// ```
+pub struct MockNameservers {
+    port: u16,
+    servers: Vec<MockNameserver>,
+    _runtime: Runtime,
+}
+
+impl MockNameservers {
+    /// Starts count nameservers, at most 254 as they are numbered in the last byte
+    /// of the address.
+    pub fn start(count: usize) -> io::Result<Self> {
+        if count > MAX_NAMESERVERS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("At most {MAX_NAMESERVERS} nameservers, not {count}"),
+            ));
+        }
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        let (port, servers) = runtime.block_on(async {
+            let mut port = 0;
+            let mut servers = Vec::with_capacity(count);
+            for last in (1..=u8::MAX).take(count) {
+                let address = IpAddr::V4(Ipv4Addr::new(127, 0, 0, last));
+                let udp = UdpSocket::bind(SocketAddr::new(address, port)).await?;
+                port = udp.local_addr()?.port();
+                let tcp = TcpListener::bind(SocketAddr::new(address, port)).await?;
+                let records = Arc::new(Mutex::new(vec![]));
+                let mut server = ServerFuture::new(Handler {
+                    records: records.clone(),
+                });
+                server.register_socket(udp);
+                server.register_listener(tcp, TCP_TIMEOUT);
+                tokio::spawn(async move { server.block_until_done().await });
+                servers.push(MockNameserver { address, records });
+            }
+            io::Result::Ok((port, servers))
+        })?;
+        Ok(Self {
+            port,
+            servers,
+            _runtime: runtime,
+        })
+    }
+
+    /// Port all nameservers listen on, see [`Propagation::nameserver_port`].
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Addresses of the nameservers, see [`Propagation::servers`].
+    pub fn addresses(&self) -> Vec<IpAddr> {
+        self.servers.iter().map(MockNameserver::address).collect()
+    }
+
+    /// The nameserver with the index, in the order of [`MockNameservers::addresses`].
+    pub fn server(&self, index: usize) -> &MockNameserver {
+        &self.servers[index]
+    }
+
+    /// Serves a TXT record with the value on all nameservers from now on.
+    pub fn txt(&self, name: &str, value: &str) -> &Self {
+        self.servers.iter().for_each(|server| {
+            server.txt(name, value);
+        });
+        self
+    }
+
+    /// Checks these nameservers right away and every 100 milliseconds.
+    pub fn propagation(&self) -> Propagation {
+        Propagation::new()
+            .servers(&self.addresses())
+            .nameserver_port(self.port)
+            .initial_delay(Duration::ZERO)
+            .interval(INTERVAL)
+    }
+}
+
+struct Handler {
+    records: Arc<Mutex<Vec<Entry>>>,
+}
+
+#[async_trait::async_trait]
+impl RequestHandler for Handler {
+    async fn handle_request<R: ResponseHandler>(
+        &self,
+        request: &Request,
+        mut response_handle: R,
+    ) -> ResponseInfo {
+        let query = request.request_info().query;
+        let name = normalize(&query.name().to_string());
+        let now = Instant::now();
+        let answers = if query.query_type() == RecordType::TXT {
+            self.records
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .filter(|entry| {
+                    entry.name == name
+                        && entry.from <= now
+                        && entry.until.map_or(true, |until| now < until)
+                })
+                .map(|entry| {
+                    Record::from_rdata(
+                        Name::from(query.name().clone()),
+                        TTL,
+                        RData::TXT(TXT::new(vec![entry.value.clone()])),
+                    )
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        let mut header = Header::response_from_request(request.header());
+        header.set_authoritative(true);
+        let response = MessageResponseBuilder::from_message_request(request).build(
+            header,
+            answers.iter(),
+            &[],
+            &[],
+            &[],
+        );
+        match response_handle.send_response(response).await {
+            Ok(info) => info,
+            Err(error) => {
+                tracing::warn!("Answering {} failed: {}", name, error);
+                let mut header = Header::response_from_request(request.header());
+                header.set_response_code(ResponseCode::ServFail);
+                header.into()
+            }
+        }
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::MockNameservers;
+    use crate::Error;
+
+    const RECORD: &str = "_acme-challenge.paulmin.nl";
+
+    #[test]
+    fn appears_after_delay() {
+        let nameservers = MockNameservers::start(3).unwrap();
+        nameservers.server(0).txt(RECORD, "JaJaNeeNee");
+        nameservers.server(1).txt(RECORD, "JaJaNeeNee");
+        nameservers
+            .server(2)
+            .txt_after(RECORD, "JaJaNeeNee", Duration::from_millis(300));
+        let report = nameservers
+            .propagation()
+            .wait_report("paulmin.nl", "JaJaNeeNee")
+            .unwrap();
+        assert!(report.attempts > 1);
+    }
+
+    #[test]
+    fn lagging() {
+        let nameservers = MockNameservers::start(2).unwrap();
+        nameservers.server(0).txt(RECORD, "JaJaNeeNee");
+        let result = nameservers
+            .propagation()
+            .retries(2)
+            .wait("paulmin.nl", "JaJaNeeNee");
+        assert!(matches!(result, Err(Error::AcmeChallege(lagging)) if lagging.len() == 1));
+    }
+
+    #[test]
+    fn too_many() {
+        let error = MockNameservers::start(255).err().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn removed() {
+        let nameservers = MockNameservers::start(2).unwrap();
+        nameservers.txt(RECORD, "JaJaNeeNee");
+        nameservers
+            .server(1)
+            .remove_txt_after(RECORD, "JaJaNeeNee", Duration::from_millis(200));
+        nameservers
+            .server(0)
+            .remove_txt_after(RECORD, "JaJaNeeNee", Duration::ZERO);
+        nameservers
+            .propagation()
+            .wait_absent("paulmin.nl", "JaJaNeeNee")
+            .unwrap();
+    }
+}